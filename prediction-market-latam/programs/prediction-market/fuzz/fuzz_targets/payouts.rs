@@ -0,0 +1,305 @@
+//! Random create/bet/resolve/claim/refund/close/finalize sequences against a
+//! single market.
+//!
+//! A shadow model tracks every bet and predicts which instructions must
+//! succeed. After each step the target asserts that the market stays solvent
+//! for every unclaimed winner, that no bet is paid twice, that finalizing
+//! only sweeps rounding dust, and that lamports are conserved across all
+//! accounts involved.
+//!
+//! Run with `cargo +nightly fuzz run payouts` from `programs/prediction-market`.
+
+#![no_main]
+
+#[path = "../svm/mod.rs"]
+mod svm;
+
+use anchor_lang::prelude::*;
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use prediction_market::Market;
+use svm::ix::*;
+use svm::Svm;
+
+const SOL: u64 = 1_000_000_000;
+const MIN_BET: u64 = 10_000_000;
+const USERS: usize = 8;
+const DURATION: i64 = 86_400;
+
+#[derive(Arbitrary, Debug)]
+enum Action {
+    Bet { user: u8, amount: u64, yes: bool },
+    Warp { seconds: u32 },
+    Resolve { outcome: bool },
+    Claim { user: u8 },
+    Refund { user: u8 },
+    CloseBet { user: u8 },
+    Finalize,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Position {
+    amount: u64,
+    yes: bool,
+    claimed: bool,
+    closed: bool,
+    /// Booked in the bettor's profile, which finalizing waits for
+    recorded: bool,
+}
+
+struct Model {
+    positions: [Option<Position>; USERS],
+    outcome: Option<bool>,
+    /// Resolved to an outcome nobody backed, so every stake is refunded
+    invalidated: bool,
+    finalized: bool,
+    paid_out: u64,
+}
+
+impl Model {
+    fn pools(&self) -> (u64, u64) {
+        self.positions
+            .iter()
+            .flatten()
+            .fold((0, 0), |(yes, no), position| {
+                if position.yes {
+                    (yes + position.amount, no)
+                } else {
+                    (yes, no + position.amount)
+                }
+            })
+    }
+
+    fn is_winner(&self, position: &Position) -> bool {
+        self.outcome.is_some() && (self.invalidated || self.outcome == Some(position.yes))
+    }
+
+    /// What the position is owed under the program's pro-rata formula, or
+    /// its stake back once invalidated.
+    fn entitlement(&self, position: &Position) -> u64 {
+        if self.invalidated {
+            return position.amount;
+        }
+        let (yes, no) = self.pools();
+        let winning_pool = if position.yes { yes } else { no };
+        (position.amount as u128 * (yes + no) as u128 / winning_pool as u128) as u64
+    }
+
+    fn outstanding(&self) -> u64 {
+        self.positions
+            .iter()
+            .flatten()
+            .filter(|position| self.is_winner(position) && !position.claimed)
+            .map(|position| self.entitlement(position))
+            .sum()
+    }
+}
+
+fuzz_target!(|actions: Vec<Action>| {
+    let mut svm = Svm::new();
+    let admin = svm.funded_account(10 * SOL);
+    svm.process(initialize_ix(admin), &[admin]).unwrap();
+    svm.process(initialize_config_ix(admin), &[admin]).unwrap();
+    let authority = svm.funded_account(10 * SOL);
+    let users: Vec<Pubkey> = (0..USERS)
+        .map(|_| svm.funded_account(1_000 * SOL))
+        .collect();
+    let market = Pubkey::new_unique();
+    let end_time = svm.now() + DURATION;
+    svm.process(
+        create_market_ix(
+            market,
+            authority,
+            &MarketParams::new("Fuzzed market", end_time),
+        ),
+        &[authority, market],
+    )
+    .unwrap();
+
+    let treasury = treasury_pda();
+    let mut tracked: Vec<Pubkey> = vec![authority, market, treasury];
+    tracked.extend(&users);
+    tracked.extend(users.iter().map(|user| bet_pda(user, &market)));
+    tracked.extend(users.iter().map(profile_pda));
+    let total_lamports =
+        |svm: &Svm| -> u128 { tracked.iter().map(|key| svm.lamports(key) as u128).sum() };
+    let genesis_total = total_lamports(&svm);
+    let market_rent = svm.lamports(&market);
+    let treasury_genesis = svm.lamports(&treasury);
+
+    let mut model = Model {
+        positions: [None; USERS],
+        outcome: None,
+        invalidated: false,
+        finalized: false,
+        paid_out: 0,
+    };
+
+    for action in actions.into_iter().take(64) {
+        match action {
+            Action::Bet { user, amount, yes } => {
+                let index = user as usize % USERS;
+                let amount = amount % (100 * SOL);
+                let expected = model.positions[index].is_none()
+                    && model.outcome.is_none()
+                    && svm.now() < end_time
+                    && amount >= MIN_BET;
+                let result = svm.process(
+                    place_bet_ix(users[index], market, amount, yes),
+                    &[users[index]],
+                );
+                assert_eq!(result.is_ok(), expected, "bet {result:?}");
+                if expected {
+                    model.positions[index] = Some(Position {
+                        amount,
+                        yes,
+                        ..Position::default()
+                    });
+                }
+            }
+            Action::Warp { seconds } => {
+                let now = svm.now();
+                svm.warp_to(now + seconds as i64);
+            }
+            Action::Resolve { outcome } => {
+                let expected = model.outcome.is_none() && !model.finalized && svm.now() >= end_time;
+                let result =
+                    svm.process(resolve_market_ix(market, authority, outcome), &[authority]);
+                assert_eq!(result.is_ok(), expected, "resolve {result:?}");
+                if expected {
+                    let (yes, no) = model.pools();
+                    let (winning, losing) = if outcome { (yes, no) } else { (no, yes) };
+                    model.outcome = Some(outcome);
+                    model.invalidated = winning == 0 && losing > 0;
+                    assert_eq!(svm.fetch::<Market>(&market).invalidated, model.invalidated);
+                }
+            }
+            Action::Claim { user } => {
+                let index = user as usize % USERS;
+                let before = svm.lamports(&users[index]);
+                let result = svm.process(claim_winnings_ix(users[index], market), &[users[index]]);
+                let claimable = match model.positions[index] {
+                    Some(position) => {
+                        model.is_winner(&position)
+                            && !model.invalidated
+                            && !position.claimed
+                            && !position.closed
+                            && !model.finalized
+                    }
+                    None => false,
+                };
+                if claimable {
+                    let owed = model.entitlement(&model.positions[index].unwrap());
+                    assert!(result.is_ok(), "claim {result:?}");
+                    assert_eq!(svm.lamports(&users[index]) - before, owed);
+                    let position = model.positions[index].as_mut().unwrap();
+                    position.claimed = true;
+                    position.recorded = true;
+                    model.paid_out += owed;
+                } else {
+                    assert!(result.is_err(), "unexpected payout to user {index}");
+                    assert_eq!(svm.lamports(&users[index]), before);
+                }
+            }
+            Action::Refund { user } => {
+                let index = user as usize % USERS;
+                let before = svm.lamports(&users[index]);
+                let result = svm.process(claim_refund_ix(users[index], market), &[users[index]]);
+                let refundable = match model.positions[index] {
+                    Some(position) => {
+                        model.invalidated
+                            && !position.claimed
+                            && !position.closed
+                            && !model.finalized
+                    }
+                    None => false,
+                };
+                if refundable {
+                    let position = model.positions[index].as_mut().unwrap();
+                    assert!(result.is_ok(), "refund {result:?}");
+                    assert_eq!(svm.lamports(&users[index]) - before, position.amount);
+                    position.claimed = true;
+                    model.paid_out += position.amount;
+                } else {
+                    assert!(result.is_err(), "unexpected refund to user {index}");
+                    assert_eq!(svm.lamports(&users[index]), before);
+                }
+            }
+            Action::CloseBet { user } => {
+                let index = user as usize % USERS;
+                let result = svm.process(close_bet_ix(users[index], market), &[users[index]]);
+                let expected = match model.positions[index] {
+                    Some(position) => {
+                        !position.closed
+                            && (model.finalized
+                                || (model.outcome.is_some()
+                                    && (position.claimed || !model.is_winner(&position))))
+                    }
+                    None => false,
+                };
+                assert_eq!(result.is_ok(), expected, "close bet {result:?}");
+                if expected {
+                    let recorded = !model.finalized && !model.invalidated;
+                    let position = model.positions[index].as_mut().unwrap();
+                    position.closed = true;
+                    position.recorded |= recorded;
+                }
+            }
+            Action::Finalize => {
+                let settled = model.outcome.is_some() && model.outstanding() == 0;
+                let recorded = model.invalidated
+                    || model
+                        .positions
+                        .iter()
+                        .flatten()
+                        .all(|position| position.recorded);
+                let expected = settled && recorded && !model.finalized;
+                let result = svm.process(finalize_market_ix(market, authority), &[]);
+                assert_eq!(result.is_ok(), expected, "finalize {result:?}");
+                if expected {
+                    model.finalized = true;
+                    // Only rounding dust may be swept
+                    let (yes, no) = model.pools();
+                    let swept = svm.lamports(&treasury) - treasury_genesis;
+                    assert_eq!(swept, yes + no - model.paid_out);
+                    let winners = model
+                        .positions
+                        .iter()
+                        .flatten()
+                        .filter(|position| model.is_winner(position))
+                        .count() as u64;
+                    assert!(
+                        winners == 0 || swept < winners,
+                        "swept {swept} from {winners} winners"
+                    );
+                }
+            }
+        }
+
+        let (yes, no) = model.pools();
+        let pool = yes + no;
+        assert!(
+            model.paid_out <= pool,
+            "paid {} from a pool of {pool}",
+            model.paid_out
+        );
+        assert_eq!(total_lamports(&svm), genesis_total);
+
+        if model.finalized {
+            assert!(svm.account(&market).is_none());
+            continue;
+        }
+
+        let state: Market = svm.fetch(&market);
+        assert_eq!((state.yes_amount, state.no_amount), (yes, no));
+        assert_eq!(state.resolved, model.outcome.is_some());
+
+        // Everything staked is either still in the market or was paid out,
+        // and the market can still cover every winner who has not claimed.
+        assert_eq!(svm.lamports(&market), market_rent + pool - model.paid_out);
+        assert!(
+            model.paid_out + model.outstanding() <= pool,
+            "market is insolvent"
+        );
+    }
+});
@@ -0,0 +1,676 @@
+//! Minimal in-process SVM used by the fuzz targets.
+//!
+//! The program is driven through its Anchor `entry` function on the host, the
+//! same way the BPF loader would call it: every account is laid out in a
+//! buffer that mirrors the loader's serialization (so `realloc`, `assign` and
+//! `close` behave as on-chain), sysvars and CPIs are served by syscall stubs,
+//! and the runtime's lamport and rent rules are checked after each
+//! instruction.
+//!
+//! The integration tests run on `solana-program-test` in native mode instead
+//! (see `tests/svm`). The fuzz targets do not, because starting a bank for
+//! every input would dominate each iteration, and `cargo fuzz` gets no
+//! coverage out of the runtime itself. The price is that the stubs below
+//! must stay faithful to the real programs, so they only cover what the
+//! program actually invokes and panic on anything else:
+//!
+//! - system program: `Transfer`, `CreateAccount`, and the `Allocate` and
+//!   `Assign` pair Anchor uses to `init` an account that already holds
+//!   lamports;
+//! - SPL Token: `InitializeAccount3` and `Transfer`, for governance escrows;
+//! - sysvars: `Clock` and `Rent`.
+//!
+//! Anything that depends on BPF-specific behaviour (compute units, stack
+//! and heap limits, CPI depth) is out of scope and belongs in a deployment
+//! check against a local validator.
+
+#![allow(dead_code)]
+
+#[path = "../../tests/svm/ix.rs"]
+pub mod ix;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::{
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    instruction::Instruction,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    program_utils::limited_deserialize,
+    system_instruction::SystemInstruction,
+    system_program,
+};
+use anchor_lang::{AccountDeserialize, AccountSerialize, Discriminator};
+use anchor_spl::token::spl_token::{
+    self,
+    instruction::TokenInstruction,
+    state::{Account as TokenAccountState, AccountState, Mint as MintState},
+};
+use pyth_solana_receiver_sdk::price_update::{PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+
+/// Unix timestamp the clock starts at for every new `Svm`.
+pub const GENESIS_TIMESTAMP: i64 = 1_700_000_000;
+
+/// Owner of builtin programs such as the system program.
+const NATIVE_LOADER_ID: Pubkey = pubkey!("NativeLoader1111111111111111111111111111111");
+
+/// Pyth receiver program that owns `PriceUpdateV2` accounts.
+pub const PYTH_RECEIVER_ID: Pubkey = pyth_solana_receiver_sdk::ID;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+thread_local! {
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+    static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    /// Net lamports moved by system program CPIs during the current instruction.
+    static CPI_DELTAS: RefCell<HashMap<Pubkey, i128>> = RefCell::new(HashMap::new());
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        EVENTS.with(|events| {
+            events
+                .borrow_mut()
+                .extend(fields.iter().map(|field| field.to_vec()))
+        });
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        CLOCK.with(|clock| unsafe { *(var_addr as *mut Clock) = clock.borrow().clone() });
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let find = |index: usize| -> std::result::Result<&AccountInfo, ProgramError> {
+            let meta = instruction
+                .accounts
+                .get(index)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let signed = info.is_signer
+                || signers_seeds.iter().any(|seeds| {
+                    Pubkey::create_program_address(seeds, &prediction_market::ID)
+                        .map(|pda| pda == meta.pubkey)
+                        .unwrap_or(false)
+                });
+            if meta.is_signer && !signed {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            Ok(info)
+        };
+
+        match instruction.program_id {
+            system_program::ID => invoke_system(&instruction.data, find),
+            spl_token::ID => invoke_token(&instruction.data, find),
+            program => panic!("CPI into unsupported program {program}"),
+        }
+    }
+}
+
+fn invoke_system<'a, 'info: 'a>(
+    data: &[u8],
+    find: impl Fn(usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError>,
+) -> ProgramResult {
+    let ix: SystemInstruction =
+        limited_deserialize(data, 1024).map_err(|_| ProgramError::InvalidInstructionData)?;
+    match ix {
+        SystemInstruction::Transfer { lamports } => {
+            let from = find(0)?;
+            let to = find(1)?;
+            if *from.owner != system_program::ID || !from.data_is_empty() {
+                return Err(ProgramError::InvalidArgument);
+            }
+            system_transfer(from, to, lamports)
+        }
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            let from = find(0)?;
+            let to = find(1)?;
+            if to.lamports() > 0 || !to.data_is_empty() || *to.owner != system_program::ID {
+                // SystemError::AccountAlreadyInUse
+                return Err(ProgramError::Custom(0));
+            }
+            system_transfer(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+            Ok(())
+        }
+        SystemInstruction::Allocate { space } => {
+            let account = find(0)?;
+            if !account.data_is_empty() || *account.owner != system_program::ID {
+                return Err(ProgramError::Custom(0));
+            }
+            account.realloc(space as usize, true)
+        }
+        SystemInstruction::Assign { owner } => {
+            let account = find(0)?;
+            if *account.owner != system_program::ID {
+                return Err(ProgramError::IllegalOwner);
+            }
+            account.assign(&owner);
+            Ok(())
+        }
+        other => panic!("unsupported system instruction {other:?}"),
+    }
+}
+
+/// Serves the subset of the SPL Token program the program uses: creating
+/// token accounts and transferring between them.
+fn invoke_token<'a, 'info: 'a>(
+    data: &[u8],
+    find: impl Fn(usize) -> std::result::Result<&'a AccountInfo<'info>, ProgramError>,
+) -> ProgramResult {
+    match TokenInstruction::unpack(data)? {
+        TokenInstruction::InitializeAccount3 { owner } => {
+            let account = find(0)?;
+            let mint = find(1)?;
+            if *account.owner != spl_token::ID || *mint.owner != spl_token::ID {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let state = TokenAccountState {
+                mint: *mint.key,
+                owner,
+                state: AccountState::Initialized,
+                ..TokenAccountState::default()
+            };
+            TokenAccountState::pack(state, &mut account.try_borrow_mut_data()?)
+        }
+        TokenInstruction::Transfer { amount } => {
+            let source = find(0)?;
+            let destination = find(1)?;
+            let authority = find(2)?;
+            let mut from = TokenAccountState::unpack(&source.try_borrow_data()?)?;
+            let mut to = TokenAccountState::unpack(&destination.try_borrow_data()?)?;
+            if from.owner != *authority.key {
+                // TokenError::OwnerMismatch
+                return Err(ProgramError::Custom(4));
+            }
+            if from.mint != to.mint {
+                // TokenError::MintMismatch
+                return Err(ProgramError::Custom(3));
+            }
+            if from.amount < amount {
+                // TokenError::InsufficientFunds
+                return Err(ProgramError::Custom(1));
+            }
+            from.amount -= amount;
+            to.amount += amount;
+            TokenAccountState::pack(from, &mut source.try_borrow_mut_data()?)?;
+            TokenAccountState::pack(to, &mut destination.try_borrow_mut_data()?)
+        }
+        other => panic!("unsupported token instruction {other:?}"),
+    }
+}
+
+fn system_transfer(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if from.lamports() < lamports {
+        // SystemError::ResultWithNegativeLamports
+        return Err(ProgramError::Custom(1));
+    }
+    **from.try_borrow_mut_lamports()? -= lamports;
+    **to.try_borrow_mut_lamports()? += lamports;
+    CPI_DELTAS.with(|deltas| {
+        let mut deltas = deltas.borrow_mut();
+        *deltas.entry(*from.key).or_default() -= lamports as i128;
+        *deltas.entry(*to.key).or_default() += lamports as i128;
+    });
+    Ok(())
+}
+
+// Byte offsets of the loader-style account layout. `AccountInfo` reads the
+// original data length from the four bytes preceding the key and writes the
+// current length into the eight bytes preceding the data.
+const ORIGINAL_LEN: usize = 4;
+const KEY: usize = 8;
+const OWNER: usize = 40;
+const LAMPORTS: usize = 72;
+const DATA_LEN: usize = 80;
+const DATA: usize = 88;
+
+struct Serialized {
+    buffer: Vec<u64>,
+}
+
+impl Serialized {
+    fn new(key: Pubkey, account: &Account) -> Self {
+        let bytes = DATA + account.data.len() + MAX_PERMITTED_DATA_INCREASE;
+        let mut serialized = Self {
+            buffer: vec![0; bytes.div_ceil(8)],
+        };
+        let raw = serialized.bytes_mut();
+        raw[ORIGINAL_LEN..KEY].copy_from_slice(&(account.data.len() as u32).to_le_bytes());
+        raw[KEY..OWNER].copy_from_slice(key.as_ref());
+        raw[OWNER..LAMPORTS].copy_from_slice(account.owner.as_ref());
+        raw[LAMPORTS..DATA_LEN].copy_from_slice(&account.lamports.to_le_bytes());
+        raw[DATA_LEN..DATA].copy_from_slice(&(account.data.len() as u64).to_le_bytes());
+        raw[DATA..DATA + account.data.len()].copy_from_slice(&account.data);
+        serialized
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.buffer.len() * 8;
+        unsafe { std::slice::from_raw_parts_mut(self.buffer.as_mut_ptr() as *mut u8, len) }
+    }
+
+    /// Builds an `AccountInfo` pointing into the buffer.
+    ///
+    /// # Safety
+    /// The buffer must outlive the returned info and must not be accessed
+    /// through `bytes_mut` while the info is alive.
+    unsafe fn info<'a>(
+        &mut self,
+        is_signer: bool,
+        is_writable: bool,
+        executable: bool,
+    ) -> AccountInfo<'a> {
+        let base = self.buffer.as_mut_ptr() as *mut u8;
+        let data_len = *(base.add(DATA_LEN) as *const u64) as usize;
+        AccountInfo {
+            key: &*(base.add(KEY) as *const Pubkey),
+            lamports: Rc::new(RefCell::new(&mut *(base.add(LAMPORTS) as *mut u64))),
+            data: Rc::new(RefCell::new(std::slice::from_raw_parts_mut(
+                base.add(DATA),
+                data_len,
+            ))),
+            owner: &*(base.add(OWNER) as *const Pubkey),
+            rent_epoch: u64::MAX,
+            is_signer,
+            is_writable,
+            executable,
+        }
+    }
+
+    fn read(&mut self, executable: bool) -> Account {
+        let raw = self.bytes_mut();
+        let data_len = u64::from_le_bytes(raw[DATA_LEN..DATA].try_into().unwrap()) as usize;
+        Account {
+            lamports: u64::from_le_bytes(raw[LAMPORTS..DATA_LEN].try_into().unwrap()),
+            data: raw[DATA..DATA + data_len].to_vec(),
+            owner: Pubkey::try_from(&raw[OWNER..LAMPORTS]).unwrap(),
+            executable,
+        }
+    }
+}
+
+pub struct Svm {
+    accounts: HashMap<Pubkey, Account>,
+    clock: Clock,
+}
+
+impl Default for Svm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Svm {
+    pub fn new() -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(Stubs));
+        });
+
+        let mut accounts = HashMap::new();
+        for program in [system_program::ID, spl_token::ID] {
+            accounts.insert(
+                program,
+                Account {
+                    lamports: 1,
+                    data: Vec::new(),
+                    owner: NATIVE_LOADER_ID,
+                    executable: true,
+                },
+            );
+        }
+        Self {
+            accounts,
+            clock: Clock {
+                slot: 1,
+                unix_timestamp: GENESIS_TIMESTAMP,
+                ..Clock::default()
+            },
+        }
+    }
+
+    pub fn now(&self) -> i64 {
+        self.clock.unix_timestamp
+    }
+
+    /// Moves the clock to `unix_timestamp`, advancing the slot alongside it.
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        let elapsed = unix_timestamp
+            .saturating_sub(self.clock.unix_timestamp)
+            .max(0) as u64;
+        self.clock.slot += elapsed.max(1) * 2;
+        self.clock.unix_timestamp = unix_timestamp;
+    }
+
+    pub fn airdrop(&mut self, key: &Pubkey, lamports: u64) {
+        let account = self.accounts.entry(*key).or_insert_with(|| Account {
+            owner: system_program::ID,
+            ..Account::default()
+        });
+        account.lamports += lamports;
+    }
+
+    /// Returns a fresh system account funded with `lamports`.
+    pub fn funded_account(&mut self, lamports: u64) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.airdrop(&key, lamports);
+        key
+    }
+
+    fn set_account(&mut self, key: Pubkey, account: Account) {
+        self.accounts.insert(key, account);
+    }
+
+    /// Installs raw, rent-exempt program account data, e.g. a fixture
+    /// written by an older program version.
+    pub fn set_program_account(&mut self, key: Pubkey, data: Vec<u8>) {
+        self.set_account(
+            key,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: prediction_market::ID,
+                executable: false,
+            },
+        );
+    }
+
+    fn set_token_state<T: Pack>(&mut self, key: Pubkey, state: T) {
+        let mut data = vec![0; T::LEN];
+        T::pack(state, &mut data).unwrap();
+        self.set_account(
+            key,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: spl_token::ID,
+                executable: false,
+            },
+        );
+    }
+
+    /// Creates an SPL mint with no mint authority.
+    pub fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        self.set_token_state(
+            mint,
+            MintState {
+                decimals,
+                is_initialized: true,
+                ..MintState::default()
+            },
+        );
+        mint
+    }
+
+    /// Creates a token account of `mint` owned by `owner` holding `amount`.
+    pub fn create_token_account(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let account = Pubkey::new_unique();
+        self.set_token_state(
+            account,
+            TokenAccountState {
+                mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..TokenAccountState::default()
+            },
+        );
+        account
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        let account = self
+            .accounts
+            .get(key)
+            .unwrap_or_else(|| panic!("account {key} does not exist"));
+        TokenAccountState::unpack(&account.data).unwrap().amount
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&Account> {
+        self.accounts.get(key)
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let account = self
+            .accounts
+            .get(key)
+            .unwrap_or_else(|| panic!("account {key} does not exist"));
+        T::try_deserialize(&mut account.data.as_slice()).expect("account deserializes")
+    }
+
+    /// Overwrites a program account's state, keeping its size.
+    pub fn store<T: AccountSerialize>(&mut self, key: &Pubkey, state: &T) {
+        let account = self
+            .accounts
+            .get_mut(key)
+            .unwrap_or_else(|| panic!("account {key} does not exist"));
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+    }
+
+    /// Writes a `PriceUpdateV2` account owned by the Pyth receiver, as if it had
+    /// been posted and fully verified at the current time.
+    pub fn set_price_update(
+        &mut self,
+        key: Pubkey,
+        feed_id: [u8; 32],
+        price: i64,
+        publish_time: i64,
+    ) {
+        let update = PriceUpdateV2 {
+            write_authority: Pubkey::new_unique(),
+            verification_level: VerificationLevel::Full,
+            price_message: PriceFeedMessage {
+                feed_id,
+                price,
+                conf: 0,
+                exponent: -8,
+                publish_time,
+                prev_publish_time: publish_time - 1,
+                ema_price: price,
+                ema_conf: 0,
+            },
+            posted_slot: self.clock.slot,
+        };
+        let mut data = Vec::new();
+        update.try_serialize(&mut data).unwrap();
+        self.set_account(
+            key,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: PYTH_RECEIVER_ID,
+                executable: false,
+            },
+        );
+    }
+
+    /// Events of type `T` emitted by the last processed instruction.
+    pub fn events<T: AnchorDeserialize + Discriminator>(&self) -> Vec<T> {
+        EVENTS.with(|events| {
+            events
+                .borrow()
+                .iter()
+                .filter(|data| data.starts_with(&T::DISCRIMINATOR))
+                .map(|data| T::deserialize(&mut &data[8..]).expect("event deserializes"))
+                .collect()
+        })
+    }
+
+    /// Executes a single instruction atomically.
+    ///
+    /// `signers` lists the accounts that signed the transaction; every account
+    /// the instruction marks as a signer must appear in it. State is only
+    /// committed when the program succeeds, and any violation of the
+    /// runtime's lamport or rent rules panics.
+    pub fn process(
+        &mut self,
+        ix: Instruction,
+        signers: &[Pubkey],
+    ) -> std::result::Result<(), ProgramError> {
+        assert_eq!(ix.program_id, prediction_market::ID);
+        for meta in ix.accounts.iter().filter(|meta| meta.is_signer) {
+            assert!(
+                signers.contains(&meta.pubkey),
+                "{} must sign the transaction",
+                meta.pubkey
+            );
+        }
+
+        CLOCK.with(|clock| *clock.borrow_mut() = self.clock.clone());
+        EVENTS.with(|events| events.borrow_mut().clear());
+        CPI_DELTAS.with(|deltas| deltas.borrow_mut().clear());
+
+        let mut keys: Vec<Pubkey> = Vec::new();
+        for meta in &ix.accounts {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let before: Vec<Account> = keys
+            .iter()
+            .map(|key| {
+                self.accounts.get(key).cloned().unwrap_or(Account {
+                    owner: system_program::ID,
+                    ..Account::default()
+                })
+            })
+            .collect();
+        let mut buffers: Vec<Serialized> = keys
+            .iter()
+            .zip(&before)
+            .map(|(key, account)| Serialized::new(*key, account))
+            .collect();
+
+        let result = {
+            let unique: Vec<AccountInfo> = keys
+                .iter()
+                .zip(&before)
+                .zip(buffers.iter_mut())
+                .map(|((key, account), buffer)| {
+                    let is_signer = ix.accounts.iter().any(|m| m.pubkey == *key && m.is_signer);
+                    let is_writable = ix
+                        .accounts
+                        .iter()
+                        .any(|m| m.pubkey == *key && m.is_writable);
+                    unsafe { buffer.info(is_signer, is_writable, account.executable) }
+                })
+                .collect();
+            let infos: Vec<AccountInfo> = ix
+                .accounts
+                .iter()
+                .map(|meta| unique[keys.iter().position(|k| *k == meta.pubkey).unwrap()].clone())
+                .collect();
+            prediction_market::entry(&prediction_market::ID, &infos, &ix.data)
+        };
+        result?;
+
+        let after: Vec<Account> = buffers
+            .iter_mut()
+            .zip(&before)
+            .map(|(buffer, account)| buffer.read(account.executable))
+            .collect();
+        self.check_runtime_rules(&ix, &keys, &before, &after);
+        for (key, account) in keys.into_iter().zip(after) {
+            if account.lamports == 0 && account.data.is_empty() {
+                self.accounts.remove(&key);
+            } else {
+                self.accounts.insert(key, account);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_runtime_rules(
+        &self,
+        ix: &Instruction,
+        keys: &[Pubkey],
+        before: &[Account],
+        after: &[Account],
+    ) {
+        let total_before: u128 = before.iter().map(|a| a.lamports as u128).sum();
+        let total_after: u128 = after.iter().map(|a| a.lamports as u128).sum();
+        assert_eq!(
+            total_before, total_after,
+            "instruction created or destroyed lamports"
+        );
+
+        let rent = Rent::default();
+        for ((key, pre), post) in keys.iter().zip(before).zip(after) {
+            let writable = ix
+                .accounts
+                .iter()
+                .any(|m| m.pubkey == *key && m.is_writable);
+            if !writable {
+                assert_eq!(pre, post, "read-only account {key} was modified");
+                continue;
+            }
+            let cpi_delta =
+                CPI_DELTAS.with(|deltas| deltas.borrow().get(key).copied().unwrap_or(0));
+            let direct_delta = post.lamports as i128 - pre.lamports as i128 - cpi_delta;
+            if direct_delta < 0 {
+                assert_eq!(
+                    pre.owner,
+                    prediction_market::ID,
+                    "program debited {key} which it does not own"
+                );
+            }
+            // Token accounts only change through the token program stub
+            if pre.data != post.data
+                && post.owner != system_program::ID
+                && post.owner != spl_token::ID
+            {
+                assert_eq!(
+                    post.owner,
+                    prediction_market::ID,
+                    "program modified data of {key} which it does not own"
+                );
+            }
+            if post.lamports > 0 && post.lamports != pre.lamports {
+                assert!(
+                    rent.is_exempt(post.lamports, post.data.len()),
+                    "account {key} is left below the rent-exempt minimum"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! Token-weighted governance: holders lock the governance mint's tokens
+//! to vote on proposals that change the config, pause the protocol, edit
+//! the oracle allow-list or resolve abandoned markets.
+
+use super::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+/// How long a market can sit unresolved past `resolve_after` before a
+/// governance proposal may resolve it instead of its authority
+pub const GOVERNANCE_RESOLUTION_TIMEOUT: i64 = 30 * 24 * 60 * 60;
+
+pub(crate) fn initialize_governance(
+    ctx: Context<InitializeGovernance>,
+    quorum_bps: u16,
+    threshold_bps: u16,
+    voting_period: i64,
+) -> Result<()> {
+    require!(quorum_bps <= 10_000, ErrorCode::InvalidGovernanceConfig);
+    require!((5_000..=10_000).contains(&threshold_bps), ErrorCode::InvalidGovernanceConfig);
+    require!(voting_period > 0, ErrorCode::InvalidGovernanceConfig);
+    
+    let governance = &mut ctx.accounts.governance;
+    governance.mint = ctx.accounts.mint.key();
+    governance.vault = ctx.accounts.vault.key();
+    governance.quorum_bps = quorum_bps;
+    governance.threshold_bps = threshold_bps;
+    governance.voting_period = voting_period;
+    governance.total_deposited = 0;
+    governance.proposal_count = 0;
+    governance.version = GOVERNANCE_VERSION;
+    
+    Ok(())
+}
+
+pub(crate) fn deposit_governance_tokens(ctx: Context<DepositGovernanceTokens>, amount: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::InvalidTokenAmount);
+    let governance = &mut ctx.accounts.governance;
+    let record = &mut ctx.accounts.voter_record;
+    let clock = Clock::get()?;
+    
+    token::transfer(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.voter_token_account.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.voter.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+    
+    record.owner = ctx.accounts.voter.key();
+    record.amount = record.amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    record.deposited_at = clock.unix_timestamp;
+    governance.total_deposited = governance.total_deposited.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    
+    Ok(())
+}
+
+pub(crate) fn withdraw_governance_tokens(ctx: Context<WithdrawGovernanceTokens>, amount: u64) -> Result<()> {
+    let governance = &mut ctx.accounts.governance;
+    let record = &mut ctx.accounts.voter_record;
+    let clock = Clock::get()?;
+    
+    require!(amount > 0, ErrorCode::InvalidTokenAmount);
+    require!(amount <= record.amount, ErrorCode::InsufficientGovernanceTokens);
+    require!(clock.unix_timestamp >= record.unlock_at, ErrorCode::GovernanceTokensLocked);
+    
+    let seeds: &[&[u8]] = &[b"governance", &[ctx.bumps.governance]];
+    token::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.voter_token_account.to_account_info(),
+                authority: governance.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )?;
+    
+    record.amount -= amount;
+    governance.total_deposited -= amount;
+    
+    Ok(())
+}
+
+pub(crate) fn create_proposal(
+    ctx: Context<CreateProposal>,
+    action: ProposalAction,
+    description_hash: [u8; 32],
+) -> Result<()> {
+    let governance = &mut ctx.accounts.governance;
+    let proposal = &mut ctx.accounts.proposal;
+    let clock = Clock::get()?;
+    
+    require!(ctx.accounts.voter_record.amount > 0, ErrorCode::NoVotingWeight);
+    action.validate()?;
+    
+    proposal.id = governance.proposal_count;
+    proposal.proposer = ctx.accounts.proposer.key();
+    proposal.action = action.clone();
+    proposal.description_hash = description_hash;
+    proposal.created_at = clock.unix_timestamp;
+    proposal.voting_end = clock.unix_timestamp.checked_add(governance.voting_period).ok_or(ErrorCode::MathOverflow)?;
+    proposal.snapshot_total = governance.total_deposited;
+    proposal.votes_for = 0;
+    proposal.votes_against = 0;
+    proposal.executed = false;
+    governance.proposal_count += 1;
+    
+    emit!(ProposalCreated {
+        proposal: proposal.key(),
+        id: proposal.id,
+        proposer: proposal.proposer,
+        action,
+    });
+    
+    Ok(())
+}
+
+pub(crate) fn cast_vote(ctx: Context<CastVote>, approve: bool) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let record = &mut ctx.accounts.voter_record;
+    let vote = &mut ctx.accounts.vote;
+    let clock = Clock::get()?;
+    
+    require!(clock.unix_timestamp < proposal.voting_end, ErrorCode::ProposalVotingClosed);
+    let weight = if record.deposited_at < proposal.created_at { record.amount } else { 0 };
+    require!(weight > 0, ErrorCode::NoVotingWeight);
+    
+    if approve {
+        proposal.votes_for = proposal.votes_for.checked_add(weight).ok_or(ErrorCode::MathOverflow)?;
+    } else {
+        proposal.votes_against = proposal.votes_against.checked_add(weight).ok_or(ErrorCode::MathOverflow)?;
+    }
+    record.unlock_at = record.unlock_at.max(proposal.voting_end);
+    
+    vote.proposal = proposal.key();
+    vote.voter = ctx.accounts.voter.key();
+    vote.approve = approve;
+    vote.weight = weight;
+    vote.timestamp = clock.unix_timestamp;
+    
+    Ok(())
+}
+
+pub(crate) fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+    let governance = &ctx.accounts.governance;
+    let proposal = &mut ctx.accounts.proposal;
+    let config = &mut ctx.accounts.config;
+    let clock = Clock::get()?;
+    
+    require!(clock.unix_timestamp >= proposal.voting_end, ErrorCode::ProposalVotingOpen);
+    require!(!proposal.executed, ErrorCode::ProposalAlreadyExecuted);
+    require!(proposal.passed(governance), ErrorCode::ProposalNotPassed);
+    
+    match proposal.action.clone() {
+        // Config changes still wait out the config timelock
+        ProposalAction::UpdateConfig(params) => {
+            let eta = clock.unix_timestamp.checked_add(config.params.timelock).ok_or(ErrorCode::MathOverflow)?;
+            emit!(ConfigUpdateQueued {
+                old: config.params.clone(),
+                new: params.clone(),
+                eta,
+            });
+            config.queued_params = params;
+            config.update_eta = eta;
+        }
+        ProposalAction::SetProtocolPause(flags) => {
+            ctx.accounts.global_state.set_paused(flags, clock.unix_timestamp);
+            emit!(ProtocolPauseSet {
+                guardian: governance.key(),
+                flags,
+            });
+        }
+        ProposalAction::AllowOracleFeed(feed) => {
+            if !config.oracle_feeds.contains(&feed) {
+                require!(config.oracle_feeds.len() < MAX_ORACLE_FEEDS, ErrorCode::TooManyOracleFeeds);
+                config.oracle_feeds.push(feed);
+            }
+        }
+        ProposalAction::RemoveOracleFeed(feed) => {
+            config.oracle_feeds.retain(|allowed| *allowed != feed);
+        }
+        // Takes over markets whose authority has gone missing, and
+        // oracle markets whose price never arrived if the config hands
+        // those to governance
+        ProposalAction::ResolveMarket(target, outcome) => {
+            let market = ctx.accounts.market.as_mut().ok_or(ErrorCode::InvalidProposalMarket)?;
+            require_keys_eq!(market.key(), target, ErrorCode::InvalidProposalMarket);
+            require!(!market.resolved, ErrorCode::AlreadyResolved);
+            ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+            require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+            require!(!market.pending, ErrorCode::MarketPending);
+            require!(market.parent_market == Pubkey::default(), ErrorCode::MustUseParentMarket);
+            market.require_condition_met()?;
+            let timeout = if market.oracle_enabled && config.params.oracle_fallback == ORACLE_FALLBACK_GOVERNANCE {
+                config.params.oracle_grace_period
+            } else {
+                GOVERNANCE_RESOLUTION_TIMEOUT
+            };
+            let takeover_at = market.resolvable_at().checked_add(timeout).ok_or(ErrorCode::MathOverflow)?;
+            require!(clock.unix_timestamp >= takeover_at, ErrorCode::ResolutionTimeoutNotReached);
+            
+            settle_market(market, outcome, false);
+            market.fallback_reason = if market.oracle_enabled {
+                FALLBACK_ORACLE_TIMEOUT
+            } else {
+                FALLBACK_AUTHORITY_TIMEOUT
+            };
+            msg!("Market {} resolved by governance: {}", target, outcome);
+        }
+    }
+    proposal.executed = true;
+    
+    emit!(ProposalExecuted {
+        proposal: proposal.key(),
+        id: proposal.id,
+    });
+    
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeGovernance<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Governance::INIT_SPACE,
+        seeds = [b"governance"],
+        bump
+    )]
+    pub governance: Account<'info, Governance>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"governance_vault"],
+        bump,
+        token::mint = mint,
+        token::authority = governance
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    pub mint: Account<'info, Mint>,
+    #[account(seeds = [b"config"], bump, has_one = authority @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositGovernanceTokens<'info> {
+    #[account(mut, seeds = [b"governance"], bump, has_one = vault)]
+    pub governance: Account<'info, Governance>,
+    #[account(mut)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = voter,
+        space = 8 + VoterRecord::INIT_SPACE,
+        seeds = [b"voter", voter.key().as_ref()],
+        bump
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+    #[account(mut, token::mint = governance.mint, token::authority = voter)]
+    pub voter_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawGovernanceTokens<'info> {
+    #[account(mut, seeds = [b"governance"], bump, has_one = vault)]
+    pub governance: Account<'info, Governance>,
+    #[account(mut)]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [b"voter", voter.key().as_ref()], bump)]
+    pub voter_record: Account<'info, VoterRecord>,
+    #[account(mut, token::mint = governance.mint)]
+    pub voter_token_account: Account<'info, TokenAccount>,
+    pub voter: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(mut, seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE,
+        seeds = [b"proposal", governance.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    #[account(seeds = [b"voter", proposer.key().as_ref()], bump)]
+    pub voter_record: Account<'info, VoterRecord>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CastVote<'info> {
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut, seeds = [b"voter", voter.key().as_ref()], bump)]
+    pub voter_record: Account<'info, VoterRecord>,
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + ProposalVote::INIT_SPACE,
+        seeds = [b"proposal_vote", proposal.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote: Account<'info, ProposalVote>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(seeds = [b"governance"], bump)]
+    pub governance: Account<'info, Governance>,
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Required by ResolveMarket proposals
+    #[account(mut)]
+    pub market: Option<Account<'info, Market>>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Governance {
+    pub mint: Pubkey,                 // voting token
+    pub vault: Pubkey,                // holds deposited tokens
+    pub quorum_bps: u16,              // share of deposited tokens that must vote
+    pub threshold_bps: u16,           // share of cast votes that must approve
+    pub voting_period: i64,           // seconds
+    pub total_deposited: u64,
+    pub proposal_count: u64,
+    // Versioning
+    pub version: u8,                  // GOVERNANCE_VERSION
+    pub reserved: [u8; 64],           // room for new fields
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct VoterRecord {
+    pub owner: Pubkey,
+    pub amount: u64,                  // tokens in the vault
+    pub deposited_at: i64,            // counts for proposals created later
+    pub unlock_at: i64,               // end of the last vote it backed
+}
+
+/// What a passed proposal does when executed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub enum ProposalAction {
+    /// Queues new config parameters behind the config timelock
+    UpdateConfig(ConfigParams),
+    /// Replaces the protocol-wide pause flags
+    SetProtocolPause(u8),
+    /// Adds a Pyth feed to the oracle allow-list
+    AllowOracleFeed([u8; 32]),
+    /// Removes a Pyth feed from the oracle allow-list
+    RemoveOracleFeed([u8; 32]),
+    /// Resolves a market left unresolved past GOVERNANCE_RESOLUTION_TIMEOUT
+    ResolveMarket(Pubkey, bool),
+}
+
+impl ProposalAction {
+    fn validate(&self) -> Result<()> {
+        match self {
+            ProposalAction::UpdateConfig(params) => params.validate(),
+            ProposalAction::SetProtocolPause(flags) => {
+                require!(flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+                Ok(())
+            }
+            ProposalAction::AllowOracleFeed(_)
+            | ProposalAction::RemoveOracleFeed(_)
+            | ProposalAction::ResolveMarket(..) => Ok(()),
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: ProposalAction,
+    pub description_hash: [u8; 32],   // hash of the off-chain rationale
+    pub created_at: i64,              // voting weight snapshot
+    pub voting_end: i64,
+    pub snapshot_total: u64,          // tokens deposited at creation
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub executed: bool,
+}
+
+impl Proposal {
+    /// Quorum counts every cast vote against the deposits at creation;
+    /// the threshold counts approvals against cast votes
+    fn passed(&self, governance: &Governance) -> bool {
+        let cast = self.votes_for as u128 + self.votes_against as u128;
+        cast * 10_000 >= self.snapshot_total as u128 * governance.quorum_bps as u128
+            && self.votes_for as u128 * 10_000 > cast * governance.threshold_bps as u128
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ProposalVote {
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    pub approve: bool,
+    pub weight: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProposalCreated {
+    pub proposal: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub action: ProposalAction,
+}
+
+#[event]
+pub struct ProposalExecuted {
+    pub proposal: Pubkey,
+    pub id: u64,
+}
@@ -1,8 +1,131 @@
 use anchor_lang::prelude::*;
 use pyth_solana_receiver_sdk::price_update::PriceUpdateV2;
 
+pub mod governance;
+pub mod order_book;
+pub mod parlay;
+
+pub use governance::*;
+pub use order_book::*;
+pub use parlay::*;
+
 declare_id!("GUzTP7BCgdTUTEDtguuUwZKdDbrkAKFiiRuqzpbSaQLu");
 
+/// How long claimed winners have to collect a redistribution share before
+/// the market can be finalized anyway
+pub const REDISTRIBUTION_PERIOD: i64 = 30 * 24 * 60 * 60;
+
+/// How long claims stay open after a claims pause is lifted or a frozen
+/// market is restored, even if that runs past the claim deadline
+pub const CLAIM_RESUME_GRACE: i64 = 7 * 24 * 60 * 60;
+
+/// Claim period of markets migrated from the legacy layout, counted from
+/// migration or the end time, whichever is later. Their bet counts only
+/// cover migrated bets, so they always settle through the deadline.
+pub const LEGACY_CLAIM_PERIOD: i64 = 365 * 24 * 60 * 60;
+
+/// Default fee taken from bets on markets with liquidity providers, in
+/// basis points. The live value is `ConfigParams::lp_fee_bps`.
+pub const LP_FEE_BPS: u64 = 50;
+
+/// `Market::moderation_status` values
+pub const MODERATION_VISIBLE: u8 = 0;
+pub const MODERATION_HIDDEN: u8 = 1;
+pub const MODERATION_FROZEN: u8 = 2;
+
+/// Bond a creator locks while the community votes on a pending market
+pub const ELIGIBILITY_DEPOSIT: u64 = 100_000_000; // 0.1 SOL
+
+/// Settled predictions a voter needs before their eligibility vote counts,
+/// unless they pass a human attestation instead
+pub const MIN_VOTER_PREDICTIONS: u32 = 3;
+
+/// Eligibility weight an attested human adds on top of their reputation,
+/// the same as one perfect prediction
+pub const HUMAN_VOTE_WEIGHT: u64 = 10;
+
+/// `Market::meta_condition` values: what a meta market asks about its parent
+pub const META_PARENT_YES: u8 = 0;
+pub const META_PARENT_NO: u8 = 1;
+pub const META_PARENT_INVALIDATED: u8 = 2;
+
+/// `Market::category` is a zero-padded UTF-8 name stored right after the
+/// authority, so clients can filter markets with a memcmp at this offset
+pub const MARKET_CATEGORY_OFFSET: usize = 8 + 32;
+pub const MAX_CATEGORY_LEN: usize = 32;
+pub const MAX_TAGS: usize = 5;
+pub const MAX_METADATA_URI_LEN: usize = 200;
+
+/// Limits on the market's free text. Users see the character limit; the
+/// byte limit sizes the account, leaving room for accented Spanish and
+/// Portuguese text, which takes two bytes per accented character in UTF-8.
+pub const MAX_QUESTION_CHARS: usize = 200;
+pub const MAX_QUESTION_BYTES: usize = 400;
+pub const MAX_DESCRIPTION_CHARS: usize = 500;
+pub const MAX_DESCRIPTION_BYTES: usize = 1000;
+pub const MAX_TAG_CHARS: usize = 20;
+pub const MAX_TAG_BYTES: usize = 40;
+pub const MAX_RESOLUTION_SOURCE_CHARS: usize = 100;
+pub const MAX_RESOLUTION_SOURCE_BYTES: usize = 200;
+
+/// Pause flags, set protocol-wide on `GlobalState::paused` or per market
+/// on `Market::paused`. An action is blocked if either sets its flag:
+///
+/// | flag               | blocks                                              |
+/// |--------------------|-----------------------------------------------------|
+/// | `PAUSE_BETTING`    | place_bet, add_liquidity, place_order, match_orders,|
+/// |                    | split_position, place_parlay (on any leg)           |
+/// | `PAUSE_RESOLUTION` | resolve_market, resolve_with_oracle                 |
+/// | `PAUSE_CLAIMS`     | claims, refunds, redistributions, remove_liquidity, |
+/// |                    | claim_position, merge_position, winning parlays,    |
+/// |                    | withdraw_house_liquidity, settle_unclaimed          |
+/// | `PAUSE_CREATION`   | create_market (protocol-wide only)                  |
+///
+/// Other settling, closing and finalizing stays open so funds are never
+/// stuck behind a pause that only meant to stop new risk. Lifting
+/// `PAUSE_CLAIMS` gives claimers `CLAIM_RESUME_GRACE` before anything
+/// unclaimed can be swept.
+pub const PAUSE_BETTING: u8 = 1 << 0;
+pub const PAUSE_RESOLUTION: u8 = 1 << 1;
+pub const PAUSE_CLAIMS: u8 = 1 << 2;
+pub const PAUSE_CREATION: u8 = 1 << 3;
+pub const PAUSE_ALL: u8 = PAUSE_BETTING | PAUSE_RESOLUTION | PAUSE_CLAIMS | PAUSE_CREATION;
+
+/// Layout versions of the versioned accounts. Accounts written before
+/// versioning are in the `Legacy*` layouts until migrated. A version only
+/// moves once a deployed layout changes, along with a migration from the
+/// one before; new fields come out of `reserved` until then.
+pub const MARKET_VERSION: u8 = 1;
+pub const BET_VERSION: u8 = 1;
+pub const GLOBAL_STATE_VERSION: u8 = 1;
+pub const CONFIG_VERSION: u8 = 1;
+pub const GOVERNANCE_VERSION: u8 = 1;
+
+/// Longest timelock a config update may set for the next one
+pub const MAX_CONFIG_TIMELOCK: i64 = 30 * 24 * 60 * 60;
+
+/// Highest LP fee a config update may set, in basis points
+pub const MAX_LP_FEE_BPS: u16 = 1_000;
+
+/// Largest M-of-N resolver set a market can have
+pub const MAX_RESOLVERS: usize = 10;
+
+/// Most Pyth feeds the oracle allow-list can hold
+pub const MAX_ORACLE_FEEDS: usize = 16;
+
+/// What settles an oracle market whose price never arrives within the
+/// config's `oracle_grace_period`
+pub const ORACLE_FALLBACK_INVALIDATE: u8 = 0;
+pub const ORACLE_FALLBACK_GOVERNANCE: u8 = 1;
+
+/// Why a market was resolved by a fallback instead of its usual path
+pub const FALLBACK_NONE: u8 = 0;
+pub const FALLBACK_ORACLE_TIMEOUT: u8 = 1;
+pub const FALLBACK_AUTHORITY_TIMEOUT: u8 = 2;
+
+/// Fixed-point scale for `Market::lp_fee_per_share`
+const FEE_PRECISION: u128 = 1_000_000_000_000;
+
 #[program]
 pub mod prediction_market {
     use super::*;
@@ -10,11 +133,262 @@ pub mod prediction_market {
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
         let global_state = &mut ctx.accounts.global_state;
         global_state.authority = ctx.accounts.authority.key();
+        global_state.pending_authority = Pubkey::default();
         global_state.total_markets = 0;
         global_state.total_volume = 0;
+        global_state.human_verifier = Pubkey::default();
+        global_state.moderator = Pubkey::default();
+        global_state.guardian = Pubkey::default();
+        global_state.paused = 0;
+        global_state.version = GLOBAL_STATE_VERSION;
+        ctx.accounts.treasury.total_collected = 0;
+        Ok(())
+    }
+
+    /// Creates the protocol config with the default parameters. The config
+    /// starts out owned by the global state's authority.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.authority = ctx.accounts.authority.key();
+        config.pending_authority = Pubkey::default();
+        config.params = ConfigParams::default();
+        config.queued_params = ConfigParams::default();
+        config.oracle_feeds = Vec::new();
+        config.update_eta = 0;
+        config.version = CONFIG_VERSION;
+        Ok(())
+    }
+
+    /// Queues new protocol parameters. They take effect through
+    /// `apply_config_update` once the current timelock has passed.
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let clock = Clock::get()?;
+        
+        params.validate()?;
+        let eta = clock.unix_timestamp.checked_add(config.params.timelock).ok_or(ErrorCode::MathOverflow)?;
+        
+        emit!(ConfigUpdateQueued {
+            old: config.params.clone(),
+            new: params.clone(),
+            eta,
+        });
+        
+        config.queued_params = params;
+        config.update_eta = eta;
+        
+        Ok(())
+    }
+
+    /// Applies the queued parameters once their timelock has passed.
+    /// Anyone can call it.
+    pub fn apply_config_update(ctx: Context<ApplyConfigUpdate>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let clock = Clock::get()?;
+        
+        require!(config.update_eta != 0, ErrorCode::NoConfigUpdateQueued);
+        require!(clock.unix_timestamp >= config.update_eta, ErrorCode::TimelockNotExpired);
+        
+        let new = config.queued_params.clone();
+        let old = std::mem::replace(&mut config.params, new);
+        config.update_eta = 0;
+        
+        emit!(ConfigUpdated {
+            old,
+            new: config.params.clone(),
+        });
+        
+        Ok(())
+    }
+
+    /// Drops the queued parameters
+    pub fn cancel_config_update(ctx: Context<UpdateConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        require!(config.update_eta != 0, ErrorCode::NoConfigUpdateQueued);
+        config.update_eta = 0;
+        
+        msg!("Config update cancelled");
+        
+        Ok(())
+    }
+
+    /// Starts handing the config to `new_authority`, who has to accept it.
+    /// `Pubkey::default()` cancels a pending transfer.
+    pub fn transfer_config_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.config.pending_authority = new_authority;
+        
+        msg!("Config authority transfer to {} pending", new_authority);
+        
+        Ok(())
+    }
+
+    /// Completes a config authority transfer
+    pub fn accept_config_authority(ctx: Context<AcceptConfigAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let old = config.authority;
+        config.authority = ctx.accounts.pending_authority.key();
+        config.pending_authority = Pubkey::default();
+        
+        emit!(ConfigAuthorityTransferred {
+            old,
+            new: config.authority,
+        });
+        
+        Ok(())
+    }
+
+    /// Sets up token-weighted governance over `mint`. Passed proposals can
+    /// change the config, pause the protocol and edit the oracle allow-list.
+    pub fn initialize_governance(
+        ctx: Context<InitializeGovernance>,
+        quorum_bps: u16,
+        threshold_bps: u16,
+        voting_period: i64,
+    ) -> Result<()> {
+        governance::initialize_governance(ctx, quorum_bps, threshold_bps, voting_period)
+    }
+
+    /// Locks governance tokens in the vault as voting weight. Tokens only
+    /// count toward proposals created after they were deposited, so each
+    /// deposit restarts the voter's snapshot.
+    pub fn deposit_governance_tokens(ctx: Context<DepositGovernanceTokens>, amount: u64) -> Result<()> {
+        governance::deposit_governance_tokens(ctx, amount)
+    }
+
+    /// Returns governance tokens once every vote they backed has ended
+    pub fn withdraw_governance_tokens(ctx: Context<WithdrawGovernanceTokens>, amount: u64) -> Result<()> {
+        governance::withdraw_governance_tokens(ctx, amount)
+    }
+
+    /// Opens a proposal to run `action`. Any voter with deposited tokens
+    /// can propose.
+    pub fn create_proposal(
+        ctx: Context<CreateProposal>,
+        action: ProposalAction,
+        description_hash: [u8; 32],
+    ) -> Result<()> {
+        governance::create_proposal(ctx, action, description_hash)
+    }
+
+    /// Votes with the tokens deposited before the proposal was created.
+    /// They stay locked until the vote ends.
+    pub fn cast_vote(ctx: Context<CastVote>, approve: bool) -> Result<()> {
+        governance::cast_vote(ctx, approve)
+    }
+
+    /// Runs a proposal's action once voting has ended with quorum reached
+    /// and the approval threshold met. Anyone can call it.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        governance::execute_proposal(ctx)
+    }
+
+    /// Starts handing protocol admin to `new_authority`, who has to accept
+    /// it. The new authority can be a multisig vault PDA, which accepts by
+    /// signing through its program. `Pubkey::default()` cancels.
+    pub fn transfer_authority(ctx: Context<TransferAuthority>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.global_state.pending_authority = new_authority;
+        
+        msg!("Protocol authority transfer to {} pending", new_authority);
+        
+        Ok(())
+    }
+
+    /// Completes a protocol authority transfer
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let global_state = &mut ctx.accounts.global_state;
+        let old = global_state.authority;
+        global_state.authority = ctx.accounts.pending_authority.key();
+        global_state.pending_authority = Pubkey::default();
+        
+        emit!(ProtocolAuthorityTransferred {
+            old,
+            new: global_state.authority,
+        });
+        
+        Ok(())
+    }
+
+    /// Sets the authority whose attestations count as proof of personhood.
+    /// `Pubkey::default()` disables verification.
+    pub fn set_human_verifier(ctx: Context<SetHumanVerifier>, verifier: Pubkey) -> Result<()> {
+        ctx.accounts.global_state.human_verifier = verifier;
+        
+        msg!("Human verifier set to {}", verifier);
+        
+        Ok(())
+    }
+
+    /// Sets the guardian allowed to pause the protocol and markets
+    pub fn set_guardian(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.global_state.guardian = guardian;
+        
+        msg!("Guardian set to {}", guardian);
+        
+        Ok(())
+    }
+
+    /// Replaces the protocol-wide pause flags; 0 unpauses everything
+    pub fn set_protocol_pause(ctx: Context<SetProtocolPause>, flags: u8) -> Result<()> {
+        require!(flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+        ctx.accounts.global_state.set_paused(flags, Clock::get()?.unix_timestamp);
+        
+        emit!(ProtocolPauseSet {
+            guardian: ctx.accounts.guardian.key(),
+            flags,
+        });
+        
         Ok(())
     }
 
+    /// Replaces a market's pause flags; 0 unpauses it
+    pub fn set_market_pause(ctx: Context<SetMarketPause>, flags: u8) -> Result<()> {
+        require!(flags & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+        let market = &mut ctx.accounts.market;
+        if market.paused & PAUSE_CLAIMS != 0 && flags & PAUSE_CLAIMS == 0 {
+            market.claims_resumed_at = Clock::get()?.unix_timestamp;
+        }
+        market.paused = flags;
+        
+        emit!(MarketPauseSet {
+            market: ctx.accounts.market.key(),
+            guardian: ctx.accounts.guardian.key(),
+            flags,
+        });
+        
+        Ok(())
+    }
+
+    /// Sets the moderator who handles content reports
+    pub fn set_moderator(ctx: Context<SetModerator>, moderator: Pubkey) -> Result<()> {
+        ctx.accounts.global_state.moderator = moderator;
+        
+        msg!("Moderator set to {}", moderator);
+        
+        Ok(())
+    }
+
+    /// Attests that `user` is a unique human, optionally until `expires_at`
+    /// (0 = never). Re-attesting refreshes the expiry.
+    pub fn attest_human(ctx: Context<AttestHuman>, expires_at: i64) -> Result<()> {
+        let attestation = &mut ctx.accounts.attestation;
+        let clock = Clock::get()?;
+        
+        require!(expires_at == 0 || expires_at > clock.unix_timestamp, ErrorCode::InvalidAttestationExpiry);
+        
+        attestation.user = ctx.accounts.user.key();
+        attestation.verifier = ctx.accounts.verifier.key();
+        attestation.issued_at = clock.unix_timestamp;
+        attestation.expires_at = expires_at;
+        
+        Ok(())
+    }
+
+    /// Withdraws an attestation, returning its rent to the verifier
+    pub fn revoke_human(_ctx: Context<RevokeHuman>) -> Result<()> {
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn create_market(
         ctx: Context<CreateMarket>,
         question: String,
@@ -24,22 +398,181 @@ pub mod prediction_market {
         oracle_feed_id: Option<[u8; 32]>,
         oracle_threshold: Option<i64>,
         oracle_comparison: Option<u8>,
+        claim_deadline: Option<i64>,
+        unclaimed_policy: u8,
+        require_human: bool,
+        eligibility_period: Option<i64>,
+        meta_condition: Option<u8>,
+        metadata: MarketMetadata,
+        schedule: MarketSchedule,
+        limits: MarketLimits,
+        condition: Option<bool>,
     ) -> Result<()> {
         let market = &mut ctx.accounts.market;
         let clock = Clock::get()?;
         
-        require!(question.len() <= 200, ErrorCode::QuestionTooLong);
+        // Only verified humans can open markets restricted to humans
+        if require_human {
+            HumanAttestation::verify(
+                ctx.accounts.creator_attestation.as_deref(),
+                &ctx.accounts.global_state,
+                clock.unix_timestamp,
+            )?;
+        }
+        
+        require!(ctx.accounts.global_state.paused & PAUSE_CREATION == 0, ErrorCode::Paused);
+        check_text(&question, ctx.accounts.config.params.max_question_chars as usize, MAX_QUESTION_BYTES, ErrorCode::QuestionTooLong, ErrorCode::QuestionTooManyBytes)?;
+        check_text(&description, MAX_DESCRIPTION_CHARS, MAX_DESCRIPTION_BYTES, ErrorCode::DescriptionTooLong, ErrorCode::DescriptionTooManyBytes)?;
         require!(end_time > clock.unix_timestamp, ErrorCode::InvalidEndTime);
+        let close_time = schedule.close_time.unwrap_or(end_time);
+        let resolve_after = schedule.resolve_after.unwrap_or(end_time);
+        require!(
+            clock.unix_timestamp < close_time && close_time <= end_time && end_time <= resolve_after,
+            ErrorCode::InvalidSchedule
+        );
+        require!(metadata.category.len() <= MAX_CATEGORY_LEN, ErrorCode::CategoryTooLong);
+        require!(metadata.tags.len() <= MAX_TAGS, ErrorCode::TooManyTags);
+        for tag in &metadata.tags {
+            check_text(tag, MAX_TAG_CHARS, MAX_TAG_BYTES, ErrorCode::TagTooLong, ErrorCode::TagTooManyBytes)?;
+        }
+        require!(metadata.uri.len() <= MAX_METADATA_URI_LEN, ErrorCode::MetadataUriTooLong);
+        check_text(
+            &metadata.resolution_source,
+            MAX_RESOLUTION_SOURCE_CHARS,
+            MAX_RESOLUTION_SOURCE_BYTES,
+            ErrorCode::ResolutionSourceTooLong,
+            ErrorCode::ResolutionSourceTooManyBytes,
+        )?;
+        
+        // Caps default to the config's and are at least one minimum bet
+        let params = &ctx.accounts.config.params;
+        let max_bet_per_user = limits.max_bet_per_user.unwrap_or(params.max_bet_per_user);
+        let max_total_pool = limits.max_total_pool.unwrap_or(params.max_total_pool);
+        require!(
+            max_bet_per_user == 0 || max_bet_per_user >= params.min_bet,
+            ErrorCode::InvalidPositionCap
+        );
+        require!(
+            max_total_pool == 0 || max_total_pool >= max_bet_per_user.max(params.min_bet),
+            ErrorCode::InvalidPositionCap
+        );
+        
+        // Validate unclaimed policy (0=treasury, 1=redistribute)
+        require!(unclaimed_policy <= 1, ErrorCode::InvalidUnclaimedPolicy);
+        if let Some(deadline) = claim_deadline {
+            require!(deadline > resolve_after, ErrorCode::InvalidClaimDeadline);
+        }
+        
+        // Meta markets bet on how the parent market resolves, so betting
+        // must close before the parent's does. The parent stays open until
+        // the meta market has read its outcome.
+        let (parent_market, parent_created_at) = match meta_condition {
+            Some(condition) => {
+                let parent = ctx.accounts.parent_market.as_mut().ok_or(ErrorCode::ParentMarketRequired)?;
+                require!(condition <= META_PARENT_INVALIDATED, ErrorCode::InvalidMetaCondition);
+                require!(!oracle_enabled, ErrorCode::InvalidMetaCondition);
+                require!(!parent.resolved, ErrorCode::AlreadyResolved);
+                require!(close_time <= parent.betting_closes_at(), ErrorCode::InvalidEndTime);
+                parent.child_markets = parent.child_markets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+                (parent.key(), parent.created_at)
+            }
+            None => (Pubkey::default(), 0),
+        };
+        
+        // Conditional markets only settle their own question once the
+        // condition market reaches `condition`, so it must still be open,
+        // and stays so until the condition has been checked
+        let (condition_market, condition_created_at) = match condition {
+            Some(_) => {
+                let parent = ctx.accounts.parent_market.as_mut().ok_or(ErrorCode::ParentMarketRequired)?;
+                require!(meta_condition.is_none(), ErrorCode::InvalidCondition);
+                require!(!parent.resolved, ErrorCode::AlreadyResolved);
+                parent.child_markets = parent.child_markets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+                (parent.key(), parent.created_at)
+            }
+            None => (Pubkey::default(), 0),
+        };
+        
+        // Markets put to an eligibility vote wait in a pending state and
+        // lock the creator's deposit until the tally
+        let eligibility_end = match eligibility_period {
+            Some(period) => {
+                let eligibility_end = clock.unix_timestamp.checked_add(period).ok_or(ErrorCode::MathOverflow)?;
+                require!(period > 0 && eligibility_end < close_time, ErrorCode::InvalidEligibilityPeriod);
+                
+                let ix = anchor_lang::solana_program::system_instruction::transfer(
+                    &ctx.accounts.payer.key(),
+                    &market.key(),
+                    ELIGIBILITY_DEPOSIT,
+                );
+                anchor_lang::solana_program::program::invoke(
+                    &ix,
+                    &[
+                        ctx.accounts.payer.to_account_info(),
+                        market.to_account_info(),
+                    ],
+                )?;
+                eligibility_end
+            }
+            None => 0,
+        };
         
         market.authority = ctx.accounts.authority.key();
+        market.category = [0; MAX_CATEGORY_LEN];
+        market.category[..metadata.category.len()].copy_from_slice(metadata.category.as_bytes());
         market.question = question;
         market.description = description;
         market.end_time = end_time;
+        market.close_time = close_time;
+        market.resolve_after = resolve_after;
+        market.close_trigger = Pubkey::default();
+        market.max_bet_per_user = max_bet_per_user;
+        market.max_total_pool = max_total_pool;
         market.created_at = clock.unix_timestamp;
         market.yes_amount = 0;
         market.no_amount = 0;
         market.resolved = false;
         market.winning_outcome = false;
+        market.yes_bets = 0;
+        market.no_bets = 0;
+        market.claimed_bets = 0;
+        market.claim_deadline = claim_deadline.unwrap_or(0);
+        market.unclaimed_policy = unclaimed_policy;
+        market.claimed_amount = 0;
+        market.unclaimed_settled = false;
+        market.redistribution_pool = 0;
+        market.redistribution_claims = 0;
+        market.lp_shares = 0;
+        market.lp_yes = 0;
+        market.lp_no = 0;
+        market.lp_fees = 0;
+        market.lp_fee_per_share = 0;
+        market.require_human = require_human;
+        market.invalidated = false;
+        market.moderation_status = MODERATION_VISIBLE;
+        market.report_count = 0;
+        market.paused = 0;
+        market.pending = eligibility_end != 0;
+        market.eligibility_end = eligibility_end;
+        market.eligibility_deposit = if market.pending { ELIGIBILITY_DEPOSIT } else { 0 };
+        market.eligibility_depositor = if market.pending { ctx.accounts.payer.key() } else { Pubkey::default() };
+        market.votes_for = 0;
+        market.votes_against = 0;
+        market.parent_market = parent_market;
+        market.parent_created_at = if condition.is_some() { condition_created_at } else { parent_created_at };
+        market.meta_condition = meta_condition.unwrap_or(0);
+        market.condition_market = condition_market;
+        market.condition_outcome = condition.unwrap_or(false);
+        market.condition_met = false;
+        market.resolver_threshold = 0;
+        market.pending_authority = Pubkey::default();
+        market.resolver = Pubkey::default();
+        market.fallback_reason = FALLBACK_NONE;
+        market.version = MARKET_VERSION;
+        market.tags = metadata.tags;
+        market.metadata_uri = metadata.uri;
+        market.metadata_hash = metadata.uri_hash;
+        market.resolution_source = metadata.resolution_source;
         
         // Oracle configuration
         market.oracle_enabled = oracle_enabled;
@@ -50,11 +583,21 @@ pub mod prediction_market {
             require!(oracle_comparison.is_some(), ErrorCode::OracleComparisonRequired);
             
             market.oracle_feed_id = oracle_feed_id.unwrap();
+            let oracle_feeds = &ctx.accounts.config.oracle_feeds;
+            require!(
+                oracle_feeds.is_empty() || oracle_feeds.contains(&market.oracle_feed_id),
+                ErrorCode::OracleFeedNotAllowed
+            );
             market.oracle_threshold = oracle_threshold.unwrap();
             market.oracle_comparison = oracle_comparison.unwrap();
             
-            // Validate comparison type (0=above, 1=below, 2=equals)
+            // Validate comparison type (0=above, 1=below, 2=equals) against
+            // the ones the config allows
             require!(market.oracle_comparison <= 2, ErrorCode::InvalidOracleComparison);
+            require!(
+                ctx.accounts.config.params.allowed_comparisons & (1 << market.oracle_comparison) != 0,
+                ErrorCode::InvalidOracleComparison
+            );
         } else {
             market.oracle_feed_id = [0; 32];
             market.oracle_threshold = 0;
@@ -64,6 +607,13 @@ pub mod prediction_market {
         Ok(())
     }
 
+    pub fn create_user_profile(ctx: Context<CreateUserProfile>) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts.user_profile.init(ctx.accounts.user.key(), clock.unix_timestamp);
+        
+        Ok(())
+    }
+
     pub fn place_bet(
         ctx: Context<PlaceBet>,
         amount: u64,
@@ -74,8 +624,19 @@ pub mod prediction_market {
         let clock = Clock::get()?;
         
         require!(!market.resolved, ErrorCode::MarketResolved);
-        require!(clock.unix_timestamp < market.end_time, ErrorCode::MarketExpired);
-        require!(amount >= 10_000_000, ErrorCode::BetTooSmall); // 0.01 SOL min
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_BETTING)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::MarketExpired);
+        require!(amount >= ctx.accounts.config.params.min_bet, ErrorCode::BetTooSmall);
+        
+        if market.require_human {
+            HumanAttestation::verify(
+                ctx.accounts.human_attestation.as_deref(),
+                &ctx.accounts.global_state,
+                clock.unix_timestamp,
+            )?;
+        }
         
         // Transfer SOL to market account
         let ix = anchor_lang::solana_program::system_instruction::transfer(
@@ -91,226 +652,2506 @@ pub mod prediction_market {
             ],
         )?;
         
+        // Liquidity providers earn a fee on every bet they make room for
+        let fee = if market.lp_shares > 0 {
+            amount.checked_mul(ctx.accounts.config.params.lp_fee_bps as u64).ok_or(ErrorCode::MathOverflow)? / 10_000
+        } else {
+            0
+        };
+        if fee > 0 {
+            market.lp_fees = market.lp_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+            market.lp_fee_per_share = market.lp_fee_per_share
+                .checked_add(fee as u128 * FEE_PRECISION / market.lp_shares as u128)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        let stake = amount - fee;
+        
+        if market.max_bet_per_user != 0 {
+            let held = held_elsewhere(
+                market,
+                &ctx.accounts.user.key(),
+                None,
+                Some(&ctx.accounts.liquidity_position),
+                Some((&ctx.accounts.position, &ctx.accounts.order_book)),
+            )?;
+            market.require_user_cap(held + stake as u128)?;
+        }
+        let total_pool = market
+            .yes_amount
+            .checked_add(market.no_amount)
+            .and_then(|pool| pool.checked_add(stake))
+            .ok_or(ErrorCode::MathOverflow)?;
+        if market.max_total_pool != 0 {
+            require!(total_pool <= market.max_total_pool, ErrorCode::PoolCapExceeded);
+            // Full once no minimum bet fits
+            if market.max_total_pool - total_pool < ctx.accounts.config.params.min_bet {
+                emit!(MarketCapReached {
+                    market: market.key(),
+                    total_pool,
+                    max_total_pool: market.max_total_pool,
+                });
+            }
+        }
+        
         // Update market totals
         if bet_yes {
-            market.yes_amount = market.yes_amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            market.yes_amount = market.yes_amount.checked_add(stake).ok_or(ErrorCode::MathOverflow)?;
+            market.yes_bets = market.yes_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
         } else {
-            market.no_amount = market.no_amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            market.no_amount = market.no_amount.checked_add(stake).ok_or(ErrorCode::MathOverflow)?;
+            market.no_bets = market.no_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
         }
         
         // Record individual bet
         bet.user = ctx.accounts.user.key();
         bet.market = market.key();
-        bet.amount = amount;
+        bet.amount = stake;
         bet.outcome = bet_yes;
         bet.claimed = false;
         bet.timestamp = clock.unix_timestamp;
+        bet.redistribution_claimed = false;
+        bet.recorded = false;
+        bet.version = BET_VERSION;
+        
+        // Update the bettor's profile, creating it on their first bet
+        let profile = &mut ctx.accounts.user_profile;
+        profile.init_if_new(ctx.accounts.user.key(), clock.unix_timestamp);
+        profile.total_volume = profile.total_volume.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        profile.markets_participated = profile.markets_participated.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
         
         Ok(())
     }
 
-    pub fn resolve_market(
-        ctx: Context<ResolveMarket>,
-        outcome: bool,
+    /// Votes on whether a pending market should go live. The vote is
+    /// weighted by the voter's profile reputation at the time of voting;
+    /// voters need `MIN_VOTER_PREDICTIONS` settled predictions or a human
+    /// attestation, so fresh wallets cannot swing the outcome.
+    pub fn vote_eligibility(
+        ctx: Context<VoteEligibility>,
+        approve: bool,
+        reason_hash: [u8; 32],
     ) -> Result<()> {
         let market = &mut ctx.accounts.market;
+        let vote = &mut ctx.accounts.vote;
         let clock = Clock::get()?;
         
-        require!(!market.resolved, ErrorCode::AlreadyResolved);
-        require!(clock.unix_timestamp >= market.end_time, ErrorCode::MarketNotExpired);
-        require!(ctx.accounts.authority.key() == market.authority, ErrorCode::Unauthorized);
+        require!(market.pending, ErrorCode::MarketNotPending);
+        require!(clock.unix_timestamp < market.eligibility_end, ErrorCode::EligibilityVoteClosed);
         
-        // Cannot manually resolve oracle-enabled markets
-        require!(!market.oracle_enabled, ErrorCode::MustUseOracle);
+        // Markets for humans are vetted by humans
+        let profile = &ctx.accounts.voter_profile;
+        let weight = if market.require_human || ctx.accounts.human_attestation.is_some() {
+            HumanAttestation::verify(
+                ctx.accounts.human_attestation.as_deref(),
+                &ctx.accounts.global_state,
+                clock.unix_timestamp,
+            )?;
+            profile.reputation().checked_add(HUMAN_VOTE_WEIGHT).ok_or(ErrorCode::MathOverflow)?
+        } else {
+            require!(
+                profile.wins.saturating_add(profile.losses) >= MIN_VOTER_PREDICTIONS,
+                ErrorCode::InsufficientReputation
+            );
+            profile.reputation()
+        };
+        require!(weight > 0, ErrorCode::InsufficientReputation);
+        if approve {
+            market.votes_for = market.votes_for.checked_add(weight).ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            market.votes_against = market.votes_against.checked_add(weight).ok_or(ErrorCode::MathOverflow)?;
+        }
         
-        market.resolved = true;
-        market.winning_outcome = outcome;
+        vote.market = market.key();
+        vote.voter = ctx.accounts.voter.key();
+        vote.approve = approve;
+        vote.reason_hash = reason_hash;
+        vote.reputation_weight = weight;
+        vote.timestamp = clock.unix_timestamp;
         
         Ok(())
     }
 
-    pub fn resolve_with_oracle(ctx: Context<ResolveWithOracle>) -> Result<()> {
+    /// Counts the eligibility vote once it has ended. A majority of weight
+    /// in favour activates the market; otherwise it is voided like an
+    /// invalidated market. The deposit goes back to whoever paid it either
+    /// way.
+    pub fn tally_eligibility(ctx: Context<TallyEligibility>) -> Result<()> {
         let market = &mut ctx.accounts.market;
         let clock = Clock::get()?;
-        let price_update = &ctx.accounts.price_update;
         
-        // Validations
-        require!(!market.resolved, ErrorCode::AlreadyResolved);
-        require!(clock.unix_timestamp >= market.end_time, ErrorCode::MarketNotExpired);
-        require!(market.oracle_enabled, ErrorCode::OracleNotEnabled);
+        require!(market.pending, ErrorCode::MarketNotPending);
+        require!(clock.unix_timestamp >= market.eligibility_end, ErrorCode::EligibilityVoteNotEnded);
         
-        // Get price from Pyth
-        let price_feed = price_update.get_price_no_older_than(
-            &clock,
-            60, // Accept price up to 60 seconds old
-            &market.oracle_feed_id,
-        )?;
+        let approved = market.votes_for > market.votes_against && !market.invalidated;
+        market.pending = false;
+        if !approved && !market.resolved {
+            settle_market(market, false, true);
+        }
         
-        let current_price = price_feed.price;
-        let threshold = market.oracle_threshold;
+        let deposit = market.eligibility_deposit;
+        market.eligibility_deposit = 0;
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.depositor.to_account_info(), deposit)?;
         
-        // Determine outcome based on comparison type
-        let outcome = match market.oracle_comparison {
-            0 => current_price > threshold, // Above
-            1 => current_price < threshold, // Below
-            2 => current_price == threshold, // Equals (rare)
-            _ => return Err(ErrorCode::InvalidOracleComparison.into()),
-        };
+        emit!(EligibilityTallied {
+            market: market.key(),
+            votes_for: market.votes_for,
+            votes_against: market.votes_against,
+            approved,
+        });
         
-        // Resolve market
-        market.resolved = true;
-        market.winning_outcome = outcome;
+        Ok(())
+    }
+
+    /// Returns an eligibility vote's rent once the vote has been tallied
+    pub fn close_eligibility_vote(ctx: Context<CloseEligibilityVote>) -> Result<()> {
+        let market_info = &ctx.accounts.market;
         
-        msg!("Market resolved with oracle. Price: {}, Threshold: {}, Outcome: {}",
-             current_price, threshold, outcome);
+        // A closed market was tallied long ago
+        if !market_info.data_is_empty() {
+            require_keys_eq!(*market_info.owner, crate::ID, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+            let market = Market::try_deserialize(&mut &market_info.try_borrow_data()?[..])?;
+            require!(!market.pending, ErrorCode::MarketPending);
+        }
         
         Ok(())
     }
 
-    pub fn claim_winnings(ctx: Context<ClaimWinnings>) -> Result<()> {
-        let market = &ctx.accounts.market;
-        let bet = &mut ctx.accounts.bet;
+    /// Deposits inventory on both sides of an open market in exchange for LP
+    /// shares. Once the market has liquidity the amounts are maxima: only
+    /// what matches the current yes/no ratio of LP inventory is taken.
+    pub fn add_liquidity(
+        ctx: Context<AddLiquidity>,
+        amount_yes: u64,
+        amount_no: u64,
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.liquidity_position;
+        let clock = Clock::get()?;
         
-        // Validate market is resolved
-        require!(market.resolved, ErrorCode::MarketNotResolved);
+        require!(!market.resolved, ErrorCode::MarketResolved);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_BETTING)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::MarketExpired);
+        require!(amount_yes > 0 && amount_no > 0, ErrorCode::InvalidLiquidityAmount);
         
-        // Validate user bet on winning outcome
-        require!(bet.outcome == market.winning_outcome, ErrorCode::WrongOutcome);
+        if market.require_human {
+            HumanAttestation::verify(
+                ctx.accounts.human_attestation.as_deref(),
+                &ctx.accounts.global_state,
+                clock.unix_timestamp,
+            )?;
+        }
         
-        // Validate not already claimed
-        require!(!bet.claimed, ErrorCode::AlreadyClaimed);
+        if position.provider == Pubkey::default() {
+            position.provider = ctx.accounts.provider.key();
+            position.market = market.key();
+            position.timestamp = clock.unix_timestamp;
+        }
         
-        // Calculate winnings
-        let total_pool = market.yes_amount.checked_add(market.no_amount).ok_or(ErrorCode::MathOverflow)?;
-        let winning_pool = if market.winning_outcome {
-            market.yes_amount
+        // Pay out fees earned so far before the share count changes
+        let fees = position.harvest_fees(market)?;
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.provider.to_account_info(), fees)?;
+        
+        let (shares, deposit_yes, deposit_no) = if market.lp_shares == 0 {
+            let shares = amount_yes.checked_add(amount_no).ok_or(ErrorCode::MathOverflow)?;
+            (shares, amount_yes, amount_no)
         } else {
-            market.no_amount
+            let total_shares = market.lp_shares as u128;
+            let shares = std::cmp::min(
+                amount_yes as u128 * total_shares / market.lp_yes as u128,
+                amount_no as u128 * total_shares / market.lp_no as u128,
+            );
+            // Round the inventory taken up so existing shares never lose value
+            let deposit_yes = (shares * market.lp_yes as u128).div_ceil(total_shares);
+            let deposit_no = (shares * market.lp_no as u128).div_ceil(total_shares);
+            (
+                u64::try_from(shares).map_err(|_| ErrorCode::MathOverflow)?,
+                deposit_yes as u64,
+                deposit_no as u64,
+            )
         };
+        require!(shares > 0, ErrorCode::InvalidLiquidityAmount);
         
-        require!(winning_pool > 0, ErrorCode::NoWinnings);
+        let deposit = deposit_yes.checked_add(deposit_no).ok_or(ErrorCode::MathOverflow)?;
         
-        // Calculate user's share: (user_bet / winning_pool) * total_pool
-        let winnings = (bet.amount as u128)
-            .checked_mul(total_pool as u128).ok_or(ErrorCode::MathOverflow)?
-            .checked_div(winning_pool as u128).ok_or(ErrorCode::MathOverflow)?
-            as u64;
+        // LP inventory counts toward the same caps as bets
+        if market.max_total_pool != 0 {
+            let total_pool = market
+                .yes_amount
+                .checked_add(market.no_amount)
+                .and_then(|pool| pool.checked_add(deposit))
+                .ok_or(ErrorCode::MathOverflow)?;
+            require!(total_pool <= market.max_total_pool, ErrorCode::PoolCapExceeded);
+        }
+        if market.max_bet_per_user != 0 {
+            // Value of the provider's shares once this deposit lands
+            let lp_tokens = position.lp_tokens.checked_add(shares).ok_or(ErrorCode::MathOverflow)? as u128;
+            let inventory = (market.lp_yes as u128 + market.lp_no as u128 + deposit as u128) * lp_tokens
+                / (market.lp_shares as u128 + shares as u128);
+            let held = held_elsewhere(
+                market,
+                &ctx.accounts.provider.key(),
+                Some(&ctx.accounts.bet),
+                None,
+                Some((&ctx.accounts.order_position, &ctx.accounts.order_book)),
+            )?;
+            market.require_user_cap(held + inventory)?;
+        }
         
-        require!(winnings > 0, ErrorCode::NoWinnings);
+        let ix = anchor_lang::solana_program::system_instruction::transfer(
+            &ctx.accounts.provider.key(),
+            &market.key(),
+            deposit,
+        );
+        anchor_lang::solana_program::program::invoke(
+            &ix,
+            &[
+                ctx.accounts.provider.to_account_info(),
+                market.to_account_info(),
+            ],
+        )?;
         
-        // Transfer winnings from market to user
-        **market.to_account_info().try_borrow_mut_lamports()? = market
-            .to_account_info()
-            .lamports()
-            .checked_sub(winnings)
-            .ok_or(ErrorCode::MathOverflow)?;
+        // LP inventory sits in the pools like any other stake
+        market.yes_amount = market.yes_amount.checked_add(deposit_yes).ok_or(ErrorCode::MathOverflow)?;
+        market.no_amount = market.no_amount.checked_add(deposit_no).ok_or(ErrorCode::MathOverflow)?;
+        market.lp_yes = market.lp_yes.checked_add(deposit_yes).ok_or(ErrorCode::MathOverflow)?;
+        market.lp_no = market.lp_no.checked_add(deposit_no).ok_or(ErrorCode::MathOverflow)?;
+        market.lp_shares = market.lp_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
         
-        **ctx.accounts.user.to_account_info().try_borrow_mut_lamports()? = ctx
-            .accounts
-            .user
-            .to_account_info()
-            .lamports()
-            .checked_add(winnings)
-            .ok_or(ErrorCode::MathOverflow)?;
+        position.lp_tokens = position.lp_tokens.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        position.amount_yes = position.amount_yes.checked_add(deposit_yes).ok_or(ErrorCode::MathOverflow)?;
+        position.amount_no = position.amount_no.checked_add(deposit_no).ok_or(ErrorCode::MathOverflow)?;
+        position.fee_debt = position.accrued_fees(market);
         
-        // Mark as claimed
-        bet.claimed = true;
+        msg!("Liquidity added: {} yes, {} no, {} LP shares", deposit_yes, deposit_no, shares);
         
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(init, payer = authority, space = 8 + 32 + 8 + 8)]
-    pub global_state: Account<'info, GlobalState>,
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Burns LP shares. While the market is open this returns the shares'
+    /// inventory on both sides; once resolved it pays the winning inventory
+    /// out at the pool's odds. Earned fees are paid out either way.
+    pub fn remove_liquidity(ctx: Context<RemoveLiquidity>, lp_tokens: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.liquidity_position;
+        let clock = Clock::get()?;
+        
+        require!(lp_tokens > 0 && lp_tokens <= position.lp_tokens, ErrorCode::InsufficientLpTokens);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+        
+        // Between close and resolution the outcome may already be known
+        require!(
+            market.resolved || clock.unix_timestamp < market.betting_closes_at(),
+            ErrorCode::LiquidityLocked
+        );
+        
+        let fees = position.harvest_fees(market)?;
+        
+        let total_shares = market.lp_shares as u128;
+        let out_yes = (lp_tokens as u128 * market.lp_yes as u128 / total_shares) as u64;
+        let out_no = (lp_tokens as u128 * market.lp_no as u128 / total_shares) as u64;
+        
+        let payout = if market.resolved && !market.refunds_pool() {
+            // The pools are frozen at resolution; only the winning inventory
+            // pays, at the same rate as a winning bet
+            let total_pool = market.yes_amount.checked_add(market.no_amount).ok_or(ErrorCode::MathOverflow)?;
+            let (inventory, winning_pool) = if market.winning_outcome {
+                (out_yes, market.yes_amount)
+            } else {
+                (out_no, market.no_amount)
+            };
+            if inventory == 0 {
+                0
+            } else {
+                (inventory as u128)
+                    .checked_mul(total_pool as u128).ok_or(ErrorCode::MathOverflow)?
+                    .checked_div(winning_pool as u128).ok_or(ErrorCode::MathOverflow)?
+                    as u64
+            }
+        } else {
+            market.yes_amount = market.yes_amount.checked_sub(out_yes).ok_or(ErrorCode::MathOverflow)?;
+            market.no_amount = market.no_amount.checked_sub(out_no).ok_or(ErrorCode::MathOverflow)?;
+            out_yes.checked_add(out_no).ok_or(ErrorCode::MathOverflow)?
+        };
+        
+        market.lp_yes = market.lp_yes.checked_sub(out_yes).ok_or(ErrorCode::MathOverflow)?;
+        market.lp_no = market.lp_no.checked_sub(out_no).ok_or(ErrorCode::MathOverflow)?;
+        market.lp_shares = market.lp_shares.checked_sub(lp_tokens).ok_or(ErrorCode::MathOverflow)?;
+        position.lp_tokens = position.lp_tokens.checked_sub(lp_tokens).ok_or(ErrorCode::MathOverflow)?;
+        position.fee_debt = position.accrued_fees(market);
+        
+        let total = payout.checked_add(fees).ok_or(ErrorCode::MathOverflow)?;
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.provider.to_account_info(), total)?;
+        
+        msg!("Liquidity removed: {} LP shares for {} lamports ({} in fees)", lp_tokens, total, fees);
+        
+        // An empty position is closed so a reused market address starts clean
+        if position.lp_tokens == 0 {
+            position.close(ctx.accounts.provider.to_account_info())?;
+        }
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct CreateMarket<'info> {
-    #[account(init, payer = authority, space = 8 + 32 + 204 + 504 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 32 + 8 + 1)]
-    pub market: Account<'info, Market>,
+    pub fn resolve_market(
+        ctx: Context<ResolveMarket>,
+        outcome: bool,
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+        
+        require!(!market.resolved, ErrorCode::AlreadyResolved);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(clock.unix_timestamp >= market.resolvable_at(), ErrorCode::MarketNotExpired);
+        let signer = ctx.accounts.authority.key();
+        require!(
+            signer == market.authority || (market.resolver != Pubkey::default() && signer == market.resolver),
+            ErrorCode::Unauthorized
+        );
+        
+        // Cannot manually resolve oracle-enabled or meta markets
+        require!(!market.oracle_enabled, ErrorCode::MustUseOracle);
+        require!(market.parent_market == Pubkey::default(), ErrorCode::MustUseParentMarket);
+        require!(market.resolver_threshold == 0, ErrorCode::MustUseResolvers);
+        market.require_condition_met()?;
+        
+        settle_market(market, outcome, false);
+        
+        Ok(())
+    }
+
+    /// Starts handing the market to `new_authority`, who has to accept it.
+    /// `Pubkey::default()` cancels a pending transfer.
+    pub fn propose_authority_transfer(ctx: Context<UpdateMarket>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.market.pending_authority = new_authority;
+        
+        msg!("Market authority transfer to {} pending", new_authority);
+        
+        Ok(())
+    }
+
+    /// Completes a market authority transfer. The resolver delegate and
+    /// close trigger were chosen by the old authority, so they are cleared.
+    pub fn accept_authority_transfer(ctx: Context<AcceptAuthorityTransfer>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let old = market.authority;
+        market.authority = ctx.accounts.pending_authority.key();
+        market.pending_authority = Pubkey::default();
+        market.resolver = Pubkey::default();
+        market.close_trigger = Pubkey::default();
+        
+        emit!(MarketAuthorityTransferred {
+            market: market.key(),
+            old,
+            new: market.authority,
+        });
+        
+        Ok(())
+    }
+
+    /// Lets `resolver` resolve the market alongside its authority.
+    /// `Pubkey::default()` removes the delegate.
+    pub fn delegate_resolver(ctx: Context<UpdateMarket>, resolver: Pubkey) -> Result<()> {
+        ctx.accounts.market.resolver = resolver;
+        
+        msg!("Market resolver delegate set to {}", resolver);
+        
+        Ok(())
+    }
+
+    /// Lets `trigger` close betting early, such as a keeper watching a
+    /// score feed. `Pubkey::default()` removes the trigger.
+    pub fn set_close_trigger(ctx: Context<UpdateMarket>, trigger: Pubkey) -> Result<()> {
+        ctx.accounts.market.close_trigger = trigger;
+        
+        msg!("Market close trigger set to {}", trigger);
+        
+        Ok(())
+    }
+
+    /// Stops betting now instead of at `close_time`, for when the outcome
+    /// becomes obvious early. Either the authority or the close trigger
+    /// can call it, even while the protocol is paused.
+    pub fn close_betting(ctx: Context<CloseBetting>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let closer = ctx.accounts.closer.key();
+        let clock = Clock::get()?;
+        
+        require!(!market.resolved, ErrorCode::AlreadyResolved);
+        require!(
+            closer == market.authority || (market.close_trigger != Pubkey::default() && closer == market.close_trigger),
+            ErrorCode::Unauthorized
+        );
+        require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::BettingAlreadyClosed);
+        
+        market.close_time = clock.unix_timestamp;
+        
+        emit!(BettingClosed {
+            market: market.key(),
+            closed_by: closer,
+            close_time: market.close_time,
+        });
+        
+        Ok(())
+    }
+
+    /// Hands resolution of a manual market to an M-of-N resolver set. From
+    /// then on the market resolves once `threshold` resolvers approve the
+    /// same outcome, and the authority can no longer resolve it alone.
+    pub fn set_resolvers(ctx: Context<SetResolvers>, resolvers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        
+        require!(!market.resolved, ErrorCode::AlreadyResolved);
+        require!(!market.oracle_enabled, ErrorCode::MustUseOracle);
+        require!(market.parent_market == Pubkey::default(), ErrorCode::MustUseParentMarket);
+        ResolverSet::validate(&resolvers, threshold)?;
+        
+        let resolver_set = &mut ctx.accounts.resolver_set;
+        resolver_set.market = market.key();
+        resolver_set.resolvers = resolvers;
+        resolver_set.threshold = threshold;
+        resolver_set.yes_approvals = 0;
+        resolver_set.no_approvals = 0;
+        market.resolver_threshold = threshold;
+        
+        msg!("Market resolves with {} of {} resolvers", threshold, resolver_set.resolvers.len());
+        
+        Ok(())
+    }
+
+    /// Records a resolver's approval of `outcome`, replacing any earlier
+    /// approval of the other outcome. The approval that reaches the
+    /// threshold resolves the market.
+    pub fn approve_resolution(ctx: Context<ApproveResolution>, outcome: bool) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let resolver_set = &mut ctx.accounts.resolver_set;
+        let clock = Clock::get()?;
+        
+        require!(!market.resolved, ErrorCode::AlreadyResolved);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(clock.unix_timestamp >= market.resolvable_at(), ErrorCode::MarketNotExpired);
+        market.require_condition_met()?;
+        
+        let resolver = ctx.accounts.resolver.key();
+        let index = resolver_set
+            .resolvers
+            .iter()
+            .position(|key| *key == resolver)
+            .ok_or(ErrorCode::NotAResolver)?;
+        let bit = 1u16 << index;
+        let approvals = if outcome {
+            resolver_set.no_approvals &= !bit;
+            resolver_set.yes_approvals |= bit;
+            resolver_set.yes_approvals.count_ones()
+        } else {
+            resolver_set.yes_approvals &= !bit;
+            resolver_set.no_approvals |= bit;
+            resolver_set.no_approvals.count_ones()
+        };
+        
+        emit!(ResolutionApproved {
+            market: market.key(),
+            resolver,
+            outcome,
+            approvals: approvals as u8,
+        });
+        
+        if approvals >= resolver_set.threshold as u32 {
+            settle_market(market, outcome, false);
+            msg!("Market resolved by {} resolvers: {}", approvals, outcome);
+        }
+        
+        Ok(())
+    }
+
+    /// Resolves a meta market from its parent once the parent has settled.
+    /// Anyone can call it; the outcome is read straight off the parent. A
+    /// meta market settled some other way, such as by moderation, is only
+    /// released, since its parent cannot be finalized until then.
+    pub fn resolve_meta_market(ctx: Context<ResolveMetaMarket>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let parent_info = ctx.accounts.parent_market.to_account_info();
+        require_keys_eq!(*parent_info.owner, crate::ID, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+        let mut parent = Market::try_deserialize(&mut &parent_info.try_borrow_data()?[..])?;
+        
+        require!(market.parent_created_at != 0, ErrorCode::AlreadyResolved);
+        // A market recreated at the parent's address is not the parent
+        require!(parent.created_at == market.parent_created_at, ErrorCode::InvalidParentMarket);
+        
+        if !market.resolved {
+            require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+            ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+            require!(!market.pending, ErrorCode::MarketPending);
+            require!(parent.resolved, ErrorCode::ParentNotResolved);
+            
+            let outcome = match market.meta_condition {
+                META_PARENT_YES => !parent.invalidated && parent.winning_outcome,
+                META_PARENT_NO => !parent.invalidated && !parent.winning_outcome,
+                _ => parent.invalidated,
+            };
+            settle_market(market, outcome, false);
+            
+            msg!("Meta market resolved from parent: {}", outcome);
+        }
+        
+        market.release_parent(&mut parent)?;
+        parent.try_serialize(&mut &mut parent_info.try_borrow_mut_data()?[..])?;
+        
+        Ok(())
+    }
+
+    /// Checks a conditional market against its condition market once that
+    /// one has settled. Anyone can call it. A met condition lets the market
+    /// resolve on its own question; any other result, including an
+    /// invalidated condition market, invalidates it so stakes are refunded.
+    /// A conditional market settled some other way is only released, since
+    /// its condition market cannot be finalized until then.
+    pub fn resolve_condition(ctx: Context<ResolveCondition>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let condition_info = ctx.accounts.condition_market.to_account_info();
+        
+        require!(!market.condition_met, ErrorCode::ConditionAlreadyMet);
+        require!(market.parent_created_at != 0, ErrorCode::AlreadyResolved);
+        require_keys_eq!(*condition_info.owner, crate::ID, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+        let mut parent = Market::try_deserialize(&mut &condition_info.try_borrow_data()?[..])?;
+        // A market recreated at the condition market's address is not it
+        require!(parent.created_at == market.parent_created_at, ErrorCode::InvalidParentMarket);
+        
+        if !market.resolved {
+            require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+            ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+            require!(!market.pending, ErrorCode::MarketPending);
+            require!(parent.resolved, ErrorCode::ParentNotResolved);
+            
+            let met = !parent.invalidated && parent.winning_outcome == market.condition_outcome;
+            if met {
+                market.condition_met = true;
+            } else {
+                settle_market(market, false, true);
+            }
+            
+            emit!(ConditionResolved {
+                market: market.key(),
+                condition_market: market.condition_market,
+                met,
+            });
+        }
+        
+        market.release_parent(&mut parent)?;
+        parent.try_serialize(&mut &mut condition_info.try_borrow_mut_data()?[..])?;
+        
+        Ok(())
+    }
+
+    pub fn resolve_with_oracle(ctx: Context<ResolveWithOracle>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+        let price_update = &ctx.accounts.price_update;
+        
+        // Validations
+        require!(!market.resolved, ErrorCode::AlreadyResolved);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(clock.unix_timestamp >= market.resolvable_at(), ErrorCode::MarketNotExpired);
+        require!(market.oracle_enabled, ErrorCode::OracleNotEnabled);
+        market.require_condition_met()?;
+        
+        // Get price from Pyth
+        let price_feed = price_update.get_price_no_older_than(
+            &clock,
+            ctx.accounts.config.params.oracle_max_age,
+            &market.oracle_feed_id,
+        )?;
+        
+        let current_price = price_feed.price;
+        let threshold = market.oracle_threshold;
+        
+        // Determine outcome based on comparison type
+        let outcome = match market.oracle_comparison {
+            0 => current_price > threshold, // Above
+            1 => current_price < threshold, // Below
+            2 => current_price == threshold, // Equals (rare)
+            _ => return Err(ErrorCode::InvalidOracleComparison.into()),
+        };
+        
+        // Resolve market
+        settle_market(market, outcome, false);
+        
+        msg!("Market resolved with oracle. Price: {}, Threshold: {}, Outcome: {}",
+             current_price, threshold, outcome);
+        
+        Ok(())
+    }
+
+    /// Settles an oracle market whose price never arrived. Once the config's
+    /// grace period after `resolve_after` has passed, anyone can invalidate it so
+    /// every bet is refunded, unless the config hands such markets to a
+    /// governance `ResolveMarket` proposal instead.
+    pub fn resolve_oracle_fallback(ctx: Context<ResolveOracleFallback>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let params = &ctx.accounts.config.params;
+        let clock = Clock::get()?;
+        
+        require!(!market.resolved, ErrorCode::AlreadyResolved);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_RESOLUTION)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(market.oracle_enabled, ErrorCode::OracleNotEnabled);
+        let fallback_at = market.resolvable_at().checked_add(params.oracle_grace_period).ok_or(ErrorCode::MathOverflow)?;
+        require!(clock.unix_timestamp >= fallback_at, ErrorCode::OracleGracePeriodActive);
+        require!(params.oracle_fallback == ORACLE_FALLBACK_INVALIDATE, ErrorCode::MustUseGovernance);
+        
+        settle_market(market, false, true);
+        market.fallback_reason = FALLBACK_ORACLE_TIMEOUT;
+        
+        msg!("Oracle never delivered, market invalidated");
+        
+        Ok(())
+    }
+
+    pub fn claim_winnings(ctx: Context<ClaimWinnings>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bet = &mut ctx.accounts.bet;
+        let clock = Clock::get()?;
+        
+        ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+        let winnings = market.claimable_winnings(bet, &ctx.accounts.global_state, clock.unix_timestamp)?;
+        
+        // Transfer winnings from market to user
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.user.to_account_info(), winnings)?;
+        
+        // Mark as claimed
+        market.record_claim(bet)?;
+        let profile = &mut ctx.accounts.user_profile;
+        profile.init_if_new(ctx.accounts.user.key(), clock.unix_timestamp);
+        profile.record_result(bet, market, winnings)?;
+        
+        Ok(())
+    }
+
+    /// Claims several markets at once. `remaining_accounts` holds writable
+    /// (market, bet) pairs owned by the signer; bets that cannot claim
+    /// (losing, already claimed, unresolved, past the deadline, paused) are
+    /// skipped and the rest are paid out together.
+    pub fn claim_many<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimMany<'info>>) -> Result<()> {
+        let user = ctx.accounts.user.key();
+        let clock = Clock::get()?;
+        
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(pairs.remainder().is_empty(), ErrorCode::InvalidClaimPair);
+        
+        require!(ctx.accounts.global_state.paused & PAUSE_CLAIMS == 0, ErrorCode::Paused);
+        ctx.accounts.user_profile.init_if_new(user, clock.unix_timestamp);
+        
+        let mut total: u64 = 0;
+        let mut claimed: u32 = 0;
+        for pair in pairs {
+            let mut market = Account::<Market>::try_from(&pair[0])
+                .map_err(|_| error!(ErrorCode::InvalidClaimPair))?;
+            let mut bet = Account::<Bet>::try_from(&pair[1])
+                .map_err(|_| error!(ErrorCode::InvalidClaimPair))?;
+            
+            // Bets are only ever created at their [b"bet", user, market] PDA,
+            // so matching fields are enough to tie the pair together
+            require!(
+                bet.user == user && bet.market == market.key(),
+                ErrorCode::InvalidClaimPair
+            );
+            
+            if market.paused & PAUSE_CLAIMS != 0 {
+                continue;
+            }
+            let Ok(winnings) = market.claimable_winnings(&bet, &ctx.accounts.global_state, clock.unix_timestamp) else {
+                continue;
+            };
+            
+            // Debit each market now, credit the user once at the end
+            let market_info = market.to_account_info();
+            **market_info.try_borrow_mut_lamports()? = market_info
+                .lamports()
+                .checked_sub(winnings)
+                .ok_or(ErrorCode::MathOverflow)?;
+            total = total.checked_add(winnings).ok_or(ErrorCode::MathOverflow)?;
+            claimed += 1;
+            
+            market.record_claim(&mut bet)?;
+            ctx.accounts.user_profile.record_result(&mut bet, &mut market, winnings)?;
+            
+            // Write back right away so a repeated pair sees the claim
+            market.exit(&crate::ID)?;
+            bet.exit(&crate::ID)?;
+        }
+        
+        let user_info = ctx.accounts.user.to_account_info();
+        **user_info.try_borrow_mut_lamports()? = user_info
+            .lamports()
+            .checked_add(total)
+            .ok_or(ErrorCode::MathOverflow)?;
+        
+        msg!("Claimed {} of {} bets: {} lamports", claimed, ctx.remaining_accounts.len() / 2, total);
+        
+        Ok(())
+    }
+
+    /// Returns the stake of a bet on an invalidated market, or on one whose
+    /// pool had nobody backing the winner
+    pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bet = &mut ctx.accounts.bet;
+        let clock = Clock::get()?;
+        
+        require!(market.refunds_pool(), ErrorCode::MarketNotInvalidated);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+        require!(bet.timestamp >= market.created_at, ErrorCode::StaleBet);
+        require!(!bet.claimed, ErrorCode::AlreadyClaimed);
+        if market.claim_deadline != 0 {
+            let deadline = market.effective_claim_deadline(&ctx.accounts.global_state);
+            require!(clock.unix_timestamp < deadline, ErrorCode::ClaimDeadlinePassed);
+        }
+        
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.user.to_account_info(), bet.amount)?;
+        
+        bet.claimed = true;
+        market.claimed_bets = market.claimed_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        
+        Ok(())
+    }
+
+    /// Emits an `UnclaimedPosition` event for every winning, unclaimed bet of
+    /// the market passed in `remaining_accounts`, so the frontend can warn
+    /// users before the claim deadline. Other accounts are skipped.
+    pub fn report_unclaimed<'info>(ctx: Context<'_, '_, 'info, 'info, ReportUnclaimed<'info>>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        
+        require!(market.resolved, ErrorCode::MarketNotResolved);
+        
+        for bet_info in ctx.remaining_accounts.iter() {
+            let Ok(bet) = Account::<Bet>::try_from(bet_info) else {
+                continue;
+            };
+            if bet.market != market.key()
+                || (bet.outcome != market.winning_outcome && !market.refunds_pool())
+                || bet.claimed
+                || bet.timestamp < market.created_at
+            {
+                continue;
+            }
+            
+            emit!(UnclaimedPosition {
+                market: market.key(),
+                bet: bet_info.key(),
+                user: bet.user,
+                amount: bet.amount,
+                claim_deadline: market.claim_deadline,
+            });
+        }
+        
+        Ok(())
+    }
+
+    /// After the claim deadline, applies the market's unclaimed policy to
+    /// everything left in the pool: either sweeps it to the treasury or sets
+    /// it aside for winners who did claim.
+    pub fn settle_unclaimed(ctx: Context<SettleUnclaimed>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+        
+        require!(market.resolved, ErrorCode::MarketNotResolved);
+        require!(market.claim_deadline != 0, ErrorCode::NoClaimDeadline);
+        require!(
+            clock.unix_timestamp >= market.effective_claim_deadline(&ctx.accounts.global_state),
+            ErrorCode::ClaimDeadlineNotReached
+        );
+        require!(!market.unclaimed_settled, ErrorCode::UnclaimedAlreadySettled);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        // Nobody could claim while paused, so nothing counts as forfeited yet
+        ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+        
+        let unclaimed_bets = market.winning_bets().saturating_sub(market.claimed_bets);
+        
+        let market_info = market.to_account_info();
+        let rent = Rent::get()?.minimum_balance(market_info.data_len());
+        let unclaimed_amount = market_info.lamports()
+            .saturating_sub(rent)
+            .saturating_sub(market.lp_entitlement()?);
+        
+        // Redistribution needs forfeited winnings and someone to receive them
+        // Unclaimed refunds always go to the treasury
+        let redistribute = market.unclaimed_policy == 1
+            && !market.refunds_pool()
+            && market.claimed_amount > 0
+            && unclaimed_bets > 0;
+        
+        if redistribute {
+            market.redistribution_pool = unclaimed_amount;
+        } else {
+            transfer_lamports(&market_info, &ctx.accounts.treasury.to_account_info(), unclaimed_amount)?;
+            let treasury = &mut ctx.accounts.treasury;
+            treasury.total_collected = treasury.total_collected.checked_add(unclaimed_amount).ok_or(ErrorCode::MathOverflow)?;
+        }
+        market.unclaimed_settled = true;
+        
+        emit!(UnclaimedSettled {
+            market: market.key(),
+            unclaimed_bets,
+            amount: unclaimed_amount,
+            redistributed: redistribute,
+        });
+        
+        Ok(())
+    }
+
+    pub fn claim_redistribution(ctx: Context<ClaimRedistribution>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bet = &mut ctx.accounts.bet;
+        
+        require!(market.unclaimed_settled && market.redistribution_pool > 0, ErrorCode::NoRedistribution);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+        require!(bet.timestamp >= market.created_at, ErrorCode::StaleBet);
+        require!(bet.outcome == market.winning_outcome, ErrorCode::WrongOutcome);
+        require!(bet.claimed, ErrorCode::NoRedistribution);
+        require!(!bet.redistribution_claimed, ErrorCode::AlreadyClaimed);
+        
+        // Share of the forfeited winnings: (user_bet / claimed_stake) * pool
+        let share = (bet.amount as u128)
+            .checked_mul(market.redistribution_pool as u128).ok_or(ErrorCode::MathOverflow)?
+            .checked_div(market.claimed_amount as u128).ok_or(ErrorCode::MathOverflow)?
+            as u64;
+        
+        transfer_lamports(&market.to_account_info(), &ctx.accounts.user.to_account_info(), share)?;
+        
+        bet.redistribution_claimed = true;
+        market.redistribution_claims = market.redistribution_claims.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        
+        let profile = &mut ctx.accounts.user_profile;
+        profile.realized_pnl = profile.realized_pnl.checked_add(share as i64).ok_or(ErrorCode::MathOverflow)?;
+        
+        Ok(())
+    }
+
+    pub fn close_bet(ctx: Context<CloseBet>) -> Result<()> {
+        let bet = &mut ctx.accounts.bet;
+        let market_info = &ctx.accounts.market;
+        
+        // A closed market has been finalized, so nothing is left to claim
+        // or record
+        if !market_info.data_is_empty() {
+            require_keys_eq!(*market_info.owner, crate::ID, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+            let mut market = Market::try_deserialize(&mut &market_info.try_borrow_data()?[..])?;
+            
+            // Bets left over from a finalized market whose address was reused
+            // can always be closed
+            if bet.timestamp >= market.created_at {
+                require!(market.resolved, ErrorCode::MarketNotResolved);
+                let losing = bet.outcome != market.winning_outcome && !market.refunds_pool();
+                require!(
+                    bet.claimed || losing || market.unclaimed_settled,
+                    ErrorCode::BetNotSettled
+                );
+                
+                // Closing would forfeit a pending redistribution share
+                let redistribution_pending = market.redistribution_pool > 0
+                    && bet.claimed
+                    && bet.outcome == market.winning_outcome
+                    && !bet.redistribution_claimed;
+                require!(!redistribution_pending, ErrorCode::BetNotSettled);
+                
+                // Losing or forfeited bets are never claimed; record them now
+                if !bet.recorded && !market.refunds_pool() {
+                    let profile = &mut ctx.accounts.user_profile;
+                    profile.init_if_new(ctx.accounts.user.key(), Clock::get()?.unix_timestamp);
+                    profile.record_result(bet, &mut market, 0)?;
+                    market.try_serialize(&mut &mut market_info.try_borrow_mut_data()?[..])?;
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Records a losing (or forfeited) bet in its owner's profile. Anyone can
+    /// call this, and the market cannot be finalized before every bet is
+    /// recorded, so a bad call cannot be kept off a profile by never
+    /// closing the bet.
+    pub fn record_prediction(ctx: Context<RecordPrediction>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bet = &mut ctx.accounts.bet;
+        
+        require!(market.resolved, ErrorCode::MarketNotResolved);
+        require!(!market.refunds_pool(), ErrorCode::MarketInvalidated);
+        require!(bet.timestamp >= market.created_at, ErrorCode::StaleBet);
+        require!(!bet.recorded, ErrorCode::AlreadyRecorded);
+        
+        // Winners are recorded when they claim
+        require!(
+            bet.outcome != market.winning_outcome || market.unclaimed_settled,
+            ErrorCode::BetNotSettled
+        );
+        
+        ctx.accounts.user_profile.record_result(bet, market, 0)?;
+        
+        Ok(())
+    }
+
+    pub fn finalize_market(ctx: Context<FinalizeMarket>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        
+        require!(market.resolved, ErrorCode::MarketNotResolved);
+        // Order book positions and parlays read their payout from the market
+        require!(!market.has_order_book, ErrorCode::OrderBookOpen);
+        require!(market.open_parlays == 0, ErrorCode::ParlaysOpen);
+        require!(market.child_markets == 0, ErrorCode::ChildMarketsOpen);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        
+        // Counts rebuilt by migration may miss bets, see `LEGACY_CLAIM_PERIOD`
+        let all_claimed = !market.legacy_counts && market.claimed_bets >= market.winning_bets();
+        
+        // Past the deadline, unclaimed winnings are forfeited once settled;
+        // a redistribution gets its own window for claimed winners
+        let settled = if market.redistribution_pool > 0 {
+            let clock = Clock::get()?;
+            market.redistribution_claims >= market.claimed_bets
+                || clock.unix_timestamp >= market.claim_deadline.saturating_add(REDISTRIBUTION_PERIOD)
+        } else {
+            all_claimed || market.unclaimed_settled
+        };
+        require!(settled && market.lp_shares == 0, ErrorCode::MarketNotSettled);
+        // Closing the market would keep unrecorded losses off their profiles
+        require!(
+            market.refunds_pool() || market.recorded_bets >= market.yes_bets.saturating_add(market.no_bets),
+            ErrorCode::PredictionsUnrecorded
+        );
+        
+        // Whatever is left above rent is rounding dust from claim_winnings
+        let market_info = market.to_account_info();
+        let rent = Rent::get()?.minimum_balance(market_info.data_len());
+        let dust = market_info.lamports().saturating_sub(rent);
+        
+        transfer_lamports(&market_info, &ctx.accounts.treasury.to_account_info(), dust)?;
+        
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.total_collected = treasury.total_collected.checked_add(dust).ok_or(ErrorCode::MathOverflow)?;
+        
+        msg!("Market finalized. Dust swept to treasury: {}", dust);
+        
+        Ok(())
+    }
+
+    /// Files a report against a market. Each wallet can report a market once;
+    /// the reason itself lives off-chain and is committed to by its hash.
+    pub fn report_market(
+        ctx: Context<ReportMarket>,
+        report_type: u8,
+        reason_hash: [u8; 32],
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let report = &mut ctx.accounts.report;
+        let clock = Clock::get()?;
+        
+        // Validate report type (0=spam, 1=misleading, 2=offensive, 3=illegal, 4=other)
+        require!(report_type <= 4, ErrorCode::InvalidReportType);
+        
+        report.market = market.key();
+        report.reporter = ctx.accounts.reporter.key();
+        report.report_type = report_type;
+        report.reason_hash = reason_hash;
+        report.status = 0;
+        report.action = 0;
+        report.created_at = clock.unix_timestamp;
+        report.resolved_at = 0;
+        
+        market.report_count = market.report_count.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        
+        emit!(MarketReported {
+            market: market.key(),
+            report: report.key(),
+            reporter: report.reporter,
+            report_type,
+        });
+        
+        Ok(())
+    }
+
+    /// Decides a pending report: 0=dismiss, 1=hide the market, 2=freeze it,
+    /// 3=invalidate it so every bet is refunded
+    pub fn moderate_report(ctx: Context<ModerateReport>, action: u8) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let report = &mut ctx.accounts.report;
+        let clock = Clock::get()?;
+        
+        require!(report.status == 0, ErrorCode::ReportAlreadyResolved);
+        // Refunds must stay claimable; only dismissals are left
+        require!(!market.invalidated || action == 0, ErrorCode::MarketInvalidated);
+        
+        match action {
+            0 => {}
+            1 => market.moderation_status = MODERATION_HIDDEN,
+            2 => market.moderation_status = MODERATION_FROZEN,
+            3 => {
+                // Payouts may have started once a market resolves
+                require!(!market.resolved, ErrorCode::MarketResolved);
+                settle_market(market, false, true);
+                market.moderation_status = MODERATION_HIDDEN;
+            }
+            _ => return Err(ErrorCode::InvalidModerationAction.into()),
+        }
+        
+        report.status = if action == 0 { 1 } else { 2 };
+        report.action = action;
+        report.resolved_at = clock.unix_timestamp;
+        
+        emit!(MarketModerated {
+            market: market.key(),
+            report: report.key(),
+            moderator: ctx.accounts.moderator.key(),
+            action,
+        });
+        
+        Ok(())
+    }
+
+    /// Makes a hidden or frozen market visible and active again
+    pub fn restore_market(ctx: Context<RestoreMarket>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        
+        require!(!market.invalidated, ErrorCode::MarketInvalidated);
+        if market.moderation_status == MODERATION_FROZEN {
+            market.claims_resumed_at = Clock::get()?.unix_timestamp;
+        }
+        market.moderation_status = MODERATION_VISIBLE;
+        
+        emit!(MarketRestored {
+            market: market.key(),
+            moderator: ctx.accounts.moderator.key(),
+        });
+        
+        Ok(())
+    }
+
+    /// Opens a limit order book for the market's YES shares. The book
+    /// escrows the collateral behind every order and position on it.
+    pub fn initialize_order_book(ctx: Context<InitializeOrderBook>) -> Result<()> {
+        order_book::initialize_order_book(ctx)
+    }
+
+    /// Posts a limit order for `quantity` YES shares at `price` and matches
+    /// it against the book, crossing at most `max_matches` resting orders.
+    /// The order escrows its collateral plus the taker fee; fills settle
+    /// into positions once `consume_events` runs.
+    pub fn place_order(
+        ctx: Context<PlaceOrder>,
+        side: u8,
+        price: u64,
+        quantity: u64,
+        max_matches: u16,
+    ) -> Result<()> {
+        order_book::place_order(ctx, side, price, quantity, max_matches)
+    }
+
+    /// Crosses up to `limit` overlapping bids and asks. Anyone can crank it,
+    /// e.g. when a taker capped its matches or the event queue was full;
+    /// a queue still too full for a fill has to be consumed first.
+    pub fn match_orders(ctx: Context<MatchOrders>, limit: u16) -> Result<()> {
+        order_book::match_orders(ctx, limit)
+    }
+
+    /// Pulls a resting order and refunds its unfilled collateral and fee.
+    /// Stays open after betting closes so funds never sit in the book.
+    pub fn cancel_order(ctx: Context<CancelOrder>, order_id: u64) -> Result<()> {
+        order_book::cancel_order(ctx, order_id)
+    }
+
+    /// Settles up to `limit` queued fills into their owners' positions,
+    /// passed as writable remaining accounts in queue order. Anyone can
+    /// crank it.
+    pub fn consume_events<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>,
+        limit: u16,
+    ) -> Result<()> {
+        order_book::consume_events(ctx, limit)
+    }
+
+    /// Pays out an order book position once the market resolves: a full
+    /// share payout for each winning share, or the collateral back if the
+    /// market was invalidated, plus any refunds owed. Closes the position.
+    pub fn claim_position(ctx: Context<ClaimPosition>) -> Result<()> {
+        order_book::claim_position(ctx)
+    }
+
+    /// Closes a resolved market's order book once every order was cancelled
+    /// or filled and every position claimed, so the market can be finalized.
+    /// The rent goes back to the market authority.
+    pub fn close_order_book(ctx: Context<CloseOrderBook>) -> Result<()> {
+        order_book::close_order_book(ctx)
+    }
+
+    /// Turns `sets * SHARE_LAMPORTS` of collateral into as many YES and NO
+    /// shares in the signer's order book position. One side of a set always
+    /// pays a full share, and an invalidated market, such as a conditional
+    /// one whose condition failed, refunds the collateral instead.
+    pub fn split_position(ctx: Context<SplitPosition>, sets: u64) -> Result<()> {
+        order_book::split_position(ctx, sets)
+    }
+
+    /// Burns `sets` matching YES and NO shares for their collateral before
+    /// the market resolves. A position can only take out collateral it paid
+    /// in, so invalidation refunds stay covered; sets bought below a full
+    /// share pay out the difference at claim time instead.
+    pub fn merge_position(ctx: Context<MergePosition>, sets: u64) -> Result<()> {
+        order_book::merge_position(ctx, sets)
+    }
+
+    /// Creates the house vault that backs parlay payouts
+    pub fn initialize_house_vault(ctx: Context<InitializeHouseVault>) -> Result<()> {
+        parlay::initialize_house_vault(ctx)
+    }
+
+    /// Adds liquidity to the house vault for shares of its assets, which
+    /// grow with lost parlay stakes and shrink with winning payouts
+    pub fn deposit_house_liquidity(ctx: Context<DepositHouseLiquidity>, amount: u64) -> Result<()> {
+        parlay::deposit_house_liquidity(ctx, amount)
+    }
+
+    /// Asks to redeem `shares`, replacing any earlier request. The
+    /// withdrawal can be made once `HOUSE_WITHDRAWAL_DELAY` has passed, for
+    /// the lower of the shares' value now and then, so a depositor who
+    /// sees a parlay win cannot leave before it is settled.
+    pub fn request_house_withdrawal(ctx: Context<RequestHouseWithdrawal>, shares: u64) -> Result<()> {
+        parlay::request_house_withdrawal(ctx, shares)
+    }
+
+    /// Redeems the requested house shares within `HOUSE_WITHDRAWAL_WINDOW`
+    /// of the request's delay ending, as long as what stays behind still
+    /// covers every open parlay's payout
+    pub fn withdraw_house_liquidity(ctx: Context<WithdrawHouseLiquidity>) -> Result<()> {
+        parlay::withdraw_house_liquidity(ctx)
+    }
+
+    /// Bets `stake` that every oracle market passed, writable, in
+    /// `remaining_accounts` resolves to the matching entry of `outcomes`.
+    /// Each leg is priced from its pool odds now, and the house vault takes
+    /// the other side within the config's risk limits. Fails if the payout
+    /// fell below `min_payout`.
+    pub fn place_parlay<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PlaceParlay<'info>>,
+        outcomes: Vec<bool>,
+        stake: u64,
+        min_payout: u64,
+    ) -> Result<()> {
+        parlay::place_parlay(ctx, outcomes, stake, min_payout)
+    }
+
+    /// Settles a parlay against its leg markets, passed writable in leg
+    /// order as `remaining_accounts`. Anyone can call it. A parlay loses as
+    /// soon as one leg resolves against it; otherwise it waits for every
+    /// leg, and invalidated legs are voided, dividing their odds out of the
+    /// payout. Closes the parlay and lets its legs be finalized.
+    pub fn settle_parlay<'info>(ctx: Context<'_, '_, 'info, 'info, SettleParlay<'info>>) -> Result<()> {
+        parlay::settle_parlay(ctx)
+    }
+
+    /// Upgrades a market written by an older program version to the current
+    /// layout, along with any of its bets passed as remaining accounts.
+    /// Anyone can run it; the payer covers the extra rent.
+    pub fn migrate_market<'info>(ctx: Context<'_, '_, 'info, 'info, MigrateMarket<'info>>) -> Result<()> {
+        let payer = &ctx.accounts.payer;
+        let system_program = &ctx.accounts.system_program;
+        let market_info = ctx.accounts.market.to_account_info();
+        let market_key = market_info.key();
+        
+        let mut market = match read_legacy::<Market, LegacyMarket>(&market_info, LEGACY_MARKET_SPACE)? {
+            Some(legacy) => {
+                // Unmigrated bets are invisible to the counts, so nothing
+                // may settle by counting claims; give them a deadline instead
+                let mut market = legacy.upgrade();
+                let now = Clock::get()?.unix_timestamp;
+                market.claim_deadline = now.max(market.end_time).checked_add(LEGACY_CLAIM_PERIOD).ok_or(ErrorCode::MathOverflow)?;
+                market
+            }
+            None => Market::try_deserialize(&mut &market_info.try_borrow_data()?[..])?,
+        };
+        
+        let mut migrated: u32 = 0;
+        for bet_info in ctx.remaining_accounts {
+            require!(bet_info.is_writable, ErrorCode::InvalidMigrationAccount);
+            let Some(legacy) = read_legacy::<Bet, LegacyBet>(bet_info, LEGACY_BET_SPACE)? else {
+                let bet = Bet::try_deserialize(&mut &bet_info.try_borrow_data()?[..])?;
+                require_keys_eq!(bet.market, market_key, ErrorCode::InvalidMigrationAccount);
+                continue;
+            };
+            require_keys_eq!(legacy.market, market_key, ErrorCode::InvalidMigrationAccount);
+            
+            // Legacy markets kept no bet counts; rebuild them as their
+            // bets come in, exactly once per bet
+            market.count_legacy_bet(&legacy)?;
+            migrate_account(bet_info, payer, system_program, 8 + Bet::INIT_SPACE, &legacy.upgrade())?;
+            migrated += 1;
+        }
+        
+        market.version = MARKET_VERSION;
+        migrate_account(&market_info, payer, system_program, 8 + Market::INIT_SPACE, &market)?;
+        
+        msg!("Market migrated with {} legacy bets", migrated);
+        
+        Ok(())
+    }
+
+    /// Upgrades the global state to the current layout
+    pub fn migrate_global_state(ctx: Context<MigrateGlobalState>) -> Result<()> {
+        let info = ctx.accounts.global_state.to_account_info();
+        let mut global_state = match read_legacy::<GlobalState, LegacyGlobalState>(&info, LEGACY_GLOBAL_STATE_SPACE)? {
+            Some(legacy) => legacy.upgrade(),
+            None => GlobalState::try_deserialize(&mut &info.try_borrow_data()?[..])?,
+        };
+        global_state.version = GLOBAL_STATE_VERSION;
+        
+        migrate_account(
+            &info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            8 + GlobalState::INIT_SPACE,
+            &global_state,
+        )
+    }
+}
+
+/// Reads a `T` account in its pre-versioning layout `L`, or returns `None`
+/// if it has already been migrated. Legacy accounts are recognized by the
+/// exact size the old program allocated for them.
+fn read_legacy<T: anchor_lang::Discriminator, L: AnchorDeserialize>(account: &AccountInfo, legacy_space: usize) -> Result<Option<L>> {
+    require_keys_eq!(*account.owner, crate::ID, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+    if account.data_len() != legacy_space {
+        return Ok(None);
+    }
+    
+    let data = account.try_borrow_data()?;
+    require!(data[..8] == T::DISCRIMINATOR, anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch);
+    let legacy = L::deserialize(&mut &data[8..]).map_err(|_| anchor_lang::error::ErrorCode::AccountDidNotDeserialize)?;
+    Ok(Some(legacy))
+}
+
+/// Grows a program account to `space` bytes, topping up its rent from
+/// `payer`, and writes `state` to it in the current layout
+fn migrate_account<'info, T: AccountSerialize>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    state: &T,
+) -> Result<()> {
+    require_keys_eq!(*account.owner, crate::ID, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+    
+    if account.data_len() < space {
+        let top_up = Rent::get()?.minimum_balance(space).saturating_sub(account.lamports());
+        if top_up > 0 {
+            let ix = anchor_lang::solana_program::system_instruction::transfer(&payer.key(), account.key, top_up);
+            anchor_lang::solana_program::program::invoke(
+                &ix,
+                &[payer.to_account_info(), account.clone(), system_program.to_account_info()],
+            )?;
+        }
+        account.realloc(space, true)?;
+    }
+    
+    state.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+    
+    Ok(())
+}
+
+/// Resolves `market`, or invalidates it so every bet is refunded, and logs
+/// the transition. An outcome nobody backed while the other side did is an
+/// invalidation too, so the losers get their stakes back instead of the
+/// pool being swept; order book markets keep the outcome, since their
+/// positions pay out from it.
+fn settle_market(market: &mut Account<Market>, outcome: bool, invalidated: bool) {
+    let (winning_pool, losing_pool) = if outcome {
+        (market.yes_amount, market.no_amount)
+    } else {
+        (market.no_amount, market.yes_amount)
+    };
+    // Nobody backed the winner, so the pool is refunded; an order book
+    // still settles its positions on the outcome itself
+    let pool_refunded = winning_pool == 0 && losing_pool > 0;
+    let invalidated = invalidated || (pool_refunded && !market.has_order_book);
+    
+    market.resolved = true;
+    market.winning_outcome = outcome;
+    market.invalidated = invalidated;
+    market.pool_refunded = pool_refunded;
+    
+    emit!(MarketResolved {
+        market: market.key(),
+        outcome,
+        invalidated,
+    });
+}
+
+/// Moves lamports out of a program-owned account
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    **from.try_borrow_mut_lamports()? = from
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    **to.try_borrow_mut_lamports()? = to
+        .lamports()
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(())
+}
+
+/// Deserializes a position account the user may not have opened yet
+fn read_position<T: AccountDeserialize>(account: &AccountInfo) -> Result<Option<T>> {
+    if *account.owner != crate::ID || account.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(T::try_deserialize(&mut &account.try_borrow_data()?[..])?))
+}
+
+/// Lamports a user holds in `market` outside the position an instruction
+/// is changing, which the caller leaves out (`None`) and counts itself:
+/// their bet's stake, their share of the liquidity inventory, and the
+/// collateral behind their order book position, resting orders and queued
+/// fills. Together these are what `max_bet_per_user` caps. Parlays don't
+/// count; their stakes go to the house vault, not the market.
+fn held_elsewhere(
+    market: &Market,
+    owner: &Pubkey,
+    bet: Option<&AccountInfo>,
+    liquidity_position: Option<&AccountInfo>,
+    order_book: Option<(&AccountInfo, &AccountInfo)>,
+) -> Result<u128> {
+    let mut held = 0u128;
+    if let Some(bet) = bet {
+        held += read_position::<Bet>(bet)?.map_or(0, |bet| bet.amount as u128);
+    }
+    if let Some(position) = liquidity_position {
+        held += read_position::<LiquidityPosition>(position)?
+            .map_or(0, |position| market.liquidity_value(position.lp_tokens));
+    }
+    if let Some((position, order_book)) = order_book {
+        held += read_position::<Position>(position)?.map_or(0, |position| position.cost as u128);
+        if *order_book.owner == crate::ID && !order_book.data_is_empty() {
+            let data = order_book.try_borrow_data()?;
+            require!(data[..8] == <OrderBook as anchor_lang::Discriminator>::DISCRIMINATOR, anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch);
+            let book: &OrderBook = bytemuck::from_bytes(&data[8..8 + std::mem::size_of::<OrderBook>()]);
+            held += book.committed(owner) as u128;
+        }
+    }
+    Ok(held)
+}
+
+/// Checks user-facing text against its character limit, then against the
+/// bytes its account field reserves
+fn check_text(
+    text: &str,
+    max_chars: usize,
+    max_bytes: usize,
+    chars_error: ErrorCode,
+    bytes_error: ErrorCode,
+) -> Result<()> {
+    if text.chars().count() > max_chars {
+        return Err(chars_error.into());
+    }
+    if text.len() > max_bytes {
+        return Err(bytes_error.into());
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GlobalState::INIT_SPACE,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Treasury::INIT_SPACE,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(seeds = [b"global_state"], bump, has_one = authority)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds = [b"config"], bump, has_one = authority @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApplyConfigUpdate<'info> {
+    #[account(mut, seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptConfigAuthority<'info> {
+    #[account(mut, seeds = [b"config"], bump, has_one = pending_authority @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, Config>,
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferAuthority<'info> {
+    #[account(mut, seeds = [b"global_state"], bump, has_one = authority @ ErrorCode::Unauthorized)]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(mut, seeds = [b"global_state"], bump, has_one = pending_authority @ ErrorCode::Unauthorized)]
+    pub global_state: Account<'info, GlobalState>,
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetHumanVerifier<'info> {
+    #[account(mut, seeds = [b"global_state"], bump, has_one = authority)]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
+    #[account(mut, seeds = [b"global_state"], bump, has_one = authority)]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolPause<'info> {
+    #[account(mut, seeds = [b"global_state"], bump, has_one = guardian @ ErrorCode::Unauthorized)]
+    pub global_state: Account<'info, GlobalState>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMarketPause<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump, has_one = guardian @ ErrorCode::Unauthorized)]
+    pub global_state: Account<'info, GlobalState>,
+    pub guardian: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetModerator<'info> {
+    #[account(mut, seeds = [b"global_state"], bump, has_one = authority)]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AttestHuman<'info> {
+    #[account(
+        init_if_needed,
+        payer = verifier,
+        space = 8 + HumanAttestation::INIT_SPACE,
+        seeds = [b"human", user.key().as_ref()],
+        bump
+    )]
+    pub attestation: Account<'info, HumanAttestation>,
+    #[account(
+        seeds = [b"global_state"],
+        bump,
+        constraint = global_state.human_verifier == verifier.key() @ ErrorCode::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    /// CHECK: only used as the attested wallet
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub verifier: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeHuman<'info> {
+    #[account(
+        mut,
+        seeds = [b"human", attestation.user.as_ref()],
+        bump,
+        has_one = verifier,
+        close = verifier
+    )]
+    pub attestation: Account<'info, HumanAttestation>,
+    #[account(mut)]
+    pub verifier: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateMarket<'info> {
+    #[account(init, payer = payer, space = 8 + Market::INIT_SPACE)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Required when creating a `require_human` market
+    #[account(seeds = [b"human", authority.key().as_ref()], bump)]
+    pub creator_attestation: Option<Account<'info, HumanAttestation>>,
+    /// Required when creating a meta or conditional market
+    #[account(mut)]
+    pub parent_market: Option<Account<'info, Market>>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    /// Can be a multisig vault PDA signing through its program
+    pub authority: Signer<'info>,
+    /// Funds the market account and any eligibility deposit
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateUserProfile<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserProfile::INIT_SPACE,
+        seeds = [b"profile", user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceBet<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + Bet::INIT_SPACE,
+        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserProfile::INIT_SPACE,
+        seeds = [b"profile", user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Required to bet on a `require_human` market
+    #[account(seeds = [b"human", user.key().as_ref()], bump)]
+    pub human_attestation: Option<Account<'info, HumanAttestation>>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    /// CHECK: the user's liquidity position, counted toward their cap; may not exist
+    #[account(seeds = [b"liquidity", user.key().as_ref(), market.key().as_ref()], bump)]
+    pub liquidity_position: UncheckedAccount<'info>,
+    /// CHECK: the user's order book position, counted toward their cap; may not exist
+    #[account(seeds = [b"position", market.key().as_ref(), user.key().as_ref()], bump)]
+    pub position: UncheckedAccount<'info>,
+    /// CHECK: the market's order book, whose orders and fills count toward the cap; may not exist
+    #[account(seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VoteEligibility<'info> {
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + EligibilityVote::INIT_SPACE,
+        seeds = [b"eligibility_vote", market.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote: Account<'info, EligibilityVote>,
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"profile", voter.key().as_ref()], bump)]
+    pub voter_profile: Account<'info, UserProfile>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Required to vote on a `require_human` market
+    #[account(seeds = [b"human", voter.key().as_ref()], bump)]
+    pub human_attestation: Option<Account<'info, HumanAttestation>>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TallyEligibility<'info> {
     #[account(mut)]
+    pub market: Account<'info, Market>,
+    /// CHECK: receives the deposit back; checked against market.eligibility_depositor
+    #[account(mut, address = market.eligibility_depositor)]
+    pub depositor: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseEligibilityVote<'info> {
+    #[account(
+        mut,
+        seeds = [b"eligibility_vote", market.key().as_ref(), voter.key().as_ref()],
+        bump,
+        has_one = voter,
+        close = voter
+    )]
+    pub vote: Account<'info, EligibilityVote>,
+    /// CHECK: may already be closed by finalize_market; deserialized in the handler otherwise
+    #[account(address = vote.market)]
+    pub market: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + LiquidityPosition::INIT_SPACE,
+        seeds = [b"liquidity", provider.key().as_ref(), market.key().as_ref()],
+        bump
+    )]
+    pub liquidity_position: Account<'info, LiquidityPosition>,
+    /// Required to provide liquidity to a `require_human` market
+    #[account(seeds = [b"human", provider.key().as_ref()], bump)]
+    pub human_attestation: Option<Account<'info, HumanAttestation>>,
+    /// CHECK: the provider's bet, counted toward their cap; may not exist
+    #[account(seeds = [b"bet", provider.key().as_ref(), market.key().as_ref()], bump)]
+    pub bet: UncheckedAccount<'info>,
+    /// CHECK: the provider's order book position, counted toward their cap; may not exist
+    #[account(seeds = [b"position", market.key().as_ref(), provider.key().as_ref()], bump)]
+    pub order_position: UncheckedAccount<'info>,
+    /// CHECK: the market's order book, whose orders and fills count toward the cap; may not exist
+    #[account(seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        seeds = [b"liquidity", provider.key().as_ref(), market.key().as_ref()],
+        bump,
+        has_one = provider
+    )]
+    pub liquidity_position: Account<'info, LiquidityPosition>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMarket<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarket<'info> {
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub market: Account<'info, Market>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBetting<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    pub closer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthorityTransfer<'info> {
+    #[account(mut, has_one = pending_authority @ ErrorCode::Unauthorized)]
+    pub market: Account<'info, Market>,
+    pub pending_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetResolvers<'info> {
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub market: Account<'info, Market>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ResolverSet::INIT_SPACE,
+        seeds = [b"resolver_set", market.key().as_ref()],
+        bump
+    )]
+    pub resolver_set: Account<'info, ResolverSet>,
     pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveResolution<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"resolver_set", market.key().as_ref()], bump, has_one = market)]
+    pub resolver_set: Account<'info, ResolverSet>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    pub resolver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMetaMarket<'info> {
+    #[account(mut, has_one = parent_market @ ErrorCode::InvalidParentMarket)]
+    pub market: Account<'info, Market>,
+    /// CHECK: deserialized in the handler, which releases the meta market's hold on it
+    #[account(mut)]
+    pub parent_market: UncheckedAccount<'info>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveCondition<'info> {
+    // Checked before `condition_market`: an unconditional market names the
+    // system program, which a transaction can never pass as writable
+    #[account(
+        mut,
+        constraint = market.condition_market != Pubkey::default() @ ErrorCode::NotConditional,
+        has_one = condition_market @ ErrorCode::InvalidParentMarket
+    )]
+    pub market: Account<'info, Market>,
+    /// CHECK: deserialized in the handler, which releases the conditional market's hold on it
+    #[account(mut)]
+    pub condition_market: UncheckedAccount<'info>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveWithOracle<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    /// The Pyth price update account
+    pub price_update: Account<'info, PriceUpdateV2>,
+    pub caller: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveOracleFallback<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimWinnings<'info> {
+    #[account(
+        mut,
+        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Bets placed before profiles existed claim into a fresh one
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserProfile::INIT_SPACE,
+        seeds = [b"profile", user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimMany<'info> {
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserProfile::INIT_SPACE,
+        seeds = [b"profile", user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct PlaceBet<'info> {
+pub struct ClaimRefund<'info> {
+    #[account(
+        mut,
+        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReportUnclaimed<'info> {
+    pub market: Account<'info, Market>,
+}
+
+#[derive(Accounts)]
+pub struct SettleUnclaimed<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRedistribution<'info> {
+    #[account(
+        mut,
+        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut, seeds = [b"profile", user.key().as_ref()], bump)]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseBet<'info> {
+    #[account(
+        mut,
+        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
+        bump,
+        has_one = user,
+        close = user
+    )]
+    pub bet: Account<'info, Bet>,
+    /// CHECK: may already be closed by finalize_market; deserialized in the handler otherwise
+    #[account(mut, address = bet.market)]
+    pub market: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserProfile::INIT_SPACE,
+        seeds = [b"profile", user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordPrediction<'info> {
+    #[account(mut, has_one = market)]
+    pub bet: Account<'info, Bet>,
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"profile", bet.user.as_ref()], bump)]
+    pub user_profile: Account<'info, UserProfile>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeMarket<'info> {
+    /// Also the pool's vault; closing it returns the rent to the creator
+    #[account(mut, has_one = authority, close = authority)]
+    pub market: Account<'info, Market>,
+    /// CHECK: receives the market's rent; must be the market creator
+    #[account(mut)]
+    pub authority: UncheckedAccount<'info>,
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct ReportMarket<'info> {
     #[account(
         init,
-        payer = user,
-        space = 8 + 32 + 32 + 8 + 1 + 1 + 8,
-        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
+        payer = reporter,
+        space = 8 + ContentReport::INIT_SPACE,
+        seeds = [b"report", market.key().as_ref(), reporter.key().as_ref()],
         bump
     )]
-    pub bet: Account<'info, Bet>,
+    pub report: Account<'info, ContentReport>,
     #[account(mut)]
     pub market: Account<'info, Market>,
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub reporter: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ResolveMarket<'info> {
+pub struct ModerateReport<'info> {
+    #[account(mut, has_one = market)]
+    pub report: Account<'info, ContentReport>,
     #[account(mut)]
     pub market: Account<'info, Market>,
-    pub authority: Signer<'info>,
+    #[account(seeds = [b"global_state"], bump, has_one = moderator @ ErrorCode::Unauthorized)]
+    pub global_state: Account<'info, GlobalState>,
+    pub moderator: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ResolveWithOracle<'info> {
+pub struct RestoreMarket<'info> {
     #[account(mut)]
     pub market: Account<'info, Market>,
-    /// The Pyth price update account
-    pub price_update: Account<'info, PriceUpdateV2>,
-    pub caller: Signer<'info>,
+    #[account(seeds = [b"global_state"], bump, has_one = moderator @ ErrorCode::Unauthorized)]
+    pub global_state: Account<'info, GlobalState>,
+    pub moderator: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimWinnings<'info> {
-    #[account(
-        mut,
-        seeds = [b"bet", user.key().as_ref(), market.key().as_ref()],
-        bump
-    )]
-    pub bet: Account<'info, Bet>,
+pub struct MigrateMarket<'info> {
+    /// CHECK: may still be in an older layout; owner and discriminator are checked in the handler
     #[account(mut)]
-    pub market: Account<'info, Market>,
+    pub market: UncheckedAccount<'info>,
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGlobalState<'info> {
+    /// CHECK: may still be in an older layout; owner and discriminator are checked in the handler
+    #[account(mut, seeds = [b"global_state"], bump)]
+    pub global_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct GlobalState {
     pub authority: Pubkey,
     pub total_markets: u64,
     pub total_volume: u64,
+    pub human_verifier: Pubkey,
+    pub moderator: Pubkey,
+    pub guardian: Pubkey,
+    pub paused: u8,                   // PAUSE_* flags
+    // Versioning
+    pub version: u8,                  // GLOBAL_STATE_VERSION once migrated
+    pub pending_authority: Pubkey,    // default = no transfer pending
+    pub claims_resumed_at: i64,       // last time PAUSE_CLAIMS was lifted
+    pub reserved: [u8; 24],           // room for new fields
+}
+
+impl GlobalState {
+    /// Replaces the pause flags, noting when claims resume
+    fn set_paused(&mut self, flags: u8, now: i64) {
+        if self.paused & PAUSE_CLAIMS != 0 && flags & PAUSE_CLAIMS == 0 {
+            self.claims_resumed_at = now;
+        }
+        self.paused = flags;
+    }
+    
+    /// Fails if `action` is paused protocol-wide or on `market`
+    fn require_active(&self, market: &Market, action: u8) -> Result<()> {
+        require!((self.paused | market.paused) & action == 0, ErrorCode::Paused);
+        Ok(())
+    }
+}
+
+/// Protocol parameters owned by the config authority
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct ConfigParams {
+    pub min_bet: u64,                 // lamports
+    pub max_question_chars: u16,      // at most MAX_QUESTION_CHARS
+    pub oracle_max_age: u64,          // seconds a Pyth price stays usable
+    pub allowed_comparisons: u8,      // bit i allows oracle_comparison i
+    pub timelock: i64,                // seconds before the next update applies
+    pub lp_fee_bps: u16,              // at most MAX_LP_FEE_BPS
+    pub oracle_grace_period: i64,     // seconds after resolve_after before the oracle fallback
+    pub oracle_fallback: u8,          // ORACLE_FALLBACK_* value
+    pub max_bet_per_user: u64,        // default market cap; 0 = none
+    pub max_total_pool: u64,          // default market cap; 0 = none
+    pub taker_fee_bps: u16,           // order book fill fees; maker <= taker <= MAX_LP_FEE_BPS
+    pub maker_fee_bps: u16,
+    pub max_parlay_payout: u64,       // lamports; 0 = none
+    pub house_liability_bps: u16,     // share of house assets open parlays may claim
+    pub max_parlay_leg_odds: u16,     // payout multiple a leg may add; at least 1
+    pub min_parlay_pool: u64,         // lamports a leg's pool needs to price from
+}
+
+impl Default for ConfigParams {
+    fn default() -> Self {
+        Self {
+            min_bet: 10_000_000, // 0.01 SOL
+            max_question_chars: MAX_QUESTION_CHARS as u16,
+            oracle_max_age: 60,
+            allowed_comparisons: 0b111,
+            timelock: 2 * 24 * 60 * 60,
+            lp_fee_bps: LP_FEE_BPS as u16,
+            oracle_grace_period: 7 * 24 * 60 * 60,
+            oracle_fallback: ORACLE_FALLBACK_INVALIDATE,
+            max_bet_per_user: 0,
+            max_total_pool: 0,
+            taker_fee_bps: TAKER_FEE_BPS,
+            maker_fee_bps: MAKER_FEE_BPS,
+            max_parlay_payout: MAX_PARLAY_PAYOUT,
+            house_liability_bps: HOUSE_LIABILITY_BPS,
+            max_parlay_leg_odds: MAX_PARLAY_LEG_ODDS,
+            min_parlay_pool: MIN_PARLAY_POOL,
+        }
+    }
+}
+
+impl ConfigParams {
+    fn validate(&self) -> Result<()> {
+        require!(self.min_bet > 0, ErrorCode::InvalidConfig);
+        require!(
+            self.max_question_chars > 0 && self.max_question_chars as usize <= MAX_QUESTION_CHARS,
+            ErrorCode::InvalidConfig
+        );
+        require!(self.oracle_max_age > 0, ErrorCode::InvalidConfig);
+        require!(self.allowed_comparisons & !0b111 == 0, ErrorCode::InvalidConfig);
+        require!((0..=MAX_CONFIG_TIMELOCK).contains(&self.timelock), ErrorCode::InvalidConfig);
+        require!(self.lp_fee_bps <= MAX_LP_FEE_BPS, ErrorCode::InvalidConfig);
+        require!(self.oracle_grace_period > 0, ErrorCode::InvalidConfig);
+        require!(self.oracle_fallback <= ORACLE_FALLBACK_GOVERNANCE, ErrorCode::InvalidConfig);
+        require!(
+            self.max_bet_per_user == 0 || self.max_bet_per_user >= self.min_bet,
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.max_total_pool == 0 || self.max_total_pool >= self.max_bet_per_user.max(self.min_bet),
+            ErrorCode::InvalidConfig
+        );
+        require!(
+            self.maker_fee_bps <= self.taker_fee_bps && self.taker_fee_bps <= MAX_LP_FEE_BPS,
+            ErrorCode::InvalidConfig
+        );
+        require!(self.house_liability_bps <= 10_000, ErrorCode::InvalidConfig);
+        require!(self.max_parlay_leg_odds > 0, ErrorCode::InvalidConfig);
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,    // default = no transfer pending
+    pub params: ConfigParams,
+    pub queued_params: ConfigParams,
+    pub update_eta: i64,              // 0 = no update queued
+    #[max_len(MAX_ORACLE_FEEDS)]
+    pub oracle_feeds: Vec<[u8; 32]>,  // Pyth feeds markets may use; empty = any
+    // Versioning
+    pub version: u8,                  // CONFIG_VERSION
+    pub reserved: [u8; 64],           // room for new fields
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct HumanAttestation {
+    pub user: Pubkey,                 // 32
+    pub verifier: Pubkey,             // 32
+    pub issued_at: i64,               // 8
+    pub expires_at: i64,              // 8 (0 = never)
+}
+
+impl HumanAttestation {
+    /// Checks that an attestation was passed, comes from the current
+    /// verifier and has not expired
+    fn verify(attestation: Option<&HumanAttestation>, global_state: &GlobalState, now: i64) -> Result<()> {
+        let attestation = attestation.ok_or(ErrorCode::HumanVerificationRequired)?;
+        require!(
+            global_state.human_verifier != Pubkey::default()
+                && attestation.verifier == global_state.human_verifier,
+            ErrorCode::HumanVerificationRequired
+        );
+        require!(
+            attestation.expires_at == 0 || now < attestation.expires_at,
+            ErrorCode::AttestationExpired
+        );
+        Ok(())
+    }
+}
+
+/// When a market stops taking bets and when it can be resolved. Both
+/// default to `end_time`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct MarketSchedule {
+    pub close_time: Option<i64>,
+    pub resolve_after: Option<i64>,
+}
+
+/// Position caps of a market, in lamports staked. `None` takes the
+/// config's default and `Some(0)` means no cap.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct MarketLimits {
+    pub max_bet_per_user: Option<u64>,
+    pub max_total_pool: Option<u64>,
+}
+
+/// Descriptive fields of a market that the program stores but never reads
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct MarketMetadata {
+    pub category: String,
+    pub tags: Vec<String>,
+    pub uri: String,
+    pub uri_hash: [u8; 32],
+    pub resolution_source: String,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Market {
     pub authority: Pubkey,           // 32
-    pub question: String,             // 4 + 200
-    pub description: String,          // 4 + 500
+    pub category: [u8; 32],           // 32 (at MARKET_CATEGORY_OFFSET)
+    #[max_len(MAX_QUESTION_BYTES)]
+    pub question: String,             // 4 + 400
+    #[max_len(MAX_DESCRIPTION_BYTES)]
+    pub description: String,          // 4 + 1000
     pub end_time: i64,                // 8
     pub created_at: i64,              // 8
     pub yes_amount: u64,              // 8
     pub no_amount: u64,               // 8
     pub resolved: bool,               // 1
     pub winning_outcome: bool,        // 1
+    // Settlement tracking
+    pub yes_bets: u32,                // 4
+    pub no_bets: u32,                 // 4
+    pub claimed_bets: u32,            // 4
+    // Claim deadline
+    pub claim_deadline: i64,          // 8 (0 = no deadline)
+    pub unclaimed_policy: u8,         // 1 (0=treasury, 1=redistribute)
+    pub claimed_amount: u64,          // 8 (winning stake already claimed)
+    pub unclaimed_settled: bool,      // 1
+    pub redistribution_pool: u64,     // 8
+    pub redistribution_claims: u32,   // 4
+    // Liquidity providers
+    pub lp_shares: u64,               // 8
+    pub lp_yes: u64,                  // 8 (LP inventory in yes_amount)
+    pub lp_no: u64,                   // 8 (LP inventory in no_amount)
+    pub lp_fees: u64,                 // 8 (fees not yet paid out)
+    pub lp_fee_per_share: u128,       // 16 (scaled by FEE_PRECISION)
+    // Proof of personhood
+    pub require_human: bool,          // 1
+    // Moderation
+    pub invalidated: bool,            // 1 (resolved with every bet refunded)
+    pub report_count: u32,            // 4
+    pub moderation_status: u8,        // 1 (0=visible, 1=hidden, 2=frozen)
+    // Emergency pause
+    pub paused: u8,                   // 1 (PAUSE_* flags)
+    // Eligibility vote
+    pub pending: bool,                // 1 (waiting for the vote)
+    pub eligibility_end: i64,         // 8 (0 = no vote)
+    pub eligibility_deposit: u64,     // 8
+    pub votes_for: u64,               // 8 (reputation weight)
+    pub votes_against: u64,           // 8 (reputation weight)
+    // Meta prediction
+    pub parent_market: Pubkey,        // 32 (default = not a meta market)
+    pub meta_condition: u8,           // 1 (META_PARENT_* value)
+    // Metadata
+    #[max_len(MAX_TAGS, MAX_TAG_BYTES)]
+    pub tags: Vec<String>,            // 4 + 5 * (4 + 40)
+    #[max_len(MAX_METADATA_URI_LEN)]
+    pub metadata_uri: String,         // 4 + 200 (off-chain description, images)
+    pub metadata_hash: [u8; 32],      // 32 (hash of the URI's content)
+    #[max_len(MAX_RESOLUTION_SOURCE_BYTES)]
+    pub resolution_source: String,    // 4 + 200
     // Oracle fields
     pub oracle_enabled: bool,         // 1
     pub oracle_feed_id: [u8; 32],     // 32
     pub oracle_threshold: i64,        // 8
     pub oracle_comparison: u8,        // 1 (0=above, 1=below, 2=equals)
+    // Versioning
+    pub version: u8,                  // 1 (MARKET_VERSION once migrated)
+    // Resolver set
+    pub resolver_threshold: u8,       // 1 (0 = authority resolves alone)
+    // Authority handover
+    pub pending_authority: Pubkey,    // 32 (default = no transfer pending)
+    pub resolver: Pubkey,             // 32 (delegate that can resolve; default = none)
+    // Fallback resolution
+    pub fallback_reason: u8,          // 1 (FALLBACK_* value)
+    // Schedule
+    pub close_time: i64,              // 8 (betting stops; 0 = end_time)
+    pub resolve_after: i64,           // 8 (resolution opens; 0 = end_time)
+    pub close_trigger: Pubkey,        // 32 (may close betting early; default = none)
+    // Position caps
+    pub max_bet_per_user: u64,        // 8 (lamports per user across bet, liquidity and order book; 0 = none)
+    pub max_total_pool: u64,          // 8 (yes + no pools; 0 = none)
+    // Order book
+    pub has_order_book: bool,         // 1 (positions still claim against it)
+    // Conditional market
+    pub condition_market: Pubkey,     // 32 (default = unconditional)
+    pub condition_outcome: bool,      // 1 (outcome the condition market must reach)
+    pub condition_met: bool,          // 1 (own resolution unlocked)
+    pub claims_resumed_at: i64,       // 8 (last time PAUSE_CLAIMS was lifted or the market unfrozen)
+    // Parlays
+    pub open_parlays: u32,            // 4 (unsettled parlays with a leg here)
+    // Migration
+    pub legacy_counts: bool,          // 1 (bet counts only cover migrated bets)
+    // Dependent markets
+    pub child_markets: u32,           // 4 (meta and conditional markets still reading this one's outcome)
+    pub parent_created_at: i64,       // 8 (parent or condition market's created_at; 0 once released)
+    pub pool_refunded: bool,          // 1 (nobody in the pool backed the winner; bets are refunded)
+    pub recorded_bets: u32,           // 4 (bets booked in their owner's profile)
+    pub eligibility_depositor: Pubkey, // 32 (payer of eligibility_deposit)
+    pub reserved: [u8; 14],           // 14 (room for new fields)
+}
+
+impl Market {
+    /// When betting stops. Markets created before schedules close at
+    /// `end_time`.
+    pub fn betting_closes_at(&self) -> i64 {
+        if self.close_time == 0 { self.end_time } else { self.close_time }
+    }
+
+    /// When the market can be resolved, `end_time` for markets created
+    /// before schedules
+    pub fn resolvable_at(&self) -> i64 {
+        if self.resolve_after == 0 { self.end_time } else { self.resolve_after }
+    }
+
+    /// Conditional markets only resolve on their own question once
+    /// `resolve_condition` found their condition met
+    fn require_condition_met(&self) -> Result<()> {
+        require!(
+            self.condition_market == Pubkey::default() || self.condition_met,
+            ErrorCode::ConditionNotMet
+        );
+        Ok(())
+    }
+
+    /// The category name without its zero padding
+    pub fn category_name(&self) -> &str {
+        let len = self.category.iter().position(|&b| b == 0).unwrap_or(MAX_CATEGORY_LEN);
+        std::str::from_utf8(&self.category[..len]).unwrap_or_default()
+    }
+
+    /// Checks that `bet` can claim right now and returns its payout:
+    /// (bet / winning_pool) * total_pool
+    pub fn claimable_winnings(&self, bet: &Bet, global_state: &GlobalState, now: i64) -> Result<u64> {
+        // Validate market is resolved
+        require!(self.resolved, ErrorCode::MarketNotResolved);
+        require!(!self.refunds_pool(), ErrorCode::MarketInvalidated);
+        require!(self.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        
+        // A finalized market's address can be reused; its old bets must not
+        // claim against the new pool
+        require!(bet.timestamp >= self.created_at, ErrorCode::StaleBet);
+        
+        // Validate user bet on winning outcome
+        require!(bet.outcome == self.winning_outcome, ErrorCode::WrongOutcome);
+        
+        // Validate not already claimed
+        require!(!bet.claimed, ErrorCode::AlreadyClaimed);
+        
+        // Validate the claim window is still open
+        if self.claim_deadline != 0 {
+            require!(now < self.effective_claim_deadline(global_state), ErrorCode::ClaimDeadlinePassed);
+        }
+        
+        // Calculate winnings
+        let total_pool = self.yes_amount.checked_add(self.no_amount).ok_or(ErrorCode::MathOverflow)?;
+        let winning_pool = if self.winning_outcome {
+            self.yes_amount
+        } else {
+            self.no_amount
+        };
+        
+        require!(winning_pool > 0, ErrorCode::NoWinnings);
+        
+        let winnings = (bet.amount as u128)
+            .checked_mul(total_pool as u128).ok_or(ErrorCode::MathOverflow)?
+            .checked_div(winning_pool as u128).ok_or(ErrorCode::MathOverflow)?
+            as u64;
+        
+        require!(winnings > 0, ErrorCode::NoWinnings);
+        
+        Ok(winnings)
+    }
+
+    /// The claim deadline, pushed back so that claimers always get
+    /// `CLAIM_RESUME_GRACE` after claims last resumed from a pause or freeze
+    fn effective_claim_deadline(&self, global_state: &GlobalState) -> i64 {
+        let resumed_at = self.claims_resumed_at.max(global_state.claims_resumed_at);
+        if resumed_at == 0 {
+            return self.claim_deadline;
+        }
+        self.claim_deadline.max(resumed_at.saturating_add(CLAIM_RESUME_GRACE))
+    }
+
+    /// Drops this market's hold on `parent` once it no longer needs the
+    /// parent's outcome, letting the parent be finalized
+    fn release_parent(&mut self, parent: &mut Market) -> Result<()> {
+        parent.child_markets = parent.child_markets.checked_sub(1).ok_or(ErrorCode::MathOverflow)?;
+        self.parent_created_at = 0;
+        Ok(())
+    }
+
+    /// Adds a bet migrated from the legacy layout to the settlement counts
+    fn count_legacy_bet(&mut self, bet: &LegacyBet) -> Result<()> {
+        if bet.outcome {
+            self.yes_bets = self.yes_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            self.no_bets = self.no_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        }
+        if bet.claimed {
+            self.claimed_bets = self.claimed_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+            self.claimed_amount = self.claimed_amount.checked_add(bet.amount).ok_or(ErrorCode::MathOverflow)?;
+            // Claimed legacy bets migrate as recorded, see `LegacyBet::upgrade`
+            self.recorded_bets = self.recorded_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Share of the liquidity inventory `lp_tokens` are worth
+    fn liquidity_value(&self, lp_tokens: u64) -> u128 {
+        if self.lp_shares == 0 {
+            return 0;
+        }
+        (self.lp_yes as u128 + self.lp_no as u128) * lp_tokens as u128 / self.lp_shares as u128
+    }
+
+    /// Fails unless `held`, everything one user would hold in the market
+    /// (see `held_elsewhere`), fits under `max_bet_per_user`
+    fn require_user_cap(&self, held: u128) -> Result<()> {
+        require!(held <= self.max_bet_per_user as u128, ErrorCode::UserPositionCapExceeded);
+        Ok(())
+    }
+
+    /// Whether pool bets get their stake back instead of a payout: the
+    /// market was invalidated, or nobody in the pool backed the winner
+    fn refunds_pool(&self) -> bool {
+        self.invalidated || self.pool_refunded
+    }
+
+    /// Bets owed a payout: the winning side, or every bet once refunded
+    fn winning_bets(&self) -> u32 {
+        if self.refunds_pool() {
+            self.yes_bets.saturating_add(self.no_bets)
+        } else if self.winning_outcome {
+            self.yes_bets
+        } else {
+            self.no_bets
+        }
+    }
+
+    /// What the remaining LP shares can still withdraw after resolution
+    fn lp_entitlement(&self) -> Result<u64> {
+        if self.refunds_pool() {
+            return Ok(self.lp_yes
+                .checked_add(self.lp_no)
+                .and_then(|inventory| inventory.checked_add(self.lp_fees))
+                .ok_or(ErrorCode::MathOverflow)?);
+        }
+        let (inventory, winning_pool) = if self.winning_outcome {
+            (self.lp_yes, self.yes_amount)
+        } else {
+            (self.lp_no, self.no_amount)
+        };
+        let payout = if inventory == 0 {
+            0
+        } else {
+            let total_pool = self.yes_amount.checked_add(self.no_amount).ok_or(ErrorCode::MathOverflow)?;
+            (inventory as u128 * total_pool as u128 / winning_pool as u128) as u64
+        };
+        Ok(payout.checked_add(self.lp_fees).ok_or(ErrorCode::MathOverflow)?)
+    }
+
+    /// Marks `bet` as paid and updates the settlement counters
+    fn record_claim(&mut self, bet: &mut Bet) -> Result<()> {
+        bet.claimed = true;
+        self.claimed_bets = self.claimed_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        self.claimed_amount = self.claimed_amount.checked_add(bet.amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Treasury {
+    pub total_collected: u64,         // 8
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Bet {
     pub user: Pubkey,                 // 32
     pub market: Pubkey,               // 32
@@ -318,11 +3159,471 @@ pub struct Bet {
     pub outcome: bool,                // 1 (true = YES, false = NO)
     pub claimed: bool,                // 1
     pub timestamp: i64,               // 8
+    pub redistribution_claimed: bool, // 1
+    pub recorded: bool,               // 1 (counted in the user's profile)
+    // Versioning
+    pub version: u8,                  // 1 (BET_VERSION once migrated)
+    pub reserved: [u8; 32],           // 32 (room for new fields)
+}
+
+// Layouts written by the program before versioning, kept so
+// `migrate_market` and `migrate_global_state` can convert them. They carry
+// the same discriminators as their current counterparts and are recognized
+// by the exact space the old program allocated.
+
+pub const LEGACY_MARKET_SPACE: usize = 8 + 32 + 204 + 504 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 32 + 8 + 1;
+pub const LEGACY_BET_SPACE: usize = 8 + 32 + 32 + 8 + 1 + 1 + 8;
+pub const LEGACY_GLOBAL_STATE_SPACE: usize = 8 + 32 + 8 + 8;
+
+#[derive(AnchorDeserialize)]
+pub struct LegacyMarket {
+    pub authority: Pubkey,
+    pub question: String,
+    pub description: String,
+    pub end_time: i64,
+    pub created_at: i64,
+    pub yes_amount: u64,
+    pub no_amount: u64,
+    pub resolved: bool,
+    pub winning_outcome: bool,
+    pub oracle_enabled: bool,
+    pub oracle_feed_id: [u8; 32],
+    pub oracle_threshold: i64,
+    pub oracle_comparison: u8,
+}
+
+impl LegacyMarket {
+    /// The market in the current layout. Bet counts start at zero and are
+    /// rebuilt by `Market::count_legacy_bet`.
+    fn upgrade(self) -> Market {
+        Market {
+            authority: self.authority,
+            category: [0; MAX_CATEGORY_LEN],
+            question: self.question,
+            description: self.description,
+            end_time: self.end_time,
+            created_at: self.created_at,
+            yes_amount: self.yes_amount,
+            no_amount: self.no_amount,
+            resolved: self.resolved,
+            winning_outcome: self.winning_outcome,
+            yes_bets: 0,
+            no_bets: 0,
+            claimed_bets: 0,
+            claim_deadline: 0,
+            unclaimed_policy: 0,
+            claimed_amount: 0,
+            unclaimed_settled: false,
+            redistribution_pool: 0,
+            redistribution_claims: 0,
+            lp_shares: 0,
+            lp_yes: 0,
+            lp_no: 0,
+            lp_fees: 0,
+            lp_fee_per_share: 0,
+            require_human: false,
+            invalidated: false,
+            report_count: 0,
+            moderation_status: MODERATION_VISIBLE,
+            paused: 0,
+            pending: false,
+            eligibility_end: 0,
+            eligibility_deposit: 0,
+            votes_for: 0,
+            votes_against: 0,
+            parent_market: Pubkey::default(),
+            meta_condition: META_PARENT_YES,
+            tags: Vec::new(),
+            metadata_uri: String::new(),
+            metadata_hash: [0; 32],
+            resolution_source: String::new(),
+            oracle_enabled: self.oracle_enabled,
+            oracle_feed_id: self.oracle_feed_id,
+            oracle_threshold: self.oracle_threshold,
+            oracle_comparison: self.oracle_comparison,
+            version: MARKET_VERSION,
+            resolver_threshold: 0,
+            pending_authority: Pubkey::default(),
+            resolver: Pubkey::default(),
+            fallback_reason: FALLBACK_NONE,
+            close_time: 0,
+            resolve_after: 0,
+            close_trigger: Pubkey::default(),
+            max_bet_per_user: 0,
+            max_total_pool: 0,
+            has_order_book: false,
+            condition_market: Pubkey::default(),
+            condition_outcome: false,
+            condition_met: false,
+            claims_resumed_at: 0,
+            open_parlays: 0,
+            legacy_counts: true,
+            child_markets: 0,
+            parent_created_at: 0,
+            pool_refunded: false,
+            recorded_bets: 0,
+            eligibility_depositor: Pubkey::default(),
+            reserved: [0; 14],
+        }
+    }
+}
+
+#[derive(AnchorDeserialize)]
+pub struct LegacyBet {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub amount: u64,
+    pub outcome: bool,
+    pub claimed: bool,
+    pub timestamp: i64,
+}
+
+impl LegacyBet {
+    fn upgrade(&self) -> Bet {
+        Bet {
+            user: self.user,
+            market: self.market,
+            amount: self.amount,
+            outcome: self.outcome,
+            claimed: self.claimed,
+            timestamp: self.timestamp,
+            redistribution_claimed: false,
+            // The payout of a bet claimed before profiles existed is
+            // unknown, so it stays out of the profile
+            recorded: self.claimed,
+            version: BET_VERSION,
+            reserved: [0; 32],
+        }
+    }
+}
+
+#[derive(AnchorDeserialize)]
+pub struct LegacyGlobalState {
+    pub authority: Pubkey,
+    pub total_markets: u64,
+    pub total_volume: u64,
+}
+
+impl LegacyGlobalState {
+    fn upgrade(self) -> GlobalState {
+        GlobalState {
+            authority: self.authority,
+            total_markets: self.total_markets,
+            total_volume: self.total_volume,
+            human_verifier: Pubkey::default(),
+            moderator: Pubkey::default(),
+            guardian: Pubkey::default(),
+            paused: 0,
+            version: GLOBAL_STATE_VERSION,
+            pending_authority: Pubkey::default(),
+            claims_resumed_at: 0,
+            reserved: [0; 24],
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct UserProfile {
+    pub user: Pubkey,                 // 32
+    pub total_volume: u64,            // 8
+    pub markets_participated: u32,    // 4
+    pub wins: u32,                    // 4
+    pub losses: u32,                  // 4
+    pub realized_pnl: i64,            // 8
+    pub brier_sum: u64,               // 8 (basis points, summed over wins + losses)
+    pub created_at: i64,              // 8
+}
+
+impl UserProfile {
+    fn init(&mut self, user: Pubkey, now: i64) {
+        self.user = user;
+        self.total_volume = 0;
+        self.markets_participated = 0;
+        self.wins = 0;
+        self.losses = 0;
+        self.realized_pnl = 0;
+        self.brier_sum = 0;
+        self.created_at = now;
+    }
+
+    /// Initializes a profile that `init_if_needed` just created
+    fn init_if_new(&mut self, user: Pubkey, now: i64) {
+        if self.user == Pubkey::default() {
+            self.init(user, now);
+        }
+    }
+
+    /// Average Brier score in basis points: 0 is perfect, 10_000 always
+    /// wrong. A bet counts as forecasting its side at the side's final
+    /// share of the pool, the odds it settled at.
+    pub fn brier_score(&self) -> u64 {
+        let predictions = self.wins as u64 + self.losses as u64;
+        if predictions == 0 {
+            return 0;
+        }
+        self.brier_sum / predictions
+    }
+
+    /// Voting weight: up to 10 per settled prediction, scaled by accuracy.
+    /// A perfect record of ten predictions weighs 100; always being wrong
+    /// weighs nothing.
+    pub fn reputation(&self) -> u64 {
+        let predictions = self.wins as u64 + self.losses as u64;
+        predictions * (10_000 - self.brier_score().min(10_000)) / 1_000
+    }
+
+    /// Books a resolved bet that returned `payout`. Only bets against
+    /// someone else, on a market the bettor could not decide, count as
+    /// predictions; the rest just add to the PnL.
+    fn record_result(&mut self, bet: &mut Bet, market: &mut Market, payout: u64) -> Result<()> {
+        // A lone side forecasts certainty, so it would score perfectly
+        let scored = bet.user != market.authority
+            && bet.user != market.resolver
+            && market.yes_amount > 0
+            && market.no_amount > 0;
+        if scored {
+            let total_pool = market.yes_amount.checked_add(market.no_amount).ok_or(ErrorCode::MathOverflow)?;
+            let side_pool = if bet.outcome {
+                market.yes_amount
+            } else {
+                market.no_amount
+            };
+            let forecast = side_pool as u128 * 10_000 / total_pool as u128;
+            
+            let correct = bet.outcome == market.winning_outcome;
+            let error = if correct { 10_000 - forecast } else { forecast };
+            self.brier_sum = self.brier_sum.checked_add((error * error / 10_000) as u64).ok_or(ErrorCode::MathOverflow)?;
+            if correct {
+                self.wins = self.wins.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+            } else {
+                self.losses = self.losses.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+            }
+        }
+        
+        let pnl = (payout as i64).checked_sub(bet.amount as i64).ok_or(ErrorCode::MathOverflow)?;
+        self.realized_pnl = self.realized_pnl.checked_add(pnl).ok_or(ErrorCode::MathOverflow)?;
+        
+        bet.recorded = true;
+        market.recorded_bets = market.recorded_bets.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct LiquidityPosition {
+    pub provider: Pubkey,             // 32
+    pub market: Pubkey,               // 32
+    pub lp_tokens: u64,               // 8
+    pub amount_yes: u64,              // 8 (total deposited)
+    pub amount_no: u64,               // 8 (total deposited)
+    pub fees_earned: u64,             // 8 (total paid out)
+    pub fee_debt: u128,               // 16
+    pub timestamp: i64,               // 8
+}
+
+impl LiquidityPosition {
+    /// Fees accrued to the current shares since the market opened
+    fn accrued_fees(&self, market: &Market) -> u128 {
+        self.lp_tokens as u128 * market.lp_fee_per_share / FEE_PRECISION
+    }
+
+    /// Books the fees earned since the last harvest and returns the amount
+    /// to pay out
+    fn harvest_fees(&mut self, market: &mut Market) -> Result<u64> {
+        let pending = self.accrued_fees(market).saturating_sub(self.fee_debt) as u64;
+        market.lp_fees = market.lp_fees.checked_sub(pending).ok_or(ErrorCode::MathOverflow)?;
+        self.fees_earned = self.fees_earned.checked_add(pending).ok_or(ErrorCode::MathOverflow)?;
+        self.fee_debt = self.accrued_fees(market);
+        Ok(pending)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ResolverSet {
+    pub market: Pubkey,               // 32
+    #[max_len(MAX_RESOLVERS)]
+    pub resolvers: Vec<Pubkey>,       // 4 + 10 * 32
+    pub threshold: u8,                // 1 (approvals needed)
+    pub yes_approvals: u16,           // 2 (bit i = resolvers[i])
+    pub no_approvals: u16,            // 2
+}
+
+impl ResolverSet {
+    /// Fails unless `resolvers` are distinct and `threshold` is reachable
+    fn validate(resolvers: &[Pubkey], threshold: u8) -> Result<()> {
+        require!(
+            !resolvers.is_empty() && resolvers.len() <= MAX_RESOLVERS,
+            ErrorCode::InvalidResolverSet
+        );
+        require!(
+            threshold > 0 && threshold as usize <= resolvers.len(),
+            ErrorCode::InvalidResolverSet
+        );
+        for (i, resolver) in resolvers.iter().enumerate() {
+            require!(!resolvers[..i].contains(resolver), ErrorCode::InvalidResolverSet);
+        }
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct EligibilityVote {
+    pub market: Pubkey,               // 32
+    pub voter: Pubkey,                // 32
+    pub approve: bool,                // 1
+    pub reason_hash: [u8; 32],        // 32
+    pub reputation_weight: u64,       // 8
+    pub timestamp: i64,               // 8
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ContentReport {
+    pub market: Pubkey,               // 32
+    pub reporter: Pubkey,             // 32
+    pub report_type: u8,              // 1 (0=spam, 1=misleading, 2=offensive, 3=illegal, 4=other)
+    pub reason_hash: [u8; 32],        // 32
+    pub status: u8,                   // 1 (0=pending, 1=dismissed, 2=actioned)
+    pub action: u8,                   // 1 (moderation action taken)
+    pub created_at: i64,              // 8
+    pub resolved_at: i64,             // 8
+}
+
+#[event]
+pub struct UnclaimedPosition {
+    pub market: Pubkey,
+    pub bet: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub claim_deadline: i64,
+}
+
+#[event]
+pub struct UnclaimedSettled {
+    pub market: Pubkey,
+    pub unclaimed_bets: u32,
+    pub amount: u64,
+    pub redistributed: bool,
+}
+
+#[event]
+pub struct MarketReported {
+    pub market: Pubkey,
+    pub report: Pubkey,
+    pub reporter: Pubkey,
+    pub report_type: u8,
+}
+
+#[event]
+pub struct MarketModerated {
+    pub market: Pubkey,
+    pub report: Pubkey,
+    pub moderator: Pubkey,
+    pub action: u8,
+}
+
+#[event]
+pub struct MarketRestored {
+    pub market: Pubkey,
+    pub moderator: Pubkey,
+}
+
+#[event]
+pub struct EligibilityTallied {
+    pub market: Pubkey,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub approved: bool,
+}
+
+#[event]
+pub struct ConfigUpdateQueued {
+    pub old: ConfigParams,
+    pub new: ConfigParams,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub old: ConfigParams,
+    pub new: ConfigParams,
+}
+
+#[event]
+pub struct ConfigAuthorityTransferred {
+    pub old: Pubkey,
+    pub new: Pubkey,
+}
+
+#[event]
+pub struct ProtocolAuthorityTransferred {
+    pub old: Pubkey,
+    pub new: Pubkey,
+}
+
+#[event]
+pub struct MarketAuthorityTransferred {
+    pub market: Pubkey,
+    pub old: Pubkey,
+    pub new: Pubkey,
+}
+
+#[event]
+pub struct MarketCapReached {
+    pub market: Pubkey,
+    pub total_pool: u64,
+    pub max_total_pool: u64,
+}
+
+#[event]
+pub struct BettingClosed {
+    pub market: Pubkey,
+    pub closed_by: Pubkey,
+    pub close_time: i64,
+}
+
+#[event]
+pub struct MarketResolved {
+    pub market: Pubkey,
+    pub outcome: bool,
+    pub invalidated: bool,
+}
+
+#[event]
+pub struct ResolutionApproved {
+    pub market: Pubkey,
+    pub resolver: Pubkey,
+    pub outcome: bool,
+    pub approvals: u8,
+}
+
+#[event]
+pub struct ProtocolPauseSet {
+    pub guardian: Pubkey,
+    pub flags: u8,
+}
+
+#[event]
+pub struct MarketPauseSet {
+    pub market: Pubkey,
+    pub guardian: Pubkey,
+    pub flags: u8,
+}
+
+#[event]
+pub struct ConditionResolved {
+    pub market: Pubkey,
+    pub condition_market: Pubkey,
+    pub met: bool,
 }
 
 #[error_code]
 pub enum ErrorCode {
-    #[msg("Question too long (max 200 characters)")]
+    #[msg("Question exceeds the configured character limit")]
     QuestionTooLong,
     #[msg("Invalid end time")]
     InvalidEndTime,
@@ -330,7 +3631,7 @@ pub enum ErrorCode {
     MarketResolved,
     #[msg("Market expired")]
     MarketExpired,
-    #[msg("Bet too small (min 0.01 SOL)")]
+    #[msg("Bet is below the configured minimum")]
     BetTooSmall,
     #[msg("Already resolved")]
     AlreadyResolved,
@@ -361,4 +3662,250 @@ pub enum ErrorCode {
     OracleNotEnabled,
     #[msg("This market must be resolved with oracle")]
     MustUseOracle,
+    // Settlement errors
+    #[msg("Bet belongs to a previous market at this address")]
+    StaleBet,
+    #[msg("Bet still has unclaimed winnings")]
+    BetNotSettled,
+    #[msg("Winning bets are still unclaimed")]
+    MarketNotSettled,
+    // Claim deadline errors
+    #[msg("Claim deadline must be after resolution opens")]
+    InvalidClaimDeadline,
+    #[msg("Invalid unclaimed policy (must be 0 or 1)")]
+    InvalidUnclaimedPolicy,
+    #[msg("Claim deadline has passed")]
+    ClaimDeadlinePassed,
+    #[msg("Market has no claim deadline")]
+    NoClaimDeadline,
+    #[msg("Claim deadline not reached yet")]
+    ClaimDeadlineNotReached,
+    #[msg("Unclaimed winnings already settled")]
+    UnclaimedAlreadySettled,
+    #[msg("No redistribution available")]
+    NoRedistribution,
+    // Batch claim errors
+    #[msg("Remaining accounts must be (market, bet) pairs owned by the signer")]
+    InvalidClaimPair,
+    // Liquidity errors
+    #[msg("Liquidity must be added on both sides")]
+    InvalidLiquidityAmount,
+    #[msg("Not enough LP shares")]
+    InsufficientLpTokens,
+    #[msg("Liquidity is locked until the market resolves")]
+    LiquidityLocked,
+    // Profile errors
+    #[msg("Prediction already recorded")]
+    AlreadyRecorded,
+    // Proof-of-personhood errors
+    #[msg("This market requires a verified human")]
+    HumanVerificationRequired,
+    #[msg("Human verification has expired")]
+    AttestationExpired,
+    #[msg("Attestation expiry must be in the future")]
+    InvalidAttestationExpiry,
+    // Moderation errors
+    #[msg("Invalid report type (must be 0-4)")]
+    InvalidReportType,
+    #[msg("Report already resolved")]
+    ReportAlreadyResolved,
+    #[msg("Invalid moderation action (must be 0-3)")]
+    InvalidModerationAction,
+    #[msg("Market is frozen by moderation")]
+    MarketFrozen,
+    #[msg("Market has been invalidated")]
+    MarketInvalidated,
+    #[msg("Market has not been invalidated")]
+    MarketNotInvalidated,
+    // Emergency pause errors
+    #[msg("This action is paused")]
+    Paused,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    // Eligibility vote errors
+    #[msg("Eligibility period must be positive and end before the market")]
+    InvalidEligibilityPeriod,
+    #[msg("Market is pending its eligibility vote")]
+    MarketPending,
+    #[msg("Market is not pending an eligibility vote")]
+    MarketNotPending,
+    #[msg("Eligibility vote has closed")]
+    EligibilityVoteClosed,
+    #[msg("Eligibility vote has not ended yet")]
+    EligibilityVoteNotEnded,
+    // Meta market errors
+    #[msg("Meta markets need their parent market")]
+    ParentMarketRequired,
+    #[msg("Invalid meta market condition")]
+    InvalidMetaCondition,
+    #[msg("Account is not this market's parent")]
+    InvalidParentMarket,
+    #[msg("Parent market has not resolved yet")]
+    ParentNotResolved,
+    #[msg("Meta markets resolve from their parent market")]
+    MustUseParentMarket,
+    // Metadata errors
+    #[msg("Category too long (max 32 bytes)")]
+    CategoryTooLong,
+    #[msg("Too many tags (max 5)")]
+    TooManyTags,
+    #[msg("Tag too long (max 20 characters)")]
+    TagTooLong,
+    #[msg("Metadata URI too long (max 200 bytes)")]
+    MetadataUriTooLong,
+    #[msg("Resolution source too long (max 100 characters)")]
+    ResolutionSourceTooLong,
+    // Text length errors
+    #[msg("Question too large (max 400 bytes of UTF-8)")]
+    QuestionTooManyBytes,
+    #[msg("Description too long (max 500 characters)")]
+    DescriptionTooLong,
+    #[msg("Description too large (max 1000 bytes of UTF-8)")]
+    DescriptionTooManyBytes,
+    #[msg("Tag too large (max 40 bytes of UTF-8)")]
+    TagTooManyBytes,
+    #[msg("Resolution source too large (max 200 bytes of UTF-8)")]
+    ResolutionSourceTooManyBytes,
+    // Migration errors
+    #[msg("Account cannot be migrated with this market")]
+    InvalidMigrationAccount,
+    // Config errors
+    #[msg("Invalid config parameters")]
+    InvalidConfig,
+    #[msg("No config update is queued")]
+    NoConfigUpdateQueued,
+    #[msg("Config update timelock has not expired")]
+    TimelockNotExpired,
+    // Governance errors
+    #[msg("Invalid governance parameters")]
+    InvalidGovernanceConfig,
+    #[msg("Token amount must be positive")]
+    InvalidTokenAmount,
+    #[msg("Not enough governance tokens deposited")]
+    InsufficientGovernanceTokens,
+    #[msg("Governance tokens are locked until the votes they backed end")]
+    GovernanceTokensLocked,
+    #[msg("No tokens deposited before this proposal was created")]
+    NoVotingWeight,
+    #[msg("Voting on this proposal has closed")]
+    ProposalVotingClosed,
+    #[msg("Voting on this proposal is still open")]
+    ProposalVotingOpen,
+    #[msg("Proposal did not reach quorum or threshold")]
+    ProposalNotPassed,
+    #[msg("Proposal was already executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Oracle allow-list is full")]
+    TooManyOracleFeeds,
+    #[msg("Oracle feed is not on the allow-list")]
+    OracleFeedNotAllowed,
+    // Resolver set errors
+    #[msg("Resolvers must be 1 to 10 distinct keys with a threshold between 1 and their count")]
+    InvalidResolverSet,
+    #[msg("This market resolves through its resolver set")]
+    MustUseResolvers,
+    #[msg("Signer is not one of the market's resolvers")]
+    NotAResolver,
+    // Governance resolution errors
+    #[msg("Proposal targets a different market")]
+    InvalidProposalMarket,
+    #[msg("Governance can only resolve markets left unresolved past the timeout")]
+    ResolutionTimeoutNotReached,
+    // Oracle fallback errors
+    #[msg("Oracle grace period has not passed")]
+    OracleGracePeriodActive,
+    #[msg("Config hands failed oracle markets to governance")]
+    MustUseGovernance,
+    // Schedule errors
+    #[msg("Schedule must satisfy now < close_time <= end_time <= resolve_after")]
+    InvalidSchedule,
+    #[msg("Betting has already closed")]
+    BettingAlreadyClosed,
+    // Position cap errors
+    #[msg("Position caps must fit at least one minimum bet, and the pool cap the user cap")]
+    InvalidPositionCap,
+    #[msg("Would take the user's holdings in the market past its cap")]
+    UserPositionCapExceeded,
+    #[msg("Bet would take the pool past the market's cap")]
+    PoolCapExceeded,
+    // Order book errors
+    #[msg("Order side must be a bid or an ask")]
+    InvalidOrderSide,
+    #[msg("Order price must be between 1 and 9999 basis points")]
+    InvalidOrderPrice,
+    #[msg("Order quantity must be positive")]
+    InvalidOrderQuantity,
+    #[msg("Order book is full")]
+    OrderBookFull,
+    #[msg("Fill event queue is full")]
+    EventQueueFull,
+    #[msg("Order not found")]
+    OrderNotFound,
+    #[msg("Position account does not match the next fill")]
+    InvalidPositionAccount,
+    #[msg("Fills must be consumed before positions are claimed")]
+    FillEventsPending,
+    #[msg("Markets with an order book stay open for position claims")]
+    OrderBookOpen,
+    // Conditional market errors
+    #[msg("A market cannot be both a meta market and a conditional one")]
+    InvalidCondition,
+    #[msg("Market is not conditional")]
+    NotConditional,
+    #[msg("Conditional market must check its condition before resolving")]
+    ConditionNotMet,
+    #[msg("Condition already met")]
+    ConditionAlreadyMet,
+    #[msg("Position does not hold that many full sets")]
+    InsufficientShares,
+    #[msg("Merging can only return collateral the position paid in")]
+    MergeExceedsCost,
+    // Parlay errors
+    #[msg("Parlays need 2 to 8 distinct markets, passed in leg order")]
+    InvalidParlayLegs,
+    #[msg("A parlay leg has no stake on its side to price from")]
+    LegHasNoOdds,
+    #[msg("Parlay payout fell below the minimum")]
+    ParlayOddsMoved,
+    #[msg("Parlay payout exceeds the configured maximum")]
+    ParlayPayoutTooLarge,
+    #[msg("House vault cannot cover this parlay")]
+    HouseCapacityExceeded,
+    #[msg("Withdrawal would leave open parlays uncovered")]
+    HouseLiquidityLocked,
+    #[msg("House amount must be positive and within the deposit")]
+    InvalidHouseAmount,
+    #[msg("Parlay legs are still unresolved")]
+    ParlayLegsPending,
+    // Eligibility voting errors
+    #[msg("Voter needs more settled predictions or a human attestation")]
+    InsufficientReputation,
+    // Order book closing errors
+    #[msg("Order book still holds orders or unclaimed positions")]
+    OrderBookNotEmpty,
+    // Parlay leg errors
+    #[msg("Parlay legs must be oracle-resolved markets")]
+    ParlayLegNotOracle,
+    #[msg("Parlay leg pool is below the configured minimum")]
+    ParlayLegPoolTooSmall,
+    #[msg("Parlay leg odds exceed the configured maximum")]
+    ParlayLegOddsTooHigh,
+    #[msg("Market still backs unsettled parlays")]
+    ParlaysOpen,
+    // Dependent market errors
+    #[msg("Markets built on this one have not read its outcome yet")]
+    ChildMarketsOpen,
+    // Order size errors
+    #[msg("Order collateral is below the configured minimum bet")]
+    OrderTooSmall,
+    // Reputation errors
+    #[msg("Every bet must be recorded in its profile before finalizing")]
+    PredictionsUnrecorded,
+    // House withdrawal errors
+    #[msg("No house withdrawal has been requested")]
+    HouseWithdrawalNotRequested,
+    #[msg("House withdrawal request is still in its delay")]
+    HouseWithdrawalNotReady,
+    #[msg("House withdrawal request expired; request it again")]
+    HouseWithdrawalExpired,
 }
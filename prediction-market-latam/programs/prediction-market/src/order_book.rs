@@ -0,0 +1,851 @@
+//! Central limit order book for trading YES and NO shares next to a
+//! market's pool. Fills queue as events and settle into each trader's
+//! `Position`, which claims against the market's outcome.
+
+use super::*;
+
+/// Default order book fees, in basis points of the collateral a fill
+/// commits. The live values are `ConfigParams::taker_fee_bps` and
+/// `ConfigParams::maker_fee_bps`.
+pub const TAKER_FEE_BPS: u16 = 30;
+pub const MAKER_FEE_BPS: u16 = 10;
+
+/// Lamports a winning outcome share pays. Order prices are in basis
+/// points of it, so a YES bid at `p` costs `p * 100` lamports a share and
+/// an ask, which buys the matching NO share, `(10_000 - p) * 100`.
+pub const SHARE_LAMPORTS: u64 = 1_000_000;
+pub const MAX_ORDER_PRICE: u64 = 9_999;
+
+/// `OrderNode::side` values: bids buy YES shares, asks sell them
+pub const ORDER_SIDE_BID: u8 = 0;
+pub const ORDER_SIDE_ASK: u8 = 1;
+
+/// Resting orders and unconsumed fills an order book holds
+pub const ORDER_BOOK_CAPACITY: usize = 64;
+pub const EVENT_QUEUE_CAPACITY: usize = 64;
+
+/// End of an order book list
+const NIL: u32 = u32::MAX;
+
+pub(crate) fn initialize_order_book(ctx: Context<InitializeOrderBook>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    
+    require!(!market.resolved, ErrorCode::MarketResolved);
+    
+    let mut book = ctx.accounts.order_book.load_init()?;
+    book.market = market.key();
+    book.next_order_id = 1;
+    book.bids_head = NIL;
+    book.asks_head = NIL;
+    for (i, node) in book.nodes.iter_mut().enumerate() {
+        node.next = if i + 1 < ORDER_BOOK_CAPACITY { i as u32 + 1 } else { NIL };
+    }
+    book.free_head = 0;
+    market.has_order_book = true;
+    
+    msg!("Order book opened for market {}", market.key());
+    
+    Ok(())
+}
+
+pub(crate) fn place_order(
+    ctx: Context<PlaceOrder>,
+    side: u8,
+    price: u64,
+    quantity: u64,
+    max_matches: u16,
+) -> Result<()> {
+    let market = &ctx.accounts.market;
+    let owner = ctx.accounts.owner.key();
+    let params = &ctx.accounts.config.params;
+    let clock = Clock::get()?;
+    
+    require!(!market.resolved, ErrorCode::MarketResolved);
+    require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+    ctx.accounts.global_state.require_active(market, PAUSE_BETTING)?;
+    require!(!market.pending, ErrorCode::MarketPending);
+    require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::MarketExpired);
+    require!(side <= ORDER_SIDE_ASK, ErrorCode::InvalidOrderSide);
+    require!((1..=MAX_ORDER_PRICE).contains(&price), ErrorCode::InvalidOrderPrice);
+    require!(quantity > 0, ErrorCode::InvalidOrderQuantity);
+    
+    if market.require_human {
+        HumanAttestation::verify(
+            ctx.accounts.human_attestation.as_deref(),
+            &ctx.accounts.global_state,
+            clock.unix_timestamp,
+        )?;
+    }
+    
+    let collateral = share_cost(side, price)
+        .checked_mul(quantity)
+        .ok_or(ErrorCode::MathOverflow)?;
+    // Keeps the book's fixed slots from being filled with dust orders
+    require!(collateral >= params.min_bet, ErrorCode::OrderTooSmall);
+    if market.max_bet_per_user != 0 {
+        let held = held_elsewhere(market, &owner, Some(&ctx.accounts.bet), Some(&ctx.accounts.liquidity_position), None)?
+            + ctx.accounts.order_book.load()?.committed(&owner) as u128
+            + ctx.accounts.position.cost as u128;
+        market.require_user_cap(held + collateral as u128)?;
+    }
+    let locked_fee = (collateral as u128 * params.taker_fee_bps as u128 / 10_000) as u64;
+    let ix = anchor_lang::solana_program::system_instruction::transfer(
+        &owner,
+        &ctx.accounts.order_book.key(),
+        collateral.checked_add(locked_fee).ok_or(ErrorCode::MathOverflow)?,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &ix,
+        &[
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.order_book.to_account_info(),
+        ],
+    )?;
+    
+    let position = &mut ctx.accounts.position;
+    let new_position = position.owner == Pubkey::default();
+    if new_position {
+        position.market = market.key();
+        position.owner = owner;
+    }
+    
+    let fees = {
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        book.escrow(collateral + locked_fee, new_position)?;
+        let order_id = book.next_order_id;
+        book.next_order_id += 1;
+        book.insert(OrderNode {
+            owner,
+            order_id,
+            price,
+            quantity,
+            locked_fee,
+            next: NIL,
+            prev: NIL,
+            taker_fee_bps: params.taker_fee_bps,
+            maker_fee_bps: params.maker_fee_bps,
+            side,
+            padding: [0; 3],
+        })?;
+        
+        emit!(OrderPlaced {
+            market: market.key(),
+            owner,
+            order_id,
+            side,
+            price,
+            quantity,
+        });
+        
+        book.match_orders(max_matches)?
+    };
+    
+    collect_order_book_fees(
+        &ctx.accounts.order_book,
+        &mut ctx.accounts.market,
+        &mut ctx.accounts.treasury,
+        fees,
+    )
+}
+
+pub(crate) fn match_orders(ctx: Context<MatchOrders>, limit: u16) -> Result<()> {
+    let market = &ctx.accounts.market;
+    let clock = Clock::get()?;
+    
+    require!(!market.resolved, ErrorCode::MarketResolved);
+    require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+    ctx.accounts.global_state.require_active(market, PAUSE_BETTING)?;
+    require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::MarketExpired);
+    
+    let fees = {
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        require!(book.event_count as usize + 2 <= EVENT_QUEUE_CAPACITY, ErrorCode::EventQueueFull);
+        book.match_orders(limit)?
+    };
+    
+    collect_order_book_fees(
+        &ctx.accounts.order_book,
+        &mut ctx.accounts.market,
+        &mut ctx.accounts.treasury,
+        fees,
+    )
+}
+
+pub(crate) fn cancel_order(ctx: Context<CancelOrder>, order_id: u64) -> Result<()> {
+    let owner = ctx.accounts.owner.key();
+    
+    let (quantity, refund) = {
+        let mut book = ctx.accounts.order_book.load_mut()?;
+        let index = book.find(order_id).ok_or(ErrorCode::OrderNotFound)?;
+        let node = book.nodes[index as usize];
+        require_keys_eq!(node.owner, owner, ErrorCode::Unauthorized);
+        book.remove(index);
+        let refund = share_cost(node.side, node.price) * node.quantity + node.locked_fee;
+        book.release(refund)?;
+        (node.quantity, refund)
+    };
+    
+    transfer_lamports(
+        &ctx.accounts.order_book.to_account_info(),
+        &ctx.accounts.owner.to_account_info(),
+        refund,
+    )?;
+    
+    emit!(OrderCancelled {
+        market: ctx.accounts.market.key(),
+        owner,
+        order_id,
+        quantity,
+    });
+    
+    Ok(())
+}
+
+pub(crate) fn consume_events<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ConsumeEvents<'info>>,
+    limit: u16,
+) -> Result<()> {
+    let mut book = ctx.accounts.order_book.load_mut()?;
+    let mut consumed = 0;
+    
+    for info in ctx.remaining_accounts.iter().take(limit as usize) {
+        let Some(event) = book.peek_event() else {
+            break;
+        };
+        require!(info.is_writable, ErrorCode::InvalidPositionAccount);
+        let mut position = Account::<Position>::try_from(info)
+            .map_err(|_| error!(ErrorCode::InvalidPositionAccount))?;
+        require!(
+            position.market == book.market && position.owner == event.owner,
+            ErrorCode::InvalidPositionAccount
+        );
+        position.apply(&event)?;
+        position.exit(&crate::ID)?;
+        book.pop_event();
+        consumed += 1;
+    }
+    
+    msg!("Consumed {} fills, {} left", consumed, book.event_count);
+    
+    Ok(())
+}
+
+pub(crate) fn claim_position(ctx: Context<ClaimPosition>) -> Result<()> {
+    let market = &ctx.accounts.market;
+    let position = &ctx.accounts.position;
+    
+    require!(market.resolved, ErrorCode::MarketNotResolved);
+    require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+    ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+    let mut book = ctx.accounts.order_book.load_mut()?;
+    require!(book.event_count == 0, ErrorCode::FillEventsPending);
+    
+    let winnings = if market.invalidated {
+        position.cost
+    } else {
+        let shares = if market.winning_outcome { position.yes_shares } else { position.no_shares };
+        shares.checked_mul(SHARE_LAMPORTS).ok_or(ErrorCode::MathOverflow)?
+    };
+    let payout = winnings.checked_add(position.balance).ok_or(ErrorCode::MathOverflow)?;
+    book.release(payout)?;
+    book.open_positions -= 1;
+    drop(book);
+    
+    transfer_lamports(
+        &ctx.accounts.order_book.to_account_info(),
+        &ctx.accounts.owner.to_account_info(),
+        payout,
+    )?;
+    
+    msg!("Position claimed: {} lamports", payout);
+    
+    Ok(())
+}
+
+pub(crate) fn close_order_book(ctx: Context<CloseOrderBook>) -> Result<()> {
+    let market = &mut ctx.accounts.market;
+    
+    require!(market.resolved, ErrorCode::MarketNotResolved);
+    {
+        let book = ctx.accounts.order_book.load()?;
+        require!(book.open_positions == 0 && book.collateral == 0, ErrorCode::OrderBookNotEmpty);
+    }
+    market.has_order_book = false;
+    
+    msg!("Order book closed for market {}", market.key());
+    
+    Ok(())
+}
+
+pub(crate) fn split_position(ctx: Context<SplitPosition>, sets: u64) -> Result<()> {
+    let market = &ctx.accounts.market;
+    let owner = ctx.accounts.owner.key();
+    let clock = Clock::get()?;
+    
+    require!(!market.resolved, ErrorCode::MarketResolved);
+    require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+    ctx.accounts.global_state.require_active(market, PAUSE_BETTING)?;
+    require!(!market.pending, ErrorCode::MarketPending);
+    require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::MarketExpired);
+    require!(sets > 0, ErrorCode::InvalidOrderQuantity);
+    
+    if market.require_human {
+        HumanAttestation::verify(
+            ctx.accounts.human_attestation.as_deref(),
+            &ctx.accounts.global_state,
+            clock.unix_timestamp,
+        )?;
+    }
+    
+    let collateral = sets.checked_mul(SHARE_LAMPORTS).ok_or(ErrorCode::MathOverflow)?;
+    if market.max_bet_per_user != 0 {
+        let held = held_elsewhere(market, &owner, Some(&ctx.accounts.bet), Some(&ctx.accounts.liquidity_position), None)?
+            + ctx.accounts.order_book.load()?.committed(&owner) as u128
+            + ctx.accounts.position.cost as u128;
+        market.require_user_cap(held + collateral as u128)?;
+    }
+    let ix = anchor_lang::solana_program::system_instruction::transfer(
+        &owner,
+        &ctx.accounts.order_book.key(),
+        collateral,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &ix,
+        &[
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.order_book.to_account_info(),
+        ],
+    )?;
+    
+    let position = &mut ctx.accounts.position;
+    let new_position = position.owner == Pubkey::default();
+    if new_position {
+        position.market = market.key();
+        position.owner = owner;
+    }
+    ctx.accounts.order_book.load_mut()?.escrow(collateral, new_position)?;
+    position.yes_shares = position.yes_shares.checked_add(sets).ok_or(ErrorCode::MathOverflow)?;
+    position.no_shares = position.no_shares.checked_add(sets).ok_or(ErrorCode::MathOverflow)?;
+    position.cost = position.cost.checked_add(collateral).ok_or(ErrorCode::MathOverflow)?;
+    
+    emit!(PositionSplit {
+        market: market.key(),
+        owner,
+        sets,
+    });
+    
+    Ok(())
+}
+
+pub(crate) fn merge_position(ctx: Context<MergePosition>, sets: u64) -> Result<()> {
+    let market = &ctx.accounts.market;
+    let position = &mut ctx.accounts.position;
+    
+    require!(!market.resolved, ErrorCode::MarketResolved);
+    require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+    ctx.accounts.global_state.require_active(market, PAUSE_CLAIMS)?;
+    require!(sets > 0, ErrorCode::InvalidOrderQuantity);
+    require!(
+        position.yes_shares >= sets && position.no_shares >= sets,
+        ErrorCode::InsufficientShares
+    );
+    let collateral = sets.checked_mul(SHARE_LAMPORTS).ok_or(ErrorCode::MathOverflow)?;
+    require!(position.cost >= collateral, ErrorCode::MergeExceedsCost);
+    
+    position.yes_shares -= sets;
+    position.no_shares -= sets;
+    position.cost -= collateral;
+    ctx.accounts.order_book.load_mut()?.release(collateral)?;
+    transfer_lamports(
+        &ctx.accounts.order_book.to_account_info(),
+        &ctx.accounts.owner.to_account_info(),
+        collateral,
+    )?;
+    
+    emit!(PositionMerged {
+        market: market.key(),
+        owner: position.owner,
+        sets,
+    });
+    
+    Ok(())
+}
+
+/// Lamports one share costs a `side` order at `price`: the YES price for
+/// bids, the NO price for asks
+fn share_cost(side: u8, price: u64) -> u64 {
+    let price = if side == ORDER_SIDE_BID { price } else { 10_000 - price };
+    price * (SHARE_LAMPORTS / 10_000)
+}
+
+/// Moves fees charged by order book fills to the market's liquidity
+/// providers, or to the treasury when it has none
+fn collect_order_book_fees(
+    order_book: &AccountLoader<OrderBook>,
+    market: &mut Account<Market>,
+    treasury: &mut Account<Treasury>,
+    fees: u64,
+) -> Result<()> {
+    if fees == 0 {
+        return Ok(());
+    }
+    order_book.load_mut()?.release(fees)?;
+    let order_book = &order_book.to_account_info();
+    if market.lp_shares > 0 {
+        market.lp_fees = market.lp_fees.checked_add(fees).ok_or(ErrorCode::MathOverflow)?;
+        market.lp_fee_per_share = market.lp_fee_per_share
+            .checked_add(fees as u128 * FEE_PRECISION / market.lp_shares as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+        transfer_lamports(order_book, &market.to_account_info(), fees)
+    } else {
+        treasury.total_collected = treasury.total_collected.checked_add(fees).ok_or(ErrorCode::MathOverflow)?;
+        transfer_lamports(order_book, &treasury.to_account_info(), fees)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeOrderBook<'info> {
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub market: Account<'info, Market>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<OrderBook>(),
+        seeds = [b"order_book", market.key().as_ref()],
+        bump
+    )]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceOrder<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Position::INIT_SPACE,
+        seeds = [b"position", market.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Required to trade on a `require_human` market
+    #[account(seeds = [b"human", owner.key().as_ref()], bump)]
+    pub human_attestation: Option<Account<'info, HumanAttestation>>,
+    /// CHECK: the owner's bet, counted toward their cap; may not exist
+    #[account(seeds = [b"bet", owner.key().as_ref(), market.key().as_ref()], bump)]
+    pub bet: UncheckedAccount<'info>,
+    /// CHECK: the owner's liquidity position, counted toward their cap; may not exist
+    #[account(seeds = [b"liquidity", owner.key().as_ref(), market.key().as_ref()], bump)]
+    pub liquidity_position: UncheckedAccount<'info>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: Account<'info, Treasury>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MatchOrders<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut, seeds = [b"treasury"], bump)]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOrder<'info> {
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumeEvents<'info> {
+    #[account(mut)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPosition<'info> {
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), owner.key().as_ref()],
+        bump,
+        has_one = owner,
+        close = owner
+    )]
+    pub position: Account<'info, Position>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseOrderBook<'info> {
+    #[account(mut, has_one = authority @ ErrorCode::Unauthorized)]
+    pub market: Account<'info, Market>,
+    #[account(
+        mut,
+        seeds = [b"order_book", market.key().as_ref()],
+        bump,
+        close = authority
+    )]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SplitPosition<'info> {
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Position::INIT_SPACE,
+        seeds = [b"position", market.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// Required to trade on a `require_human` market
+    #[account(seeds = [b"human", owner.key().as_ref()], bump)]
+    pub human_attestation: Option<Account<'info, HumanAttestation>>,
+    /// CHECK: the owner's bet, counted toward their cap; may not exist
+    #[account(seeds = [b"bet", owner.key().as_ref(), market.key().as_ref()], bump)]
+    pub bet: UncheckedAccount<'info>,
+    /// CHECK: the owner's liquidity position, counted toward their cap; may not exist
+    #[account(seeds = [b"liquidity", owner.key().as_ref(), market.key().as_ref()], bump)]
+    pub liquidity_position: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MergePosition<'info> {
+    pub market: Account<'info, Market>,
+    #[account(mut, seeds = [b"order_book", market.key().as_ref()], bump)]
+    pub order_book: AccountLoader<'info, OrderBook>,
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), owner.key().as_ref()],
+        bump,
+        has_one = owner
+    )]
+    pub position: Account<'info, Position>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+/// A market's limit order book. Resting orders live in a fixed slab of
+/// nodes linked into a bid list (best price first) and an ask list, with
+/// ties in placement order; fills wait in a ring buffer until
+/// `consume_events` settles them into positions.
+#[account(zero_copy)]
+pub struct OrderBook {
+    pub market: Pubkey,               // 32
+    pub next_order_id: u64,           // 8
+    pub bids_head: u32,               // 4 (best bid; NIL = none)
+    pub asks_head: u32,               // 4 (best ask; NIL = none)
+    pub free_head: u32,               // 4 (unused nodes)
+    pub event_head: u32,              // 4 (oldest queued fill)
+    pub event_count: u32,             // 4
+    pub open_positions: u32,          // 4 (positions not yet claimed)
+    pub collateral: u64,              // 8 (lamports owed to orders and positions)
+    pub nodes: [OrderNode; ORDER_BOOK_CAPACITY],
+    pub events: [FillEvent; EVENT_QUEUE_CAPACITY],
+}
+
+#[zero_copy]
+pub struct OrderNode {
+    pub owner: Pubkey,                // 32
+    pub order_id: u64,                // 8
+    pub price: u64,                   // 8 (basis points of SHARE_LAMPORTS)
+    pub quantity: u64,                // 8 (shares left to fill)
+    pub locked_fee: u64,              // 8 (taker fee escrowed for the rest)
+    pub next: u32,                    // 4 (next order in its list or the free list)
+    pub prev: u32,                    // 4
+    pub taker_fee_bps: u16,           // 2 (config rates when placed)
+    pub maker_fee_bps: u16,           // 2
+    pub side: u8,                     // 1 (ORDER_SIDE_* value)
+    pub padding: [u8; 3],             // 3
+}
+
+#[zero_copy]
+pub struct FillEvent {
+    pub owner: Pubkey,                // 32
+    pub order_id: u64,                // 8
+    pub shares: u64,                  // 8
+    pub cost: u64,                    // 8 (collateral now backing the shares)
+    pub refund: u64,                  // 8 (price improvement and unused fee)
+    pub side: u8,                     // 1
+    pub padding: [u8; 7],             // 7
+}
+
+impl OrderBook {
+    fn head(&self, side: u8) -> u32 {
+        if side == ORDER_SIDE_BID { self.bids_head } else { self.asks_head }
+    }
+
+    fn set_head(&mut self, side: u8, index: u32) {
+        if side == ORDER_SIDE_BID { self.bids_head = index } else { self.asks_head = index }
+    }
+
+    /// Links `order` into its side behind every order at a better or equal
+    /// price
+    fn insert(&mut self, mut order: OrderNode) -> Result<u32> {
+        let index = self.free_head;
+        require!(index != NIL, ErrorCode::OrderBookFull);
+        self.free_head = self.nodes[index as usize].next;
+        
+        let mut prev = NIL;
+        let mut next = self.head(order.side);
+        while next != NIL {
+            let other = &self.nodes[next as usize];
+            let better = if order.side == ORDER_SIDE_BID { order.price > other.price } else { order.price < other.price };
+            if better {
+                break;
+            }
+            prev = next;
+            next = other.next;
+        }
+        
+        order.prev = prev;
+        order.next = next;
+        self.nodes[index as usize] = order;
+        if prev == NIL {
+            self.set_head(order.side, index);
+        } else {
+            self.nodes[prev as usize].next = index;
+        }
+        if next != NIL {
+            self.nodes[next as usize].prev = index;
+        }
+        Ok(index)
+    }
+
+    /// Unlinks the order at `index` and returns its node to the free list
+    fn remove(&mut self, index: u32) {
+        let node = self.nodes[index as usize];
+        if node.prev == NIL {
+            self.set_head(node.side, node.next);
+        } else {
+            self.nodes[node.prev as usize].next = node.next;
+        }
+        if node.next != NIL {
+            self.nodes[node.next as usize].prev = node.prev;
+        }
+        self.nodes[index as usize] = bytemuck::Zeroable::zeroed();
+        self.nodes[index as usize].next = self.free_head;
+        self.free_head = index;
+    }
+
+    /// Collateral `owner` has committed to the book and not yet settled
+    /// into their position: resting orders and queued fills
+    pub(crate) fn committed(&self, owner: &Pubkey) -> u64 {
+        let mut committed = 0;
+        for side in [ORDER_SIDE_BID, ORDER_SIDE_ASK] {
+            let mut index = self.head(side);
+            while index != NIL {
+                let node = &self.nodes[index as usize];
+                if node.owner == *owner {
+                    committed += share_cost(side, node.price) * node.quantity;
+                }
+                index = node.next;
+            }
+        }
+        for i in 0..self.event_count as usize {
+            let event = &self.events[(self.event_head as usize + i) % EVENT_QUEUE_CAPACITY];
+            if event.owner == *owner {
+                committed += event.cost;
+            }
+        }
+        committed
+    }
+
+    fn find(&self, order_id: u64) -> Option<u32> {
+        for side in [ORDER_SIDE_BID, ORDER_SIDE_ASK] {
+            let mut index = self.head(side);
+            while index != NIL {
+                let node = &self.nodes[index as usize];
+                if node.order_id == order_id {
+                    return Some(index);
+                }
+                index = node.next;
+            }
+        }
+        None
+    }
+
+    /// Crosses the best bid and ask while they overlap, up to `limit`
+    /// times or until the event queue has no room for both fills. The
+    /// older order was resting first, so it is the maker and sets the
+    /// price. Returns the fees charged.
+    fn match_orders(&mut self, limit: u16) -> Result<u64> {
+        let mut fees = 0u64;
+        for _ in 0..limit {
+            let (bid, ask) = (self.bids_head, self.asks_head);
+            if bid == NIL || ask == NIL || self.event_count as usize + 2 > EVENT_QUEUE_CAPACITY {
+                break;
+            }
+            let (bid_node, ask_node) = (self.nodes[bid as usize], self.nodes[ask as usize]);
+            if bid_node.price < ask_node.price {
+                break;
+            }
+            let bid_is_maker = bid_node.order_id < ask_node.order_id;
+            let price = if bid_is_maker { bid_node.price } else { ask_node.price };
+            let shares = bid_node.quantity.min(ask_node.quantity);
+            fees += self.fill(bid, price, shares, bid_is_maker)?;
+            fees += self.fill(ask, price, shares, !bid_is_maker)?;
+        }
+        Ok(fees)
+    }
+
+    /// Fills `shares` of the order at `index` at `price` and queues the
+    /// fill for its owner. Returns the fee charged, at the maker or taker
+    /// rate the order was placed with.
+    fn fill(&mut self, index: u32, price: u64, shares: u64, maker: bool) -> Result<u64> {
+        let node = &mut self.nodes[index as usize];
+        let escrowed = share_cost(node.side, node.price) * shares;
+        let cost = share_cost(node.side, price) * shares;
+        let rate = if maker { node.maker_fee_bps } else { node.taker_fee_bps };
+        let fee = (cost as u128 * rate as u128 / 10_000) as u64;
+        
+        // The fee locked for these shares covers any fee charged on them;
+        // the last fill releases whatever rounding left behind
+        node.quantity -= shares;
+        let released = if node.quantity == 0 {
+            node.locked_fee
+        } else {
+            node.locked_fee.min((escrowed as u128 * node.taker_fee_bps as u128 / 10_000) as u64)
+        };
+        node.locked_fee -= released;
+        
+        let event = FillEvent {
+            owner: node.owner,
+            order_id: node.order_id,
+            shares,
+            cost,
+            refund: escrowed - cost + released - fee,
+            side: node.side,
+            padding: [0; 7],
+        };
+        if node.quantity == 0 {
+            self.remove(index);
+        }
+        self.push_event(event)?;
+        Ok(fee)
+    }
+
+    /// Records lamports paid into the book, and a position opened with them
+    fn escrow(&mut self, amount: u64, new_position: bool) -> Result<()> {
+        self.collateral = self.collateral.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        if new_position {
+            self.open_positions = self.open_positions.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Records lamports paid out of the book
+    fn release(&mut self, amount: u64) -> Result<()> {
+        self.collateral = self.collateral.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    fn push_event(&mut self, event: FillEvent) -> Result<()> {
+        require!((self.event_count as usize) < EVENT_QUEUE_CAPACITY, ErrorCode::EventQueueFull);
+        let slot = (self.event_head as usize + self.event_count as usize) % EVENT_QUEUE_CAPACITY;
+        self.events[slot] = event;
+        self.event_count += 1;
+        Ok(())
+    }
+
+    fn peek_event(&self) -> Option<FillEvent> {
+        (self.event_count > 0).then(|| self.events[self.event_head as usize])
+    }
+
+    fn pop_event(&mut self) {
+        self.event_head = (self.event_head + 1) % EVENT_QUEUE_CAPACITY as u32;
+        self.event_count -= 1;
+    }
+}
+
+/// A trader's settled order book fills in one market
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    pub market: Pubkey,               // 32
+    pub owner: Pubkey,                // 32
+    pub yes_shares: u64,              // 8
+    pub no_shares: u64,               // 8
+    pub cost: u64,                    // 8 (collateral behind the shares; refunded if invalidated)
+    pub balance: u64,                 // 8 (refunds owed to the owner)
+}
+
+impl Position {
+    fn apply(&mut self, event: &FillEvent) -> Result<()> {
+        let shares = if event.side == ORDER_SIDE_BID { &mut self.yes_shares } else { &mut self.no_shares };
+        *shares = shares.checked_add(event.shares).ok_or(ErrorCode::MathOverflow)?;
+        self.cost = self.cost.checked_add(event.cost).ok_or(ErrorCode::MathOverflow)?;
+        self.balance = self.balance.checked_add(event.refund).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[event]
+pub struct OrderPlaced {
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    pub order_id: u64,
+    pub side: u8,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+#[event]
+pub struct OrderCancelled {
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    pub order_id: u64,
+    pub quantity: u64,
+}
+
+#[event]
+pub struct PositionSplit {
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    pub sets: u64,
+}
+
+#[event]
+pub struct PositionMerged {
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    pub sets: u64,
+}
@@ -0,0 +1,433 @@
+//! Parlays across oracle markets, backed by a shared house vault that
+//! takes the other side of every one.
+
+use super::*;
+
+/// How many markets a parlay can combine
+pub const MIN_PARLAY_LEGS: usize = 2;
+pub const MAX_PARLAY_LEGS: usize = 8;
+
+/// Default share of the house vault's assets that open parlay payouts may
+/// claim, in basis points. The live value is
+/// `ConfigParams::house_liability_bps`.
+pub const HOUSE_LIABILITY_BPS: u16 = 5_000;
+
+/// Default parlay limits: the largest payout, in lamports, the highest
+/// odds multiple a leg may be priced at, and the smallest pool a leg can
+/// price from. The live values are in `ConfigParams`.
+pub const MAX_PARLAY_PAYOUT: u64 = 100_000_000_000; // 100 SOL
+pub const MAX_PARLAY_LEG_ODDS: u16 = 10;
+pub const MIN_PARLAY_POOL: u64 = 1_000_000_000; // 1 SOL
+
+/// Shares and lamports the house vault counts on top of its own when
+/// pricing shares, so a donation to a near-empty vault cannot round a
+/// later deposit down to nothing
+const HOUSE_VIRTUAL_SHARES: u128 = 1_000;
+const HOUSE_VIRTUAL_ASSETS: u128 = 1;
+
+/// How long a house withdrawal request waits before it can be carried
+/// out, leaving time for parlays that have already won to be settled
+/// against the vault, and how long it stays usable after that
+pub const HOUSE_WITHDRAWAL_DELAY: i64 = 24 * 60 * 60;
+pub const HOUSE_WITHDRAWAL_WINDOW: i64 = 24 * 60 * 60;
+
+/// Fixed-point scale for `ParlayLeg::odds`
+const ODDS_PRECISION: u128 = 1_000_000_000;
+
+pub(crate) fn initialize_house_vault(ctx: Context<InitializeHouseVault>) -> Result<()> {
+    let house_vault = &mut ctx.accounts.house_vault;
+    house_vault.total_shares = 0;
+    house_vault.liability = 0;
+    house_vault.parlay_count = 0;
+    
+    msg!("House vault initialized");
+    
+    Ok(())
+}
+
+pub(crate) fn deposit_house_liquidity(ctx: Context<DepositHouseLiquidity>, amount: u64) -> Result<()> {
+    let house_vault = &mut ctx.accounts.house_vault;
+    let assets = HouseVault::assets(&house_vault.to_account_info())?;
+    
+    let shares = amount as u128 * (house_vault.total_shares as u128 + HOUSE_VIRTUAL_SHARES)
+        / (assets as u128 + HOUSE_VIRTUAL_ASSETS);
+    let shares = u64::try_from(shares).map_err(|_| error!(ErrorCode::MathOverflow))?;
+    require!(shares > 0, ErrorCode::InvalidHouseAmount);
+    
+    let ix = anchor_lang::solana_program::system_instruction::transfer(
+        &ctx.accounts.provider.key(),
+        &house_vault.key(),
+        amount,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &ix,
+        &[
+            ctx.accounts.provider.to_account_info(),
+            house_vault.to_account_info(),
+        ],
+    )?;
+    
+    let deposit = &mut ctx.accounts.house_deposit;
+    deposit.provider = ctx.accounts.provider.key();
+    deposit.shares = deposit.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+    house_vault.total_shares = house_vault.total_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+    
+    msg!("House deposit: {} lamports for {} shares", amount, shares);
+    
+    Ok(())
+}
+
+pub(crate) fn request_house_withdrawal(ctx: Context<RequestHouseWithdrawal>, shares: u64) -> Result<()> {
+    let house_vault = &ctx.accounts.house_vault;
+    let deposit = &mut ctx.accounts.house_deposit;
+    
+    require!(shares > 0 && shares <= deposit.shares, ErrorCode::InvalidHouseAmount);
+    
+    let assets = HouseVault::assets(&house_vault.to_account_info())?;
+    deposit.withdrawal_shares = shares;
+    deposit.withdrawal_value = house_vault.share_value(shares, assets);
+    deposit.withdrawal_requested_at = Clock::get()?.unix_timestamp;
+    
+    msg!("House withdrawal requested: {} shares", shares);
+    
+    Ok(())
+}
+
+pub(crate) fn withdraw_house_liquidity(ctx: Context<WithdrawHouseLiquidity>) -> Result<()> {
+    let house_vault = &mut ctx.accounts.house_vault;
+    let deposit = &mut ctx.accounts.house_deposit;
+    let clock = Clock::get()?;
+    
+    require!(ctx.accounts.global_state.paused & PAUSE_CLAIMS == 0, ErrorCode::Paused);
+    require!(deposit.withdrawal_shares > 0, ErrorCode::HouseWithdrawalNotRequested);
+    let ready_at = deposit.withdrawal_requested_at.checked_add(HOUSE_WITHDRAWAL_DELAY).ok_or(ErrorCode::MathOverflow)?;
+    require!(clock.unix_timestamp >= ready_at, ErrorCode::HouseWithdrawalNotReady);
+    require!(clock.unix_timestamp < ready_at + HOUSE_WITHDRAWAL_WINDOW, ErrorCode::HouseWithdrawalExpired);
+    
+    let shares = deposit.withdrawal_shares;
+    let assets = HouseVault::assets(&house_vault.to_account_info())?;
+    let amount = house_vault.share_value(shares, assets).min(deposit.withdrawal_value);
+    require!(assets - amount >= house_vault.liability, ErrorCode::HouseLiquidityLocked);
+    
+    deposit.shares -= shares;
+    deposit.withdrawal_shares = 0;
+    deposit.withdrawal_value = 0;
+    house_vault.total_shares -= shares;
+    transfer_lamports(&house_vault.to_account_info(), &ctx.accounts.provider.to_account_info(), amount)?;
+    
+    msg!("House withdrawal: {} shares for {} lamports", shares, amount);
+    
+    Ok(())
+}
+
+pub(crate) fn place_parlay<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PlaceParlay<'info>>,
+    outcomes: Vec<bool>,
+    stake: u64,
+    min_payout: u64,
+) -> Result<()> {
+    let params = &ctx.accounts.config.params;
+    let clock = Clock::get()?;
+    
+    require!(
+        (MIN_PARLAY_LEGS..=MAX_PARLAY_LEGS).contains(&outcomes.len())
+            && ctx.remaining_accounts.len() == outcomes.len(),
+        ErrorCode::InvalidParlayLegs
+    );
+    require!(stake >= params.min_bet, ErrorCode::BetTooSmall);
+    
+    let mut legs: Vec<ParlayLeg> = Vec::with_capacity(outcomes.len());
+    let mut payout = stake as u128;
+    for (info, &outcome) in ctx.remaining_accounts.iter().zip(&outcomes) {
+        require!(info.is_writable, ErrorCode::InvalidParlayLegs);
+        let mut market = Account::<Market>::try_from(info).map_err(|_| error!(ErrorCode::InvalidParlayLegs))?;
+        require!(legs.iter().all(|leg| leg.market != market.key()), ErrorCode::InvalidParlayLegs);
+        require!(!market.resolved, ErrorCode::MarketResolved);
+        require!(market.moderation_status != MODERATION_FROZEN, ErrorCode::MarketFrozen);
+        ctx.accounts.global_state.require_active(&market, PAUSE_BETTING)?;
+        require!(!market.pending, ErrorCode::MarketPending);
+        require!(clock.unix_timestamp < market.betting_closes_at(), ErrorCode::MarketExpired);
+        // Parlays never pass through the pool, so they cannot prove
+        // the bettor is human
+        require!(!market.require_human, ErrorCode::HumanVerificationRequired);
+        // Nobody can steer a price feed, unlike a market's authority
+        require!(market.oracle_enabled, ErrorCode::ParlayLegNotOracle);
+        
+        let side = if outcome { market.yes_amount } else { market.no_amount };
+        require!(side > 0, ErrorCode::LegHasNoOdds);
+        let total = market.yes_amount as u128 + market.no_amount as u128;
+        require!(total >= params.min_parlay_pool as u128, ErrorCode::ParlayLegPoolTooSmall);
+        let odds = u64::try_from(total * ODDS_PRECISION / side as u128).map_err(|_| error!(ErrorCode::MathOverflow))?;
+        require!(
+            odds as u128 <= params.max_parlay_leg_odds as u128 * ODDS_PRECISION,
+            ErrorCode::ParlayLegOddsTooHigh
+        );
+        payout = payout.checked_mul(odds as u128).ok_or(ErrorCode::MathOverflow)? / ODDS_PRECISION;
+        
+        // The market stays open until the parlay settles against it
+        market.open_parlays = market.open_parlays.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        market.exit(&crate::ID)?;
+        legs.push(ParlayLeg {
+            market: market.key(),
+            outcome,
+            odds,
+        });
+    }
+    let payout = u64::try_from(payout).map_err(|_| error!(ErrorCode::MathOverflow))?;
+    require!(payout >= min_payout, ErrorCode::ParlayOddsMoved);
+    if params.max_parlay_payout != 0 {
+        require!(payout <= params.max_parlay_payout, ErrorCode::ParlayPayoutTooLarge);
+    }
+    
+    // The stake joins the vault, and every open payout together may
+    // only claim the configured share of it
+    let house_vault = &mut ctx.accounts.house_vault;
+    let assets = HouseVault::assets(&house_vault.to_account_info())? as u128 + stake as u128;
+    let liability = house_vault.liability.checked_add(payout).ok_or(ErrorCode::MathOverflow)?;
+    require!(
+        liability as u128 * 10_000 <= assets * params.house_liability_bps as u128,
+        ErrorCode::HouseCapacityExceeded
+    );
+    
+    let ix = anchor_lang::solana_program::system_instruction::transfer(
+        &ctx.accounts.owner.key(),
+        &house_vault.key(),
+        stake,
+    );
+    anchor_lang::solana_program::program::invoke(
+        &ix,
+        &[
+            ctx.accounts.owner.to_account_info(),
+            house_vault.to_account_info(),
+        ],
+    )?;
+    
+    let parlay = &mut ctx.accounts.parlay;
+    parlay.id = house_vault.parlay_count;
+    parlay.owner = ctx.accounts.owner.key();
+    parlay.stake = stake;
+    parlay.payout = payout;
+    parlay.legs = legs;
+    parlay.created_at = clock.unix_timestamp;
+    house_vault.liability = liability;
+    house_vault.parlay_count += 1;
+    
+    emit!(ParlayPlaced {
+        parlay: parlay.key(),
+        id: parlay.id,
+        owner: parlay.owner,
+        stake,
+        payout,
+        legs: outcomes.len() as u8,
+    });
+    
+    Ok(())
+}
+
+pub(crate) fn settle_parlay<'info>(ctx: Context<'_, '_, 'info, 'info, SettleParlay<'info>>) -> Result<()> {
+    let parlay = &ctx.accounts.parlay;
+    
+    require!(ctx.remaining_accounts.len() == parlay.legs.len(), ErrorCode::InvalidParlayLegs);
+    
+    let mut lost = false;
+    let mut pending = false;
+    let mut payout = parlay.payout as u128;
+    for (info, leg) in ctx.remaining_accounts.iter().zip(&parlay.legs) {
+        require_keys_eq!(info.key(), leg.market, ErrorCode::InvalidParlayLegs);
+        require!(info.is_writable, ErrorCode::InvalidParlayLegs);
+        let mut market = Account::<Market>::try_from(info)?;
+        market.open_parlays = market.open_parlays.checked_sub(1).ok_or(ErrorCode::MathOverflow)?;
+        market.exit(&crate::ID)?;
+        
+        if !market.resolved {
+            pending = true;
+        } else if market.invalidated {
+            payout = payout * ODDS_PRECISION / leg.odds as u128;
+        } else if market.winning_outcome != leg.outcome {
+            lost = true;
+        }
+    }
+    require!(lost || !pending, ErrorCode::ParlayLegsPending);
+    
+    let paid = if lost { 0 } else { payout as u64 };
+    if paid > 0 {
+        require!(ctx.accounts.global_state.paused & PAUSE_CLAIMS == 0, ErrorCode::Paused);
+        transfer_lamports(
+            &ctx.accounts.house_vault.to_account_info(),
+            &ctx.accounts.owner.to_account_info(),
+            paid,
+        )?;
+    }
+    let house_vault = &mut ctx.accounts.house_vault;
+    house_vault.liability = house_vault.liability.checked_sub(parlay.payout).ok_or(ErrorCode::MathOverflow)?;
+    
+    emit!(ParlaySettled {
+        parlay: parlay.key(),
+        id: parlay.id,
+        owner: parlay.owner,
+        won: !lost,
+        paid,
+    });
+    
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeHouseVault<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + HouseVault::INIT_SPACE,
+        seeds = [b"house_vault"],
+        bump
+    )]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(seeds = [b"config"], bump, has_one = authority @ ErrorCode::Unauthorized)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositHouseLiquidity<'info> {
+    #[account(mut, seeds = [b"house_vault"], bump)]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + HouseDeposit::INIT_SPACE,
+        seeds = [b"house_deposit", provider.key().as_ref()],
+        bump
+    )]
+    pub house_deposit: Account<'info, HouseDeposit>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RequestHouseWithdrawal<'info> {
+    #[account(seeds = [b"house_vault"], bump)]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(mut, seeds = [b"house_deposit", provider.key().as_ref()], bump, has_one = provider)]
+    pub house_deposit: Account<'info, HouseDeposit>,
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawHouseLiquidity<'info> {
+    #[account(mut, seeds = [b"house_vault"], bump)]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(mut, seeds = [b"house_deposit", provider.key().as_ref()], bump, has_one = provider)]
+    pub house_deposit: Account<'info, HouseDeposit>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceParlay<'info> {
+    #[account(mut, seeds = [b"house_vault"], bump)]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Parlay::INIT_SPACE,
+        seeds = [b"parlay", house_vault.parlay_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub parlay: Account<'info, Parlay>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(seeds = [b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleParlay<'info> {
+    #[account(mut, has_one = owner, close = owner)]
+    pub parlay: Account<'info, Parlay>,
+    #[account(mut, seeds = [b"house_vault"], bump)]
+    pub house_vault: Account<'info, HouseVault>,
+    #[account(seeds = [b"global_state"], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    /// CHECK: receives the payout and the parlay's rent; must be its owner
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+}
+
+/// Liquidity that takes the other side of every parlay. Its lamports,
+/// less rent, are the assets depositors hold shares of.
+#[account]
+#[derive(InitSpace)]
+pub struct HouseVault {
+    pub total_shares: u64,            // 8
+    pub liability: u64,               // 8 (payouts owed if every open parlay wins)
+    pub parlay_count: u64,            // 8
+}
+
+impl HouseVault {
+    fn assets(info: &AccountInfo) -> Result<u64> {
+        let rent = Rent::get()?.minimum_balance(info.data_len());
+        Ok(info.lamports().saturating_sub(rent))
+    }
+
+    /// Lamports `shares` redeem for while the vault holds `assets`
+    fn share_value(&self, shares: u64, assets: u64) -> u64 {
+        (shares as u128 * (assets as u128 + HOUSE_VIRTUAL_ASSETS)
+            / (self.total_shares as u128 + HOUSE_VIRTUAL_SHARES)) as u64
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct HouseDeposit {
+    pub provider: Pubkey,             // 32
+    pub shares: u64,                  // 8
+    pub withdrawal_shares: u64,       // 8 (requested; 0 = none)
+    pub withdrawal_value: u64,        // 8 (their lamports when requested; caps the payout)
+    pub withdrawal_requested_at: i64, // 8
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Parlay {
+    pub id: u64,                      // 8
+    pub owner: Pubkey,                // 32
+    pub stake: u64,                   // 8
+    pub payout: u64,                  // 8 (if every leg wins)
+    #[max_len(MAX_PARLAY_LEGS)]
+    pub legs: Vec<ParlayLeg>,         // 4 + 8 * 41
+    pub created_at: i64,              // 8
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct ParlayLeg {
+    pub market: Pubkey,               // 32
+    pub outcome: bool,                // 1
+    pub odds: u64,                    // 8 (pool odds at entry, scaled by ODDS_PRECISION)
+}
+
+#[event]
+pub struct ParlayPlaced {
+    pub parlay: Pubkey,
+    pub id: u64,
+    pub owner: Pubkey,
+    pub stake: u64,
+    pub payout: u64,
+    pub legs: u8,
+}
+
+#[event]
+pub struct ParlaySettled {
+    pub parlay: Pubkey,
+    pub id: u64,
+    pub owner: Pubkey,
+    pub won: bool,
+    pub paid: u64,
+}
@@ -0,0 +1,5296 @@
+mod svm;
+
+use anchor_lang::prelude::*;
+use prediction_market::{
+    Bet, BettingClosed, ConditionResolved, Config, ConfigAuthorityTransferred, ConfigParams,
+    ConfigUpdateQueued, ConfigUpdated, ContentReport, EligibilityTallied, EligibilityVote,
+    ErrorCode, GlobalState, Governance, HouseDeposit, HouseVault, LiquidityPosition, Market,
+    MarketAuthorityTransferred, MarketCapReached, MarketLimits, MarketMetadata, MarketModerated,
+    MarketPauseSet, MarketReported, MarketResolved, MarketSchedule, OrderPlaced, ParlayPlaced,
+    ParlaySettled, Position, ProposalAction, ProposalExecuted, ProtocolAuthorityTransferred,
+    ProtocolPauseSet, ResolutionApproved, ResolverSet, Treasury, UnclaimedPosition,
+    UnclaimedSettled, UserProfile, VoterRecord, BET_VERSION, CLAIM_RESUME_GRACE,
+    ELIGIBILITY_DEPOSIT, EVENT_QUEUE_CAPACITY, FALLBACK_AUTHORITY_TIMEOUT, FALLBACK_NONE,
+    FALLBACK_ORACLE_TIMEOUT, GLOBAL_STATE_VERSION, GOVERNANCE_RESOLUTION_TIMEOUT,
+    HOUSE_WITHDRAWAL_DELAY, HOUSE_WITHDRAWAL_WINDOW, HUMAN_VOTE_WEIGHT, LEGACY_BET_SPACE,
+    LEGACY_CLAIM_PERIOD, LEGACY_GLOBAL_STATE_SPACE, LEGACY_MARKET_SPACE, LP_FEE_BPS,
+    MARKET_CATEGORY_OFFSET, MARKET_VERSION, MAX_ORACLE_FEEDS, META_PARENT_INVALIDATED,
+    META_PARENT_NO, META_PARENT_YES, MIN_VOTER_PREDICTIONS, MODERATION_FROZEN, MODERATION_HIDDEN,
+    MODERATION_VISIBLE, ORACLE_FALLBACK_GOVERNANCE, ORDER_BOOK_CAPACITY, ORDER_SIDE_ASK,
+    ORDER_SIDE_BID, PAUSE_BETTING, PAUSE_CLAIMS, PAUSE_CREATION, PAUSE_RESOLUTION,
+    REDISTRIBUTION_PERIOD, SHARE_LAMPORTS,
+};
+use pyth_solana_receiver_sdk::error::GetPriceError;
+use svm::ix::*;
+use svm::Svm;
+
+const SOL: u64 = 1_000_000_000;
+const MIN_BET: u64 = 10_000_000;
+const ONE_DAY: i64 = 86_400;
+
+fn assert_error(result: std::result::Result<(), ProgramError>, code: impl Into<u32>) {
+    assert_eq!(result, Err(ProgramError::Custom(code.into())));
+}
+
+/// An initialized protocol plus a market with a funded creator, ending one
+/// day after genesis.
+struct Fixture {
+    svm: Svm,
+    admin: Pubkey,
+    authority: Pubkey,
+    market: Pubkey,
+    end_time: i64,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        Self::with(|_| {})
+    }
+
+    fn with_oracle(oracle: Option<OracleParams>) -> Self {
+        Self::with(|params| params.oracle = oracle)
+    }
+
+    fn with(configure: impl FnOnce(&mut MarketParams)) -> Self {
+        let mut svm = Svm::new();
+        let admin = svm.funded_account(10 * SOL);
+        svm.process(initialize_ix(admin), &[admin]).unwrap();
+        svm.process(initialize_config_ix(admin), &[admin]).unwrap();
+        let authority = svm.funded_account(10 * SOL);
+        let market = svm.signer();
+        let end_time = svm.now() + ONE_DAY;
+        let mut params = MarketParams::new("Will the bill pass?", end_time);
+        configure(&mut params);
+        svm.process(
+            create_market_ix(market, authority, &params),
+            &[authority, market],
+        )
+        .unwrap();
+        Self {
+            svm,
+            admin,
+            authority,
+            market,
+            end_time,
+        }
+    }
+
+    fn bettor(&mut self, amount: u64, bet_yes: bool) -> Pubkey {
+        let user = self.svm.funded_account(amount + SOL);
+        self.svm
+            .process(place_bet_ix(user, self.market, amount, bet_yes), &[user])
+            .unwrap();
+        user
+    }
+
+    fn expire(&mut self) {
+        self.svm.warp_to(self.end_time);
+    }
+
+    fn resolve(&mut self, outcome: bool) {
+        self.expire();
+        self.svm
+            .process(
+                resolve_market_ix(self.market, self.authority, outcome),
+                &[self.authority],
+            )
+            .unwrap();
+    }
+
+    fn claim(&mut self, user: Pubkey) {
+        self.svm
+            .process(claim_winnings_ix(user, self.market), &[user])
+            .unwrap();
+    }
+
+    fn record(&mut self, user: Pubkey) {
+        self.svm
+            .process(record_prediction_ix(user, self.market), &[])
+            .unwrap();
+    }
+
+    fn provide(&mut self, amount_yes: u64, amount_no: u64) -> Pubkey {
+        let provider = self.svm.funded_account(amount_yes + amount_no + SOL);
+        self.svm
+            .process(
+                add_liquidity_ix(provider, self.market, amount_yes, amount_no),
+                &[provider],
+            )
+            .unwrap();
+        provider
+    }
+
+    fn profile(&self, user: &Pubkey) -> UserProfile {
+        self.svm.fetch(&profile_pda(user))
+    }
+
+    fn market(&self) -> Market {
+        self.svm.fetch(&self.market)
+    }
+}
+
+#[test]
+fn initialize_creates_empty_global_state() {
+    let mut svm = Svm::new();
+    let authority = svm.funded_account(SOL);
+
+    svm.process(initialize_ix(authority), &[authority]).unwrap();
+
+    let state: GlobalState = svm.fetch(&global_state_pda());
+    assert_eq!(state.authority, authority);
+    assert_eq!(state.total_markets, 0);
+    assert_eq!(state.total_volume, 0);
+    assert_eq!(state.human_verifier, Pubkey::default());
+    let treasury: Treasury = svm.fetch(&treasury_pda());
+    assert_eq!(treasury.total_collected, 0);
+}
+
+#[test]
+fn create_market_stores_manual_market() {
+    let fixture = Fixture::new();
+
+    let market = fixture.market();
+    assert_eq!(market.authority, fixture.authority);
+    assert_eq!(market.question, "Will the bill pass?");
+    assert_eq!(market.end_time, fixture.end_time);
+    assert_eq!(market.created_at, svm::GENESIS_TIMESTAMP);
+    assert!(!market.resolved);
+    assert!(!market.oracle_enabled);
+    assert_eq!(market.oracle_feed_id, [0; 32]);
+}
+
+#[test]
+fn create_market_stores_oracle_configuration() {
+    let fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+
+    let market = fixture.market();
+    assert!(market.oracle_enabled);
+    assert_eq!(market.oracle_feed_id, FEED_ID);
+    assert_eq!(market.oracle_threshold, 100);
+    assert_eq!(market.oracle_comparison, 0);
+}
+
+#[test]
+fn create_market_rejects_long_question() {
+    let mut svm = Svm::new();
+    let authority = svm.funded_account(10 * SOL);
+    svm.process(initialize_ix(authority), &[authority]).unwrap();
+    svm.process(initialize_config_ix(authority), &[authority])
+        .unwrap();
+    let market = svm.signer();
+    let question = "?".repeat(201);
+
+    let result = svm.process(
+        create_market_ix(
+            market,
+            authority,
+            &MarketParams::new(&question, svm.now() + ONE_DAY),
+        ),
+        &[authority, market],
+    );
+
+    assert_error(result, ErrorCode::QuestionTooLong);
+}
+
+#[test]
+fn create_market_limits_text_by_characters_and_bytes() {
+    let mut fixture = Fixture::new();
+    let mut create = |question: String, description: String| {
+        let market = fixture.svm.signer();
+        let mut params = MarketParams::new(&question, fixture.end_time);
+        params.description = description;
+        fixture.svm.process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+    };
+
+    // Accented characters take two bytes but count once
+    create("ã".repeat(200), "é".repeat(500)).unwrap();
+    assert_error(
+        create("ã".repeat(201), String::new()),
+        ErrorCode::QuestionTooLong,
+    );
+    assert_error(
+        create("🗳".repeat(101), String::new()),
+        ErrorCode::QuestionTooManyBytes,
+    );
+    assert_error(
+        create("?".to_string(), "é".repeat(501)),
+        ErrorCode::DescriptionTooLong,
+    );
+    assert_error(
+        create("?".to_string(), "🗳".repeat(251)),
+        ErrorCode::DescriptionTooManyBytes,
+    );
+}
+
+#[test]
+fn create_market_rejects_end_time_in_the_past() {
+    let mut svm = Svm::new();
+    let authority = svm.funded_account(10 * SOL);
+    svm.process(initialize_ix(authority), &[authority]).unwrap();
+    svm.process(initialize_config_ix(authority), &[authority])
+        .unwrap();
+    let market = svm.signer();
+
+    let result = svm.process(
+        create_market_ix(
+            market,
+            authority,
+            &MarketParams::new("Too late?", svm.now()),
+        ),
+        &[authority, market],
+    );
+
+    assert_error(result, ErrorCode::InvalidEndTime);
+}
+
+#[test]
+fn create_market_requires_complete_oracle_configuration() {
+    let cases = [
+        (
+            OracleParams {
+                feed_id: None,
+                threshold: Some(1),
+                comparison: Some(0),
+            },
+            ErrorCode::OracleFeedIdRequired,
+        ),
+        (
+            OracleParams {
+                feed_id: Some(FEED_ID),
+                threshold: None,
+                comparison: Some(0),
+            },
+            ErrorCode::OracleThresholdRequired,
+        ),
+        (
+            OracleParams {
+                feed_id: Some(FEED_ID),
+                threshold: Some(1),
+                comparison: None,
+            },
+            ErrorCode::OracleComparisonRequired,
+        ),
+        (
+            OracleParams {
+                feed_id: Some(FEED_ID),
+                threshold: Some(1),
+                comparison: Some(3),
+            },
+            ErrorCode::InvalidOracleComparison,
+        ),
+    ];
+
+    for (oracle, code) in cases {
+        let mut svm = Svm::new();
+        let authority = svm.funded_account(10 * SOL);
+        svm.process(initialize_ix(authority), &[authority]).unwrap();
+        svm.process(initialize_config_ix(authority), &[authority])
+            .unwrap();
+        let market = svm.signer();
+        let result = svm.process(
+            create_market_ix(
+                market,
+                authority,
+                &MarketParams {
+                    oracle: Some(oracle),
+                    ..MarketParams::new("BTC above 100k?", svm.now() + ONE_DAY)
+                },
+            ),
+            &[authority, market],
+        );
+        assert_error(result, code);
+    }
+}
+
+#[test]
+fn place_bet_moves_stake_into_market() {
+    let mut fixture = Fixture::new();
+    let market_before = fixture.svm.lamports(&fixture.market);
+
+    let user = fixture.bettor(SOL, true);
+
+    let bet: Bet = fixture.svm.fetch(&bet_pda(&user, &fixture.market));
+    assert_eq!(bet.user, user);
+    assert_eq!(bet.market, fixture.market);
+    assert_eq!(bet.amount, SOL);
+    assert!(bet.outcome);
+    assert!(!bet.claimed);
+    assert_eq!(fixture.market().yes_amount, SOL);
+    assert_eq!(fixture.market().no_amount, 0);
+    assert_eq!(fixture.svm.lamports(&fixture.market), market_before + SOL);
+}
+
+#[test]
+fn place_bet_rejects_dust() {
+    let mut fixture = Fixture::new();
+    let user = fixture.svm.funded_account(SOL);
+
+    let result = fixture.svm.process(
+        place_bet_ix(user, fixture.market, MIN_BET - 1, true),
+        &[user],
+    );
+
+    assert_error(result, ErrorCode::BetTooSmall);
+}
+
+#[test]
+fn place_bet_rejects_second_bet_from_same_user() {
+    let mut fixture = Fixture::new();
+    let user = fixture.bettor(MIN_BET, true);
+
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, MIN_BET, false), &[user]);
+
+    assert!(result.is_err());
+    assert_eq!(fixture.market().no_amount, 0);
+}
+
+#[test]
+fn place_bet_rejects_expired_market() {
+    let mut fixture = Fixture::new();
+    let user = fixture.svm.funded_account(SOL);
+    fixture.expire();
+
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, MIN_BET, true), &[user]);
+
+    assert_error(result, ErrorCode::MarketExpired);
+}
+
+#[test]
+fn place_bet_rejects_resolved_market() {
+    let mut fixture = Fixture::new();
+    fixture.resolve(true);
+    let user = fixture.svm.funded_account(SOL);
+
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, MIN_BET, true), &[user]);
+
+    assert_error(result, ErrorCode::MarketResolved);
+}
+
+#[test]
+fn place_bet_rejects_pool_overflow() {
+    let mut fixture = Fixture::new();
+    let mut market = fixture.market();
+    market.yes_amount = u64::MAX - 1;
+    fixture.svm.store(&fixture.market, &market);
+    let user = fixture.svm.funded_account(SOL);
+
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, MIN_BET, true), &[user]);
+
+    assert_error(result, ErrorCode::MathOverflow);
+}
+
+#[test]
+fn resolve_market_sets_outcome() {
+    let mut fixture = Fixture::new();
+
+    fixture.resolve(false);
+
+    let market = fixture.market();
+    assert!(market.resolved);
+    assert!(!market.winning_outcome);
+}
+
+#[test]
+fn resolve_market_waits_for_end_time() {
+    let mut fixture = Fixture::new();
+    fixture.svm.warp_to(fixture.end_time - 1);
+
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, true),
+        &[fixture.authority],
+    );
+
+    assert_error(result, ErrorCode::MarketNotExpired);
+}
+
+#[test]
+fn resolve_market_rejects_other_signers() {
+    let mut fixture = Fixture::new();
+    fixture.expire();
+    let intruder = fixture.svm.funded_account(SOL);
+
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, intruder, true),
+        &[intruder],
+    );
+
+    assert_error(result, ErrorCode::Unauthorized);
+}
+
+#[test]
+fn resolve_market_rejects_oracle_markets() {
+    let mut fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+    fixture.expire();
+
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, true),
+        &[fixture.authority],
+    );
+
+    assert_error(result, ErrorCode::MustUseOracle);
+}
+
+#[test]
+fn resolve_market_only_once() {
+    let mut fixture = Fixture::new();
+    fixture.resolve(true);
+
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, false),
+        &[fixture.authority],
+    );
+
+    assert_error(result, ErrorCode::AlreadyResolved);
+    assert!(fixture.market().winning_outcome);
+}
+
+#[test]
+fn resolve_with_oracle_applies_comparison() {
+    // (comparison, price, expected outcome) against a threshold of 100
+    let cases = [
+        (0, 101, true),
+        (0, 100, false),
+        (1, 99, true),
+        (1, 100, false),
+        (2, 100, true),
+        (2, 101, false),
+    ];
+
+    for (comparison, price, expected) in cases {
+        let mut fixture = Fixture::with_oracle(Some(OracleParams {
+            comparison: Some(comparison),
+            ..OracleParams::above(100)
+        }));
+        fixture.expire();
+        let price_update = Pubkey::new_unique();
+        let now = fixture.svm.now();
+        fixture
+            .svm
+            .set_price_update(price_update, FEED_ID, price, now);
+        let caller = fixture.svm.funded_account(SOL);
+
+        fixture
+            .svm
+            .process(
+                resolve_with_oracle_ix(fixture.market, price_update, caller),
+                &[caller],
+            )
+            .unwrap();
+
+        let market = fixture.market();
+        assert!(market.resolved);
+        assert_eq!(
+            market.winning_outcome, expected,
+            "comparison {comparison} at {price}"
+        );
+    }
+}
+
+#[test]
+fn resolve_with_oracle_waits_for_end_time() {
+    let mut fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+    let price_update = Pubkey::new_unique();
+    let now = fixture.svm.now();
+    fixture
+        .svm
+        .set_price_update(price_update, FEED_ID, 150, now);
+    let caller = fixture.svm.funded_account(SOL);
+
+    let result = fixture.svm.process(
+        resolve_with_oracle_ix(fixture.market, price_update, caller),
+        &[caller],
+    );
+
+    assert_error(result, ErrorCode::MarketNotExpired);
+}
+
+#[test]
+fn resolve_with_oracle_rejects_manual_markets() {
+    let mut fixture = Fixture::new();
+    fixture.expire();
+    let price_update = Pubkey::new_unique();
+    let now = fixture.svm.now();
+    fixture
+        .svm
+        .set_price_update(price_update, FEED_ID, 150, now);
+    let caller = fixture.svm.funded_account(SOL);
+
+    let result = fixture.svm.process(
+        resolve_with_oracle_ix(fixture.market, price_update, caller),
+        &[caller],
+    );
+
+    assert_error(result, ErrorCode::OracleNotEnabled);
+}
+
+#[test]
+fn resolve_with_oracle_rejects_stale_or_foreign_prices() {
+    let mut fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+    fixture.expire();
+    let caller = fixture.svm.funded_account(SOL);
+    let now = fixture.svm.now();
+
+    let stale = Pubkey::new_unique();
+    fixture.svm.set_price_update(stale, FEED_ID, 150, now - 61);
+    let result = fixture.svm.process(
+        resolve_with_oracle_ix(fixture.market, stale, caller),
+        &[caller],
+    );
+    assert_error(result, GetPriceError::PriceTooOld);
+
+    let other_feed = Pubkey::new_unique();
+    fixture.svm.set_price_update(other_feed, [9; 32], 150, now);
+    let result = fixture.svm.process(
+        resolve_with_oracle_ix(fixture.market, other_feed, caller),
+        &[caller],
+    );
+    assert_error(result, GetPriceError::MismatchedFeedId);
+
+    assert!(!fixture.market().resolved);
+}
+
+#[test]
+fn resolve_with_oracle_only_once() {
+    let mut fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+    fixture.expire();
+    let price_update = Pubkey::new_unique();
+    let now = fixture.svm.now();
+    fixture
+        .svm
+        .set_price_update(price_update, FEED_ID, 150, now);
+    let caller = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(
+            resolve_with_oracle_ix(fixture.market, price_update, caller),
+            &[caller],
+        )
+        .unwrap();
+
+    let result = fixture.svm.process(
+        resolve_with_oracle_ix(fixture.market, price_update, caller),
+        &[caller],
+    );
+
+    assert_error(result, ErrorCode::AlreadyResolved);
+}
+
+#[test]
+fn claim_winnings_pays_pro_rata_share() {
+    let mut fixture = Fixture::new();
+    let winner_a = fixture.bettor(SOL, true);
+    let winner_b = fixture.bettor(3 * SOL, true);
+    fixture.bettor(2 * SOL, false);
+    fixture.resolve(true);
+
+    for (winner, stake) in [(winner_a, SOL), (winner_b, 3 * SOL)] {
+        let before = fixture.svm.lamports(&winner);
+        fixture
+            .svm
+            .process(claim_winnings_ix(winner, fixture.market), &[winner])
+            .unwrap();
+        // Total pool is 6 SOL, winning side 4 SOL.
+        assert_eq!(fixture.svm.lamports(&winner) - before, stake * 6 / 4);
+        let bet: Bet = fixture.svm.fetch(&bet_pda(&winner, &fixture.market));
+        assert!(bet.claimed);
+    }
+}
+
+#[test]
+fn claim_winnings_requires_resolution() {
+    let mut fixture = Fixture::new();
+    let user = fixture.bettor(SOL, true);
+    fixture.expire();
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(user, fixture.market), &[user]);
+
+    assert_error(result, ErrorCode::MarketNotResolved);
+}
+
+#[test]
+fn claim_winnings_rejects_losing_side() {
+    let mut fixture = Fixture::new();
+    fixture.bettor(SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(loser, fixture.market), &[loser]);
+
+    assert_error(result, ErrorCode::WrongOutcome);
+}
+
+#[test]
+fn claim_winnings_only_once() {
+    let mut fixture = Fixture::new();
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture
+        .svm
+        .process(claim_winnings_ix(winner, fixture.market), &[winner])
+        .unwrap();
+    let balance = fixture.svm.lamports(&winner);
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(winner, fixture.market), &[winner]);
+
+    assert_error(result, ErrorCode::AlreadyClaimed);
+    assert_eq!(fixture.svm.lamports(&winner), balance);
+}
+
+#[test]
+fn claim_winnings_rejects_empty_winning_pool() {
+    let mut fixture = Fixture::new();
+    let user = fixture.bettor(SOL, true);
+    fixture.resolve(true);
+    // Inconsistent totals can only come from corrupted state, but the
+    // division must still be guarded.
+    let mut market = fixture.market();
+    market.yes_amount = 0;
+    fixture.svm.store(&fixture.market, &market);
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(user, fixture.market), &[user]);
+
+    assert_error(result, ErrorCode::NoWinnings);
+}
+
+/// Opens another market, with the same end time, next to the fixture's.
+fn another_market(fixture: &mut Fixture) -> Pubkey {
+    let market = fixture.svm.signer();
+    fixture
+        .svm
+        .process(
+            create_market_ix(
+                market,
+                fixture.authority,
+                &MarketParams::new("Will the vote be delayed?", fixture.end_time),
+            ),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    market
+}
+
+#[test]
+fn claim_many_pays_winning_bets_and_skips_the_rest() {
+    let mut fixture = Fixture::new();
+    let second = another_market(&mut fixture);
+    let third = another_market(&mut fixture);
+    let user = fixture.bettor(SOL, true);
+    for (market, bet_yes) in [(second, false), (third, true)] {
+        fixture.svm.airdrop(&user, SOL);
+        fixture
+            .svm
+            .process(place_bet_ix(user, market, SOL, bet_yes), &[user])
+            .unwrap();
+    }
+    for market in [fixture.market, second, third] {
+        let other = fixture.svm.funded_account(2 * SOL);
+        fixture
+            .svm
+            .process(place_bet_ix(other, market, SOL, false), &[other])
+            .unwrap();
+    }
+    fixture.resolve(true);
+    for market in [second, third] {
+        fixture
+            .svm
+            .process(
+                resolve_market_ix(market, fixture.authority, true),
+                &[fixture.authority],
+            )
+            .unwrap();
+    }
+    // Already claimed on the third market
+    fixture
+        .svm
+        .process(claim_winnings_ix(user, third), &[user])
+        .unwrap();
+    let before = fixture.svm.lamports(&user);
+
+    // The repeated pair must not pay twice
+    fixture
+        .svm
+        .process(
+            claim_many_ix(user, &[fixture.market, second, third, fixture.market]),
+            &[user],
+        )
+        .unwrap();
+
+    assert_eq!(fixture.svm.lamports(&user) - before, 2 * SOL);
+    let bet: Bet = fixture.svm.fetch(&bet_pda(&user, &fixture.market));
+    assert!(bet.claimed);
+    assert_eq!(fixture.market().claimed_bets, 1);
+    let bet: Bet = fixture.svm.fetch(&bet_pda(&user, &second));
+    assert!(!bet.claimed);
+}
+
+#[test]
+fn claim_many_rejects_malformed_pairs() {
+    let mut fixture = Fixture::new();
+    let user = fixture.bettor(SOL, true);
+    let other = fixture.bettor(SOL, true);
+    fixture.resolve(true);
+
+    // Someone else's bet
+    let mut ix = claim_many_ix(user, &[]);
+    ix.accounts.push(AccountMeta::new(fixture.market, false));
+    ix.accounts
+        .push(AccountMeta::new(bet_pda(&other, &fixture.market), false));
+    let result = fixture.svm.process(ix, &[user]);
+    assert_error(result, ErrorCode::InvalidClaimPair);
+
+    // A market without its bet
+    let mut ix = claim_many_ix(user, &[fixture.market]);
+    ix.accounts.pop();
+    let result = fixture.svm.process(ix, &[user]);
+    assert_error(result, ErrorCode::InvalidClaimPair);
+
+    // Pair in the wrong order
+    let mut ix = claim_many_ix(user, &[fixture.market]);
+    let last = ix.accounts.len() - 1;
+    ix.accounts.swap(last - 1, last);
+    let result = fixture.svm.process(ix, &[user]);
+    assert_error(result, ErrorCode::InvalidClaimPair);
+}
+
+#[test]
+fn close_bet_refunds_rent_after_claim() {
+    let mut fixture = Fixture::new();
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture.claim(winner);
+    let bet = bet_pda(&winner, &fixture.market);
+    let rent = fixture.svm.lamports(&bet);
+    let before = fixture.svm.lamports(&winner);
+
+    fixture
+        .svm
+        .process(close_bet_ix(winner, fixture.market), &[winner])
+        .unwrap();
+
+    assert!(fixture.svm.account(&bet).is_none());
+    assert_eq!(fixture.svm.lamports(&winner), before + rent);
+}
+
+#[test]
+fn close_bet_refunds_losing_bet() {
+    let mut fixture = Fixture::new();
+    fixture.bettor(SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+
+    fixture
+        .svm
+        .process(close_bet_ix(loser, fixture.market), &[loser])
+        .unwrap();
+
+    assert!(fixture
+        .svm
+        .account(&bet_pda(&loser, &fixture.market))
+        .is_none());
+}
+
+#[test]
+fn close_bet_rejects_open_positions() {
+    let mut fixture = Fixture::new();
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+
+    let result = fixture
+        .svm
+        .process(close_bet_ix(winner, fixture.market), &[winner]);
+    assert_error(result, ErrorCode::MarketNotResolved);
+
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(close_bet_ix(winner, fixture.market), &[winner]);
+    assert_error(result, ErrorCode::BetNotSettled);
+}
+
+#[test]
+fn finalize_market_sweeps_dust_and_closes_market() {
+    let mut fixture = Fixture::new();
+    // Stakes chosen so the pro-rata division leaves a lamport behind
+    let winners = [
+        fixture.bettor(MIN_BET + 1, true),
+        fixture.bettor(MIN_BET + 2, true),
+        fixture.bettor(MIN_BET + 4, true),
+    ];
+    let loser = fixture.bettor(MIN_BET + 7, false);
+    fixture.resolve(true);
+    for winner in winners {
+        fixture.claim(winner);
+    }
+    fixture.record(loser);
+    let market_lamports = fixture.svm.lamports(&fixture.market);
+    let rent =
+        Rent::default().minimum_balance(fixture.svm.account(&fixture.market).unwrap().data.len());
+    let dust = market_lamports - rent;
+    assert_eq!(dust, 1);
+    let treasury_before = fixture.svm.lamports(&treasury_pda());
+    let authority_before = fixture.svm.lamports(&fixture.authority);
+    let cranker = fixture.svm.funded_account(SOL);
+
+    fixture
+        .svm
+        .process(
+            finalize_market_ix(fixture.market, fixture.authority),
+            &[cranker],
+        )
+        .unwrap();
+
+    assert!(fixture.svm.account(&fixture.market).is_none());
+    assert_eq!(
+        fixture.svm.lamports(&treasury_pda()),
+        treasury_before + dust
+    );
+    assert_eq!(
+        fixture.svm.lamports(&fixture.authority),
+        authority_before + rent
+    );
+    let treasury: Treasury = fixture.svm.fetch(&treasury_pda());
+    assert_eq!(treasury.total_collected, dust);
+}
+
+#[test]
+fn finalize_market_waits_for_every_winner() {
+    let mut fixture = Fixture::new();
+    let first = fixture.bettor(SOL, true);
+    let second = fixture.bettor(SOL, true);
+    let loser = fixture.bettor(SOL, false);
+
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::MarketNotResolved);
+
+    fixture.resolve(true);
+    fixture.claim(first);
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::MarketNotSettled);
+
+    // The loss must reach the loser's profile before the market closes
+    fixture.claim(second);
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::PredictionsUnrecorded);
+    fixture.record(loser);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn markets_without_winners_refund_the_losing_side() {
+    let mut fixture = Fixture::new();
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    assert!(fixture.market().invalidated);
+    let treasury_before = fixture.svm.lamports(&treasury_pda());
+
+    // The stake is not the treasury's to take
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::MarketNotSettled);
+
+    let balance = fixture.svm.lamports(&loser);
+    fixture
+        .svm
+        .process(claim_refund_ix(loser, fixture.market), &[loser])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&loser), balance + SOL);
+
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&treasury_pda()), treasury_before);
+}
+
+#[test]
+fn bets_from_finalized_market_cannot_claim_against_reused_address() {
+    let mut fixture = Fixture::new();
+    let winner = fixture.bettor(SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture.claim(winner);
+    fixture.record(loser);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+
+    // The creator still holds the market keypair and opens a new market at
+    // the same address, which then resolves the way the stale bet predicted.
+    fixture.end_time = fixture.svm.now() + ONE_DAY;
+    fixture
+        .svm
+        .process(
+            create_market_ix(
+                fixture.market,
+                fixture.authority,
+                &MarketParams::new("Round two?", fixture.end_time),
+            ),
+            &[fixture.authority, fixture.market],
+        )
+        .unwrap();
+    fixture.bettor(SOL, false);
+    fixture.resolve(false);
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(loser, fixture.market), &[loser]);
+    assert_error(result, ErrorCode::StaleBet);
+
+    fixture
+        .svm
+        .process(close_bet_ix(loser, fixture.market), &[loser])
+        .unwrap();
+}
+
+#[test]
+fn create_market_validates_claim_deadline_and_policy() {
+    let mut fixture = Fixture::new();
+    let end_time = fixture.end_time;
+    let cases = [
+        (
+            MarketParams {
+                claim_deadline: Some(end_time),
+                ..MarketParams::new("Deadline?", end_time)
+            },
+            ErrorCode::InvalidClaimDeadline,
+        ),
+        (
+            MarketParams {
+                unclaimed_policy: 2,
+                ..MarketParams::new("Policy?", end_time)
+            },
+            ErrorCode::InvalidUnclaimedPolicy,
+        ),
+    ];
+
+    for (params, code) in cases {
+        let market = fixture.svm.signer();
+        let result = fixture.svm.process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        );
+        assert_error(result, code);
+    }
+}
+
+fn deadline_fixture(policy: u8) -> Fixture {
+    Fixture::with(|params| {
+        params.claim_deadline = Some(params.end_time + ONE_DAY);
+        params.unclaimed_policy = policy;
+    })
+}
+
+#[test]
+fn claim_winnings_closes_at_deadline() {
+    let mut fixture = deadline_fixture(0);
+    let winner = fixture.bettor(SOL, true);
+    fixture.resolve(true);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(winner, fixture.market), &[winner]);
+
+    assert_error(result, ErrorCode::ClaimDeadlinePassed);
+}
+
+#[test]
+fn report_unclaimed_emits_open_winning_positions() {
+    let mut fixture = deadline_fixture(0);
+    let claimed = fixture.bettor(SOL, true);
+    let unclaimed = fixture.bettor(2 * SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture.claim(claimed);
+    let bets: Vec<Pubkey> = [claimed, unclaimed, loser]
+        .iter()
+        .map(|user| bet_pda(user, &fixture.market))
+        .chain([fixture.market, Pubkey::new_unique()])
+        .collect();
+
+    fixture
+        .svm
+        .process(report_unclaimed_ix(fixture.market, &bets), &[])
+        .unwrap();
+
+    let events = fixture.svm.events::<UnclaimedPosition>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].user, unclaimed);
+    assert_eq!(events[0].bet, bet_pda(&unclaimed, &fixture.market));
+    assert_eq!(events[0].amount, 2 * SOL);
+    assert_eq!(events[0].claim_deadline, fixture.market().claim_deadline);
+}
+
+#[test]
+fn settle_unclaimed_requires_passed_deadline() {
+    let mut fixture = Fixture::new();
+    fixture.bettor(SOL, true);
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::NoClaimDeadline);
+
+    let mut fixture = deadline_fixture(0);
+    fixture.bettor(SOL, true);
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::MarketNotResolved);
+
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::ClaimDeadlineNotReached);
+
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::UnclaimedAlreadySettled);
+}
+
+#[test]
+fn settle_unclaimed_sweeps_to_treasury() {
+    let mut fixture = deadline_fixture(0);
+    let claimed = fixture.bettor(SOL, true);
+    let forfeited = fixture.bettor(SOL, true);
+    let loser = fixture.bettor(2 * SOL, false);
+    fixture.resolve(true);
+    fixture.claim(claimed);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+    let treasury_before = fixture.svm.lamports(&treasury_pda());
+
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+
+    assert_eq!(
+        fixture.svm.lamports(&treasury_pda()),
+        treasury_before + 2 * SOL
+    );
+    let events = fixture.svm.events::<UnclaimedSettled>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].unclaimed_bets, 1);
+    assert_eq!(events[0].amount, 2 * SOL);
+    assert!(!events[0].redistributed);
+
+    // The forfeited bet can be closed and the market finalized
+    fixture
+        .svm
+        .process(close_bet_ix(forfeited, fixture.market), &[forfeited])
+        .unwrap();
+    fixture.record(loser);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+    assert!(fixture.svm.account(&fixture.market).is_none());
+}
+
+#[test]
+fn settle_unclaimed_redistributes_to_claimed_winners() {
+    let mut fixture = deadline_fixture(1);
+    let first = fixture.bettor(SOL, true);
+    let second = fixture.bettor(3 * SOL, true);
+    let forfeited = fixture.bettor(2 * SOL, true);
+    let loser = fixture.bettor(2 * SOL, false);
+    fixture.resolve(true);
+    fixture.claim(first);
+    fixture.claim(second);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+
+    // Pool of 8 SOL over 6 SOL of winning stake: the forfeited 2 SOL stake
+    // leaves 8/3 SOL behind for the 4 SOL of claimed stake.
+    let market = fixture.market();
+    assert!(market.unclaimed_settled);
+    let pool = market.redistribution_pool;
+    assert_eq!(pool, 8 * SOL - (SOL * 8 / 6) - (3 * SOL * 8 / 6));
+    assert!(fixture.svm.events::<UnclaimedSettled>()[0].redistributed);
+
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::MarketNotSettled);
+    let result = fixture
+        .svm
+        .process(close_bet_ix(first, fixture.market), &[first]);
+    assert_error(result, ErrorCode::BetNotSettled);
+
+    for (winner, stake) in [(first, SOL), (second, 3 * SOL)] {
+        let before = fixture.svm.lamports(&winner);
+        fixture
+            .svm
+            .process(claim_redistribution_ix(winner, fixture.market), &[winner])
+            .unwrap();
+        assert_eq!(
+            fixture.svm.lamports(&winner) - before,
+            pool * stake / (4 * SOL)
+        );
+    }
+    let result = fixture
+        .svm
+        .process(claim_redistribution_ix(first, fixture.market), &[first]);
+    assert_error(result, ErrorCode::AlreadyClaimed);
+
+    fixture.record(forfeited);
+    fixture.record(loser);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn redistribution_window_expires() {
+    let mut fixture = deadline_fixture(1);
+    let claimed = fixture.bettor(SOL, true);
+    let forfeited = fixture.bettor(SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture.claim(claimed);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+
+    let result = fixture.svm.process(
+        claim_redistribution_ix(forfeited, fixture.market),
+        &[forfeited],
+    );
+    assert_error(result, ErrorCode::NoRedistribution);
+
+    fixture.svm.warp_to(deadline + REDISTRIBUTION_PERIOD);
+    fixture.record(forfeited);
+    fixture.record(loser);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn redistribution_falls_back_to_treasury_without_claimants() {
+    let mut fixture = deadline_fixture(1);
+    fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+    let treasury_before = fixture.svm.lamports(&treasury_pda());
+
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+
+    assert_eq!(
+        fixture.svm.lamports(&treasury_pda()),
+        treasury_before + 2 * SOL
+    );
+    assert!(!fixture.svm.events::<UnclaimedSettled>()[0].redistributed);
+}
+
+fn fee(amount: u64) -> u64 {
+    amount * LP_FEE_BPS / 10_000
+}
+
+#[test]
+fn add_liquidity_mints_shares_at_the_current_ratio() {
+    let mut fixture = Fixture::new();
+    let first = fixture.provide(2 * SOL, SOL);
+
+    let position: LiquidityPosition = fixture.svm.fetch(&liquidity_pda(&first, &fixture.market));
+    assert_eq!(position.provider, first);
+    assert_eq!(position.lp_tokens, 3 * SOL);
+    let market = fixture.market();
+    assert_eq!((market.yes_amount, market.no_amount), (2 * SOL, SOL));
+    assert_eq!(market.lp_shares, 3 * SOL);
+
+    // Only the part of the deposit matching the 2:1 inventory is taken
+    let second = fixture.svm.funded_account(3 * SOL);
+    let before = fixture.svm.lamports(&second);
+    fixture
+        .svm
+        .process(
+            add_liquidity_ix(second, fixture.market, SOL, SOL),
+            &[second],
+        )
+        .unwrap();
+
+    let position: LiquidityPosition = fixture.svm.fetch(&liquidity_pda(&second, &fixture.market));
+    assert_eq!(position.lp_tokens, 3 * SOL / 2);
+    assert_eq!((position.amount_yes, position.amount_no), (SOL, SOL / 2));
+    let rent = fixture
+        .svm
+        .lamports(&liquidity_pda(&second, &fixture.market));
+    assert_eq!(before - fixture.svm.lamports(&second), 3 * SOL / 2 + rent);
+    let market = fixture.market();
+    assert_eq!((market.lp_yes, market.lp_no), (3 * SOL, 3 * SOL / 2));
+}
+
+#[test]
+fn add_liquidity_rejects_one_sided_or_late_deposits() {
+    let mut fixture = Fixture::new();
+    let provider = fixture.svm.funded_account(10 * SOL);
+
+    let result = fixture.svm.process(
+        add_liquidity_ix(provider, fixture.market, SOL, 0),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::InvalidLiquidityAmount);
+
+    fixture.expire();
+    let result = fixture.svm.process(
+        add_liquidity_ix(provider, fixture.market, SOL, SOL),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::MarketExpired);
+}
+
+#[test]
+fn bets_pay_fees_to_liquidity_providers() {
+    let mut fixture = Fixture::new();
+    let provider = fixture.provide(SOL, SOL);
+    let user = fixture.bettor(2 * SOL, true);
+
+    let bet: Bet = fixture.svm.fetch(&bet_pda(&user, &fixture.market));
+    assert_eq!(bet.amount, 2 * SOL - fee(2 * SOL));
+    let market = fixture.market();
+    assert_eq!(market.yes_amount, SOL + bet.amount);
+    assert_eq!(market.lp_fees, fee(2 * SOL));
+
+    // Withdrawing while open returns the inventory plus the fees
+    let before = fixture.svm.lamports(&provider);
+    let rent = fixture
+        .svm
+        .lamports(&liquidity_pda(&provider, &fixture.market));
+    fixture
+        .svm
+        .process(
+            remove_liquidity_ix(provider, fixture.market, 2 * SOL),
+            &[provider],
+        )
+        .unwrap();
+
+    assert_eq!(
+        fixture.svm.lamports(&provider) - before,
+        2 * SOL + fee(2 * SOL) + rent
+    );
+    assert!(fixture
+        .svm
+        .account(&liquidity_pda(&provider, &fixture.market))
+        .is_none());
+    let market = fixture.market();
+    assert_eq!((market.yes_amount, market.no_amount), (bet.amount, 0));
+    assert_eq!((market.lp_shares, market.lp_fees), (0, 0));
+
+    // Without providers, bets are fee-free again
+    let user = fixture.bettor(SOL, false);
+    let bet: Bet = fixture.svm.fetch(&bet_pda(&user, &fixture.market));
+    assert_eq!(bet.amount, SOL);
+}
+
+#[test]
+fn remove_liquidity_pays_winning_inventory_after_resolution() {
+    let mut fixture = Fixture::new();
+    let provider = fixture.provide(SOL, SOL);
+    let winner = fixture.bettor(2 * SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture.record(loser);
+    let market = fixture.market();
+    let total_pool = (market.yes_amount + market.no_amount) as u128;
+
+    // The market cannot close while LP shares are outstanding
+    fixture.claim(winner);
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::MarketNotSettled);
+
+    let before = fixture.svm.lamports(&provider);
+    let rent = fixture
+        .svm
+        .lamports(&liquidity_pda(&provider, &fixture.market));
+    fixture
+        .svm
+        .process(
+            remove_liquidity_ix(provider, fixture.market, 2 * SOL),
+            &[provider],
+        )
+        .unwrap();
+
+    let inventory = (SOL as u128 * total_pool / market.yes_amount as u128) as u64;
+    assert_eq!(
+        fixture.svm.lamports(&provider) - before,
+        inventory + fee(2 * SOL) + fee(SOL) + rent
+    );
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn remove_liquidity_is_locked_until_resolution() {
+    let mut fixture = Fixture::new();
+    let provider = fixture.provide(SOL, SOL);
+    fixture.expire();
+
+    let result = fixture.svm.process(
+        remove_liquidity_ix(provider, fixture.market, SOL),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::LiquidityLocked);
+
+    let result = fixture.svm.process(
+        remove_liquidity_ix(provider, fixture.market, 3 * SOL),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::InsufficientLpTokens);
+}
+
+#[test]
+fn settle_unclaimed_leaves_liquidity_in_place() {
+    let mut fixture = deadline_fixture(0);
+    let provider = fixture.provide(SOL, SOL);
+    let forfeited = fixture.bettor(SOL, true);
+    fixture.resolve(true);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline);
+
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+
+    let market = fixture.market();
+    let total_pool = (market.yes_amount + market.no_amount) as u128;
+    let inventory = (SOL as u128 * total_pool / market.yes_amount as u128) as u64;
+    let events = fixture.svm.events::<UnclaimedSettled>();
+    assert_eq!(
+        events[0].amount,
+        market.yes_amount + market.no_amount - inventory
+    );
+
+    // The provider can still withdraw after the deadline
+    let before = fixture.svm.lamports(&provider);
+    let rent = fixture
+        .svm
+        .lamports(&liquidity_pda(&provider, &fixture.market));
+    fixture
+        .svm
+        .process(
+            remove_liquidity_ix(provider, fixture.market, 2 * SOL),
+            &[provider],
+        )
+        .unwrap();
+    assert_eq!(
+        fixture.svm.lamports(&provider) - before,
+        inventory + fee(SOL) + rent
+    );
+    fixture
+        .svm
+        .process(close_bet_ix(forfeited, fixture.market), &[forfeited])
+        .unwrap();
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn place_bet_tracks_volume_in_profile() {
+    let mut fixture = Fixture::new();
+    let second = another_market(&mut fixture);
+    let user = fixture.svm.funded_account(5 * SOL);
+    fixture
+        .svm
+        .process(create_user_profile_ix(user), &[user])
+        .unwrap();
+    assert_eq!(fixture.profile(&user).user, user);
+
+    fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, SOL, true), &[user])
+        .unwrap();
+    fixture
+        .svm
+        .process(place_bet_ix(user, second, 2 * SOL, false), &[user])
+        .unwrap();
+
+    let profile = fixture.profile(&user);
+    assert_eq!(profile.total_volume, 3 * SOL);
+    assert_eq!(profile.markets_participated, 2);
+    assert_eq!(
+        (profile.wins, profile.losses, profile.realized_pnl),
+        (0, 0, 0)
+    );
+
+    // Bettors who never created one get a profile on their first bet
+    let newcomer = fixture.bettor(SOL, true);
+    assert_eq!(fixture.profile(&newcomer).markets_participated, 1);
+}
+
+#[test]
+fn results_are_recorded_in_profiles() {
+    let mut fixture = Fixture::new();
+    let winner = fixture.bettor(SOL, true);
+    let loser = fixture.bettor(3 * SOL, false);
+    fixture.resolve(true);
+    fixture.claim(winner);
+
+    // Winning at 1:4 odds: forecast 25%, Brier (1 - 0.25)^2
+    let profile = fixture.profile(&winner);
+    assert_eq!((profile.wins, profile.losses), (1, 0));
+    assert_eq!(profile.realized_pnl, 3 * SOL as i64);
+    assert_eq!(profile.brier_score(), 5_625);
+
+    // Winners are recorded by their claim, losers by anyone
+    let result = fixture
+        .svm
+        .process(record_prediction_ix(winner, fixture.market), &[]);
+    assert_error(result, ErrorCode::AlreadyRecorded);
+    fixture
+        .svm
+        .process(record_prediction_ix(loser, fixture.market), &[])
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(record_prediction_ix(loser, fixture.market), &[]);
+    assert_error(result, ErrorCode::AlreadyRecorded);
+
+    // Losing at 3:4 odds: forecast 75%, Brier 0.75^2
+    let profile = fixture.profile(&loser);
+    assert_eq!((profile.wins, profile.losses), (0, 1));
+    assert_eq!(profile.realized_pnl, -3 * SOL as i64);
+    assert_eq!(profile.brier_score(), 5_625);
+
+    // Closing the bet afterwards does not count it twice
+    fixture
+        .svm
+        .process(close_bet_ix(loser, fixture.market), &[loser])
+        .unwrap();
+    assert_eq!(fixture.profile(&loser).losses, 1);
+}
+
+#[test]
+fn self_decided_and_uncontested_bets_are_not_scored() {
+    // The creator decides their own market
+    let mut fixture = Fixture::new();
+    let creator = fixture.authority;
+    fixture.svm.airdrop(&creator, 2 * SOL);
+    fixture
+        .svm
+        .process(place_bet_ix(creator, fixture.market, SOL, true), &[creator])
+        .unwrap();
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    fixture.claim(creator);
+    let profile = fixture.profile(&creator);
+    assert_eq!((profile.wins, profile.losses, profile.brier_sum), (0, 0, 0));
+    assert_eq!(profile.realized_pnl, SOL as i64);
+    fixture.record(loser);
+    assert_eq!(fixture.profile(&loser).losses, 1);
+
+    // Alone in the pool, a bet forecasts certainty
+    let mut fixture = Fixture::new();
+    let lone = fixture.bettor(SOL, true);
+    fixture.resolve(true);
+    fixture.claim(lone);
+    let profile = fixture.profile(&lone);
+    assert_eq!((profile.wins, profile.brier_sum), (0, 0));
+    assert!(
+        fixture
+            .svm
+            .fetch::<Bet>(&bet_pda(&lone, &fixture.market))
+            .recorded
+    );
+}
+
+#[test]
+fn record_prediction_waits_for_unclaimed_winners() {
+    let mut fixture = Fixture::new();
+    let winner = fixture.bettor(SOL, true);
+    fixture.expire();
+
+    let result = fixture
+        .svm
+        .process(record_prediction_ix(winner, fixture.market), &[]);
+    assert_error(result, ErrorCode::MarketNotResolved);
+
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(record_prediction_ix(winner, fixture.market), &[]);
+    assert_error(result, ErrorCode::BetNotSettled);
+}
+
+#[test]
+fn close_bet_records_losses() {
+    let mut fixture = Fixture::new();
+    fixture.bettor(SOL, true);
+    let loser = fixture.bettor(SOL, false);
+    fixture.resolve(true);
+
+    fixture
+        .svm
+        .process(close_bet_ix(loser, fixture.market), &[loser])
+        .unwrap();
+
+    let profile = fixture.profile(&loser);
+    assert_eq!(profile.losses, 1);
+    assert_eq!(profile.realized_pnl, -(SOL as i64));
+    assert_eq!(profile.brier_score(), 2_500);
+}
+
+/// Names a human verifier and opens a `require_human` market whose
+/// creator it has attested.
+fn human_market(fixture: &mut Fixture) -> (Pubkey, Pubkey) {
+    let verifier = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(
+            set_human_verifier_ix(fixture.admin, verifier),
+            &[fixture.admin],
+        )
+        .unwrap();
+    fixture
+        .svm
+        .process(attest_human_ix(verifier, fixture.authority, 0), &[verifier])
+        .unwrap();
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will the mayor resign?", fixture.end_time);
+    params.require_human = true;
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    (verifier, market)
+}
+
+#[test]
+fn set_human_verifier_is_admin_only() {
+    let mut fixture = Fixture::new();
+    let verifier = Pubkey::new_unique();
+
+    let result = fixture.svm.process(
+        set_human_verifier_ix(fixture.authority, verifier),
+        &[fixture.authority],
+    );
+    assert_error(result, anchor_lang::error::ErrorCode::ConstraintHasOne);
+
+    fixture
+        .svm
+        .process(
+            set_human_verifier_ix(fixture.admin, verifier),
+            &[fixture.admin],
+        )
+        .unwrap();
+    let state: GlobalState = fixture.svm.fetch(&global_state_pda());
+    assert_eq!(state.human_verifier, verifier);
+}
+
+#[test]
+fn attest_human_requires_the_configured_verifier() {
+    let mut fixture = Fixture::new();
+    let (verifier, _) = human_market(&mut fixture);
+    let user = fixture.svm.funded_account(SOL);
+    let impostor = fixture.svm.funded_account(SOL);
+
+    let result = fixture
+        .svm
+        .process(attest_human_ix(impostor, user, 0), &[impostor]);
+    assert_error(result, ErrorCode::Unauthorized);
+
+    let now = fixture.svm.now();
+    let result = fixture
+        .svm
+        .process(attest_human_ix(verifier, user, now), &[verifier]);
+    assert_error(result, ErrorCode::InvalidAttestationExpiry);
+}
+
+#[test]
+fn create_market_requires_attested_creator_for_human_markets() {
+    let mut fixture = Fixture::new();
+    human_market(&mut fixture);
+    assert!(!fixture.market().require_human);
+
+    let creator = fixture.svm.funded_account(10 * SOL);
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will the mayor resign?", fixture.end_time);
+    params.require_human = true;
+    let mut ix = create_market_ix(market, creator, &params);
+    // No attestation passed at all
+    ix.accounts[2] = AccountMeta::new_readonly(prediction_market::ID, false);
+    let result = fixture.svm.process(ix, &[creator, market]);
+
+    assert_error(result, ErrorCode::HumanVerificationRequired);
+}
+
+#[test]
+fn place_bet_requires_live_attestation_on_human_markets() {
+    let mut fixture = Fixture::new();
+    let (verifier, market) = human_market(&mut fixture);
+    assert!(fixture.svm.fetch::<Market>(&market).require_human);
+    let user = fixture.svm.funded_account(10 * SOL);
+
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, market, SOL, true), &[user]);
+    assert_error(result, ErrorCode::HumanVerificationRequired);
+
+    let expires_at = fixture.svm.now() + 60;
+    fixture
+        .svm
+        .process(attest_human_ix(verifier, user, expires_at), &[verifier])
+        .unwrap();
+    fixture.svm.warp_to(expires_at);
+    let result = fixture
+        .svm
+        .process(place_human_bet_ix(user, market, SOL, true), &[user]);
+    assert_error(result, ErrorCode::AttestationExpired);
+
+    // Re-attesting refreshes the expiry
+    fixture
+        .svm
+        .process(attest_human_ix(verifier, user, 0), &[verifier])
+        .unwrap();
+    fixture
+        .svm
+        .process(place_human_bet_ix(user, market, SOL, true), &[user])
+        .unwrap();
+
+    // Markets without the flag ignore verification
+    let other = fixture.svm.funded_account(10 * SOL);
+    fixture
+        .svm
+        .process(place_bet_ix(other, fixture.market, SOL, true), &[other])
+        .unwrap();
+}
+
+#[test]
+fn liquidity_and_splits_require_attestation_on_human_markets() {
+    let mut fixture = Fixture::new();
+    let (verifier, market) = human_market(&mut fixture);
+    fixture
+        .svm
+        .process(
+            initialize_order_book_ix(market, fixture.authority),
+            &[fixture.authority],
+        )
+        .unwrap();
+    let user = fixture.svm.funded_account(10 * SOL);
+
+    let result = fixture
+        .svm
+        .process(add_liquidity_ix(user, market, SOL, SOL), &[user]);
+    assert_error(result, ErrorCode::HumanVerificationRequired);
+    let result = fixture
+        .svm
+        .process(split_position_ix(user, market, 2), &[user]);
+    assert_error(result, ErrorCode::HumanVerificationRequired);
+
+    fixture
+        .svm
+        .process(attest_human_ix(verifier, user, 0), &[verifier])
+        .unwrap();
+    fixture
+        .svm
+        .process(add_human_liquidity_ix(user, market, SOL, SOL), &[user])
+        .unwrap();
+    fixture
+        .svm
+        .process(split_human_position_ix(user, market, 2), &[user])
+        .unwrap();
+    assert_eq!(
+        fixture
+            .svm
+            .fetch::<Position>(&position_pda(&market, &user))
+            .yes_shares,
+        2
+    );
+}
+
+#[test]
+fn revoked_or_rotated_attestations_stop_counting() {
+    let mut fixture = Fixture::new();
+    let (verifier, market) = human_market(&mut fixture);
+    let user = fixture.svm.funded_account(10 * SOL);
+    fixture
+        .svm
+        .process(attest_human_ix(verifier, user, 0), &[verifier])
+        .unwrap();
+
+    // Rotating the verifier invalidates what the old one issued
+    let new_verifier = Pubkey::new_unique();
+    fixture
+        .svm
+        .process(
+            set_human_verifier_ix(fixture.admin, new_verifier),
+            &[fixture.admin],
+        )
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(place_human_bet_ix(user, market, SOL, true), &[user]);
+    assert_error(result, ErrorCode::HumanVerificationRequired);
+
+    let before = fixture.svm.lamports(&verifier);
+    let rent = fixture.svm.lamports(&human_pda(&user));
+    fixture
+        .svm
+        .process(revoke_human_ix(verifier, user), &[verifier])
+        .unwrap();
+    assert!(fixture.svm.account(&human_pda(&user)).is_none());
+    assert_eq!(fixture.svm.lamports(&verifier), before + rent);
+}
+
+/// Appoints a moderator and files a report against the fixture's market.
+fn reported_market(fixture: &mut Fixture) -> (Pubkey, Pubkey) {
+    let moderator = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(set_moderator_ix(fixture.admin, moderator), &[fixture.admin])
+        .unwrap();
+    let reporter = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(
+            report_market_ix(reporter, fixture.market, 1, [9; 32]),
+            &[reporter],
+        )
+        .unwrap();
+    (moderator, reporter)
+}
+
+fn moderate(fixture: &mut Fixture, moderator: Pubkey, reporter: Pubkey, action: u8) {
+    fixture
+        .svm
+        .process(
+            moderate_report_ix(moderator, fixture.market, reporter, action),
+            &[moderator],
+        )
+        .unwrap();
+}
+
+#[test]
+fn report_market_files_a_pending_report() {
+    let mut fixture = Fixture::new();
+    let (_, reporter) = reported_market(&mut fixture);
+
+    let report: ContentReport = fixture.svm.fetch(&report_pda(&fixture.market, &reporter));
+    assert_eq!(report.reporter, reporter);
+    assert_eq!((report.report_type, report.status), (1, 0));
+    assert_eq!(report.reason_hash, [9; 32]);
+    assert_eq!(fixture.market().report_count, 1);
+    let events = fixture.svm.events::<MarketReported>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].reporter, reporter);
+
+    // One report per wallet and market
+    let result = fixture.svm.process(
+        report_market_ix(reporter, fixture.market, 0, [0; 32]),
+        &[reporter],
+    );
+    assert!(result.is_err());
+
+    let other = fixture.svm.funded_account(SOL);
+    let result = fixture.svm.process(
+        report_market_ix(other, fixture.market, 5, [0; 32]),
+        &[other],
+    );
+    assert_error(result, ErrorCode::InvalidReportType);
+}
+
+#[test]
+fn moderate_report_is_moderator_only_and_final() {
+    let mut fixture = Fixture::new();
+    let (moderator, reporter) = reported_market(&mut fixture);
+
+    let result = fixture.svm.process(
+        moderate_report_ix(fixture.admin, fixture.market, reporter, 0),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    let result = fixture.svm.process(
+        moderate_report_ix(moderator, fixture.market, reporter, 4),
+        &[moderator],
+    );
+    assert_error(result, ErrorCode::InvalidModerationAction);
+
+    moderate(&mut fixture, moderator, reporter, 0);
+
+    let report: ContentReport = fixture.svm.fetch(&report_pda(&fixture.market, &reporter));
+    assert_eq!((report.status, report.action), (1, 0));
+    assert_eq!(fixture.market().moderation_status, MODERATION_VISIBLE);
+    let events = fixture.svm.events::<MarketModerated>();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].moderator, events[0].action), (moderator, 0));
+
+    let result = fixture.svm.process(
+        moderate_report_ix(moderator, fixture.market, reporter, 1),
+        &[moderator],
+    );
+    assert_error(result, ErrorCode::ReportAlreadyResolved);
+}
+
+#[test]
+fn frozen_markets_stop_until_restored() {
+    let mut fixture = Fixture::new();
+    let (moderator, reporter) = reported_market(&mut fixture);
+    fixture.bettor(SOL, true);
+    moderate(&mut fixture, moderator, reporter, 2);
+    assert_eq!(fixture.market().moderation_status, MODERATION_FROZEN);
+
+    let user = fixture.svm.funded_account(2 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, SOL, false), &[user]);
+    assert_error(result, ErrorCode::MarketFrozen);
+    fixture.expire();
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::MarketFrozen);
+
+    let result = fixture.svm.process(
+        restore_market_ix(fixture.authority, fixture.market),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(restore_market_ix(moderator, fixture.market), &[moderator])
+        .unwrap();
+    fixture.resolve(true);
+}
+
+#[test]
+fn hidden_markets_keep_trading() {
+    let mut fixture = Fixture::new();
+    let (moderator, reporter) = reported_market(&mut fixture);
+    moderate(&mut fixture, moderator, reporter, 1);
+
+    assert_eq!(fixture.market().moderation_status, MODERATION_HIDDEN);
+    fixture.bettor(SOL, true);
+}
+
+#[test]
+fn invalidated_markets_refund_every_bet() {
+    let mut fixture = Fixture::new();
+    let (moderator, reporter) = reported_market(&mut fixture);
+    let yes = fixture.bettor(SOL, true);
+    let no = fixture.bettor(3 * SOL, false);
+    moderate(&mut fixture, moderator, reporter, 3);
+
+    let market = fixture.market();
+    assert!(market.resolved && market.invalidated);
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(yes, fixture.market), &[yes]);
+    assert_error(result, ErrorCode::MarketInvalidated);
+    let result = fixture.svm.process(close_bet_ix(no, fixture.market), &[no]);
+    assert_error(result, ErrorCode::BetNotSettled);
+
+    for (user, stake) in [(yes, SOL), (no, 3 * SOL)] {
+        let before = fixture.svm.lamports(&user);
+        fixture
+            .svm
+            .process(claim_refund_ix(user, fixture.market), &[user])
+            .unwrap();
+        assert_eq!(fixture.svm.lamports(&user) - before, stake);
+        fixture
+            .svm
+            .process(close_bet_ix(user, fixture.market), &[user])
+            .unwrap();
+        // Refunds are not predictions
+        assert_eq!(fixture.profile(&user).losses, 0);
+    }
+
+    // Nothing but rent is left, so the market closes with no dust
+    let treasury = fixture.svm.lamports(&treasury_pda());
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&treasury_pda()), treasury);
+}
+
+#[test]
+fn invalidation_returns_liquidity_inventory() {
+    let mut fixture = Fixture::new();
+    let (moderator, reporter) = reported_market(&mut fixture);
+    let provider = fixture.provide(SOL, SOL);
+    let user = fixture.bettor(SOL, true);
+    moderate(&mut fixture, moderator, reporter, 3);
+
+    let before = fixture.svm.lamports(&provider);
+    let rent = fixture
+        .svm
+        .lamports(&liquidity_pda(&provider, &fixture.market));
+    fixture
+        .svm
+        .process(
+            remove_liquidity_ix(provider, fixture.market, 2 * SOL),
+            &[provider],
+        )
+        .unwrap();
+    assert_eq!(
+        fixture.svm.lamports(&provider) - before,
+        2 * SOL + fee(SOL) + rent
+    );
+
+    let before = fixture.svm.lamports(&user);
+    fixture
+        .svm
+        .process(claim_refund_ix(user, fixture.market), &[user])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&user) - before, SOL - fee(SOL));
+}
+
+#[test]
+fn resolved_markets_cannot_be_invalidated() {
+    let mut fixture = Fixture::new();
+    let (moderator, reporter) = reported_market(&mut fixture);
+    let user = fixture.bettor(SOL, true);
+    fixture.resolve(true);
+
+    let result = fixture.svm.process(
+        moderate_report_ix(moderator, fixture.market, reporter, 3),
+        &[moderator],
+    );
+    assert_error(result, ErrorCode::MarketResolved);
+
+    let result = fixture
+        .svm
+        .process(claim_refund_ix(user, fixture.market), &[user]);
+    assert_error(result, ErrorCode::MarketNotInvalidated);
+}
+
+/// Appoints a guardian for the fixture's protocol.
+fn guardian(fixture: &mut Fixture) -> Pubkey {
+    let guardian = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(set_guardian_ix(fixture.admin, guardian), &[fixture.admin])
+        .unwrap();
+    guardian
+}
+
+fn pause_protocol(fixture: &mut Fixture, guardian: Pubkey, flags: u8) {
+    fixture
+        .svm
+        .process(set_protocol_pause_ix(guardian, flags), &[guardian])
+        .unwrap();
+}
+
+#[test]
+fn pause_is_guardian_only() {
+    let mut fixture = Fixture::new();
+    let guardian = guardian(&mut fixture);
+
+    let result = fixture.svm.process(
+        set_protocol_pause_ix(fixture.admin, PAUSE_BETTING),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    let result = fixture.svm.process(
+        set_market_pause_ix(fixture.authority, fixture.market, PAUSE_BETTING),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    let result = fixture
+        .svm
+        .process(set_protocol_pause_ix(guardian, 1 << 4), &[guardian]);
+    assert_error(result, ErrorCode::InvalidPauseFlags);
+
+    pause_protocol(&mut fixture, guardian, PAUSE_BETTING | PAUSE_CLAIMS);
+    let events = fixture.svm.events::<ProtocolPauseSet>();
+    assert_eq!(
+        (events[0].guardian, events[0].flags),
+        (guardian, PAUSE_BETTING | PAUSE_CLAIMS)
+    );
+    fixture
+        .svm
+        .process(
+            set_market_pause_ix(guardian, fixture.market, PAUSE_RESOLUTION),
+            &[guardian],
+        )
+        .unwrap();
+    let events = fixture.svm.events::<MarketPauseSet>();
+    assert_eq!(
+        (events[0].market, events[0].flags),
+        (fixture.market, PAUSE_RESOLUTION)
+    );
+
+    let state: GlobalState = fixture.svm.fetch(&global_state_pda());
+    assert_eq!(state.paused, PAUSE_BETTING | PAUSE_CLAIMS);
+    assert_eq!(fixture.market().paused, PAUSE_RESOLUTION);
+}
+
+#[test]
+fn betting_pause_leaves_claims_open() {
+    let mut fixture = Fixture::new();
+    let guardian = guardian(&mut fixture);
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    pause_protocol(&mut fixture, guardian, PAUSE_BETTING);
+
+    let user = fixture.svm.funded_account(5 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, SOL, true), &[user]);
+    assert_error(result, ErrorCode::Paused);
+    let result = fixture
+        .svm
+        .process(add_liquidity_ix(user, fixture.market, SOL, SOL), &[user]);
+    assert_error(result, ErrorCode::Paused);
+
+    // New markets, resolution and claims carry on
+    another_market(&mut fixture);
+    fixture.resolve(true);
+    fixture.claim(winner);
+}
+
+#[test]
+fn claims_pause_blocks_every_payout_path() {
+    let mut fixture = Fixture::new();
+    let guardian = guardian(&mut fixture);
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    pause_protocol(&mut fixture, guardian, PAUSE_CLAIMS);
+
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(winner, fixture.market), &[winner]);
+    assert_error(result, ErrorCode::Paused);
+    let result = fixture
+        .svm
+        .process(claim_many_ix(winner, &[fixture.market]), &[winner]);
+    assert_error(result, ErrorCode::Paused);
+
+    pause_protocol(&mut fixture, guardian, 0);
+    fixture.claim(winner);
+}
+
+#[test]
+fn claims_get_a_grace_period_after_a_pause_or_freeze() {
+    let mut fixture = deadline_fixture(0);
+    let guardian = guardian(&mut fixture);
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    pause_protocol(&mut fixture, guardian, PAUSE_CLAIMS);
+
+    // The pause outlasts the deadline; nothing is forfeited meanwhile
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline + ONE_DAY);
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::Paused);
+
+    pause_protocol(&mut fixture, guardian, 0);
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::ClaimDeadlineNotReached);
+    fixture.claim(winner);
+
+    // Restoring a market frozen through its deadline reopens claims too
+    let mut fixture = deadline_fixture(0);
+    let winner = fixture.bettor(SOL, true);
+    fixture.bettor(SOL, false);
+    fixture.resolve(true);
+    let (moderator, reporter) = reported_market(&mut fixture);
+    moderate(&mut fixture, moderator, reporter, 2);
+    let deadline = fixture.market().claim_deadline;
+    fixture.svm.warp_to(deadline + ONE_DAY);
+    fixture
+        .svm
+        .process(restore_market_ix(moderator, fixture.market), &[moderator])
+        .unwrap();
+    let restored_at = fixture.svm.now();
+
+    fixture.svm.warp_to(restored_at + CLAIM_RESUME_GRACE - 1);
+    let result = fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::ClaimDeadlineNotReached);
+    fixture.svm.warp_to(restored_at + CLAIM_RESUME_GRACE);
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(winner, fixture.market), &[winner]);
+    assert_error(result, ErrorCode::ClaimDeadlinePassed);
+    fixture
+        .svm
+        .process(settle_unclaimed_ix(fixture.market), &[])
+        .unwrap();
+}
+
+#[test]
+fn market_pause_only_affects_that_market() {
+    let mut fixture = Fixture::new();
+    let guardian = guardian(&mut fixture);
+    let second = another_market(&mut fixture);
+    let user = fixture.bettor(SOL, true);
+    fixture.svm.airdrop(&user, SOL);
+    fixture
+        .svm
+        .process(place_bet_ix(user, second, SOL, true), &[user])
+        .unwrap();
+    fixture
+        .svm
+        .process(
+            set_market_pause_ix(guardian, second, PAUSE_RESOLUTION | PAUSE_CLAIMS),
+            &[guardian],
+        )
+        .unwrap();
+
+    fixture.resolve(true);
+    let result = fixture.svm.process(
+        resolve_market_ix(second, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::Paused);
+
+    // A batch claim skips the paused market
+    let before = fixture.svm.lamports(&user);
+    fixture
+        .svm
+        .process(claim_many_ix(user, &[fixture.market, second]), &[user])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&user) - before, SOL);
+}
+
+#[test]
+fn creation_pause_blocks_new_markets() {
+    let mut fixture = Fixture::new();
+    let guardian = guardian(&mut fixture);
+    pause_protocol(&mut fixture, guardian, PAUSE_CREATION);
+
+    let market = fixture.svm.signer();
+    let result = fixture.svm.process(
+        create_market_ix(
+            market,
+            fixture.authority,
+            &MarketParams::new("Will the budget pass?", fixture.end_time),
+        ),
+        &[fixture.authority, market],
+    );
+    assert_error(result, ErrorCode::Paused);
+
+    // Existing markets keep taking bets
+    fixture.bettor(SOL, true);
+}
+
+const ELIGIBILITY_PERIOD: i64 = 3_600;
+
+fn pending_fixture() -> Fixture {
+    Fixture::with(|params| params.eligibility_period = Some(ELIGIBILITY_PERIOD))
+}
+
+/// Creates a profile with `wins` perfect predictions.
+fn forecaster(fixture: &mut Fixture, wins: u32) -> Pubkey {
+    let user = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(create_user_profile_ix(user), &[user])
+        .unwrap();
+    let mut profile = fixture.profile(&user);
+    profile.wins = wins;
+    fixture.svm.store(&profile_pda(&user), &profile);
+    user
+}
+
+/// Votes with a forecaster who just meets the track record requirement.
+fn voter(fixture: &mut Fixture, approve: bool) -> Pubkey {
+    let voter = forecaster(fixture, MIN_VOTER_PREDICTIONS);
+    fixture
+        .svm
+        .process(
+            vote_eligibility_ix(voter, fixture.market, approve),
+            &[voter],
+        )
+        .unwrap();
+    voter
+}
+
+fn tally(fixture: &mut Fixture) {
+    fixture
+        .svm
+        .warp_to(svm::GENESIS_TIMESTAMP + ELIGIBILITY_PERIOD);
+    fixture
+        .svm
+        .process(tally_eligibility_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn pending_market_locks_deposit_and_blocks_trading() {
+    let mut fixture = pending_fixture();
+
+    let market = fixture.market();
+    assert!(market.pending);
+    assert_eq!(
+        market.eligibility_end,
+        svm::GENESIS_TIMESTAMP + ELIGIBILITY_PERIOD
+    );
+    assert_eq!(market.eligibility_deposit, ELIGIBILITY_DEPOSIT);
+
+    let user = fixture.svm.funded_account(2 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, SOL, true), &[user]);
+    assert_error(result, ErrorCode::MarketPending);
+    fixture.expire();
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::MarketPending);
+}
+
+#[test]
+fn eligibility_period_must_end_before_market() {
+    let mut fixture = Fixture::new();
+
+    for period in [0, ONE_DAY] {
+        let market = fixture.svm.signer();
+        let mut params = MarketParams::new("Will the budget pass?", fixture.end_time);
+        params.eligibility_period = Some(period);
+        let result = fixture.svm.process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        );
+        assert_error(result, ErrorCode::InvalidEligibilityPeriod);
+    }
+}
+
+#[test]
+fn approved_market_goes_live_and_refunds_deposit() {
+    let mut fixture = pending_fixture();
+    let voter = voter(&mut fixture, true);
+    let vote: EligibilityVote = fixture
+        .svm
+        .fetch(&eligibility_vote_pda(&fixture.market, &voter));
+    assert!(vote.approve);
+    assert_eq!(vote.reputation_weight, 30);
+
+    let result = fixture
+        .svm
+        .process(tally_eligibility_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::EligibilityVoteNotEnded);
+
+    let before = fixture.svm.lamports(&fixture.authority);
+    tally(&mut fixture);
+    let events = fixture.svm.events::<EligibilityTallied>();
+    assert!(events[0].approved);
+    assert_eq!((events[0].votes_for, events[0].votes_against), (30, 0));
+    assert_eq!(
+        fixture.svm.lamports(&fixture.authority) - before,
+        ELIGIBILITY_DEPOSIT
+    );
+
+    let market = fixture.market();
+    assert!(!market.pending && !market.resolved);
+    assert_eq!(market.eligibility_deposit, 0);
+    fixture.bettor(SOL, true);
+
+    let before = fixture.svm.lamports(&voter);
+    fixture
+        .svm
+        .process(close_eligibility_vote_ix(voter, fixture.market), &[voter])
+        .unwrap();
+    assert!(fixture.svm.lamports(&voter) > before);
+}
+
+#[test]
+fn rejected_market_is_invalidated() {
+    let mut fixture = pending_fixture();
+    voter(&mut fixture, true);
+    // A forecaster with ten perfect calls outweighs a newcomer
+    let expert = forecaster(&mut fixture, 10);
+    fixture
+        .svm
+        .process(
+            vote_eligibility_ix(expert, fixture.market, false),
+            &[expert],
+        )
+        .unwrap();
+
+    // Votes can only be closed once tallied
+    let result = fixture
+        .svm
+        .process(close_eligibility_vote_ix(expert, fixture.market), &[expert]);
+    assert_error(result, ErrorCode::MarketPending);
+
+    let before = fixture.svm.lamports(&fixture.authority);
+    tally(&mut fixture);
+    let events = fixture.svm.events::<EligibilityTallied>();
+    assert!(!events[0].approved);
+    assert_eq!((events[0].votes_for, events[0].votes_against), (30, 100));
+    assert_eq!(
+        fixture.svm.lamports(&fixture.authority) - before,
+        ELIGIBILITY_DEPOSIT
+    );
+    let market = fixture.market();
+    assert!(!market.pending && market.resolved && market.invalidated);
+
+    let result = fixture
+        .svm
+        .process(tally_eligibility_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::MarketNotPending);
+}
+
+#[test]
+fn eligibility_deposit_returns_to_its_payer() {
+    let mut fixture = Fixture::new();
+    // A vault authority with no lamports has someone else fund the deposit
+    let vault = fixture.svm.signer();
+    let payer = fixture.svm.funded_account(SOL);
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will the committee approve?", fixture.end_time);
+    params.eligibility_period = Some(ELIGIBILITY_PERIOD);
+    params.payer = Some(payer);
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, vault, &params),
+            &[vault, payer, market],
+        )
+        .unwrap();
+    assert_eq!(
+        fixture.svm.fetch::<Market>(&market).eligibility_depositor,
+        payer
+    );
+
+    fixture
+        .svm
+        .warp_to(svm::GENESIS_TIMESTAMP + ELIGIBILITY_PERIOD);
+    let result = fixture
+        .svm
+        .process(tally_eligibility_ix(market, vault), &[]);
+    assert_error(result, anchor_lang::error::ErrorCode::ConstraintAddress);
+    let before = fixture.svm.lamports(&payer);
+    fixture
+        .svm
+        .process(tally_eligibility_ix(market, payer), &[])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&payer) - before, ELIGIBILITY_DEPOSIT);
+}
+
+#[test]
+fn eligibility_votes_need_a_track_record_or_an_attestation() {
+    let mut fixture = pending_fixture();
+
+    // A fresh profile, or one that was always wrong, carries no weight
+    for (wins, losses) in [(0, 0), (2, 0), (0, MIN_VOTER_PREDICTIONS)] {
+        let user = forecaster(&mut fixture, wins);
+        let mut profile = fixture.profile(&user);
+        profile.losses = losses;
+        profile.brier_sum = losses as u64 * 10_000;
+        fixture.svm.store(&profile_pda(&user), &profile);
+        let result = fixture
+            .svm
+            .process(vote_eligibility_ix(user, fixture.market, true), &[user]);
+        assert_error(result, ErrorCode::InsufficientReputation);
+    }
+
+    // An attested human can vote without a record
+    let verifier = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(
+            set_human_verifier_ix(fixture.admin, verifier),
+            &[fixture.admin],
+        )
+        .unwrap();
+    let human = forecaster(&mut fixture, 0);
+    fixture
+        .svm
+        .process(attest_human_ix(verifier, human, 0), &[verifier])
+        .unwrap();
+    fixture
+        .svm
+        .process(
+            vote_human_eligibility_ix(human, fixture.market, true),
+            &[human],
+        )
+        .unwrap();
+    let vote: EligibilityVote = fixture
+        .svm
+        .fetch(&eligibility_vote_pda(&fixture.market, &human));
+    assert_eq!(vote.reputation_weight, HUMAN_VOTE_WEIGHT);
+}
+
+#[test]
+fn eligibility_vote_closes_at_period_end() {
+    let mut fixture = pending_fixture();
+    fixture
+        .svm
+        .warp_to(svm::GENESIS_TIMESTAMP + ELIGIBILITY_PERIOD);
+
+    let voter = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(create_user_profile_ix(voter), &[voter])
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(vote_eligibility_ix(voter, fixture.market, true), &[voter]);
+    assert_error(result, ErrorCode::EligibilityVoteClosed);
+}
+
+fn meta_market(fixture: &mut Fixture, condition: u8) -> Pubkey {
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will the bill market resolve YES?", fixture.end_time);
+    params.meta = Some((fixture.market, condition));
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    market
+}
+
+#[test]
+fn meta_market_resolves_from_parent() {
+    let mut fixture = Fixture::new();
+    let yes = meta_market(&mut fixture, META_PARENT_YES);
+    let no = meta_market(&mut fixture, META_PARENT_NO);
+    let market: Market = fixture.svm.fetch(&yes);
+    assert_eq!(market.parent_market, fixture.market);
+    assert_eq!(market.meta_condition, META_PARENT_YES);
+
+    let user = fixture.svm.funded_account(2 * SOL);
+    fixture
+        .svm
+        .process(place_bet_ix(user, yes, SOL, true), &[user])
+        .unwrap();
+
+    fixture.expire();
+    let result = fixture
+        .svm
+        .process(resolve_meta_market_ix(yes, fixture.market), &[]);
+    assert_error(result, ErrorCode::ParentNotResolved);
+    let result = fixture.svm.process(
+        resolve_market_ix(yes, fixture.authority, false),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::MustUseParentMarket);
+
+    fixture.resolve(true);
+    for market in [yes, no] {
+        fixture
+            .svm
+            .process(resolve_meta_market_ix(market, fixture.market), &[])
+            .unwrap();
+    }
+    let market: Market = fixture.svm.fetch(&yes);
+    assert!(market.resolved && market.winning_outcome);
+    let market: Market = fixture.svm.fetch(&no);
+    assert!(market.resolved && !market.winning_outcome);
+
+    let before = fixture.svm.lamports(&user);
+    fixture
+        .svm
+        .process(claim_winnings_ix(user, yes), &[user])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&user) - before, SOL);
+}
+
+#[test]
+fn meta_market_must_close_with_its_parent() {
+    let mut fixture = Fixture::new();
+
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will the bill market resolve YES?", fixture.end_time + 1);
+    params.meta = Some((fixture.market, META_PARENT_YES));
+    let result = fixture.svm.process(
+        create_market_ix(market, fixture.authority, &params),
+        &[fixture.authority, market],
+    );
+    assert_error(result, ErrorCode::InvalidEndTime);
+
+    params.end_time = fixture.end_time;
+    params.meta = Some((fixture.market, 3));
+    let result = fixture.svm.process(
+        create_market_ix(market, fixture.authority, &params),
+        &[fixture.authority, market],
+    );
+    assert_error(result, ErrorCode::InvalidMetaCondition);
+
+    // Leaving out the optional parent account
+    params.meta = Some((fixture.market, META_PARENT_YES));
+    let mut ix = create_market_ix(market, fixture.authority, &params);
+    for meta in ix.accounts.iter_mut() {
+        if meta.pubkey == fixture.market {
+            *meta = AccountMeta::new_readonly(prediction_market::ID, false);
+        }
+    }
+    let result = fixture.svm.process(ix, &[fixture.authority, market]);
+    assert_error(result, ErrorCode::ParentMarketRequired);
+
+    // Resolution only reads the recorded parent
+    let meta = meta_market(&mut fixture, META_PARENT_YES);
+    let other = another_market(&mut fixture);
+    fixture.expire();
+    let result = fixture
+        .svm
+        .process(resolve_meta_market_ix(meta, other), &[]);
+    assert_error(result, ErrorCode::InvalidParentMarket);
+}
+
+#[test]
+fn meta_market_tracks_parent_invalidation() {
+    let mut fixture = Fixture::new();
+    let invalidated = meta_market(&mut fixture, META_PARENT_INVALIDATED);
+    let yes = meta_market(&mut fixture, META_PARENT_YES);
+    let (moderator, reporter) = reported_market(&mut fixture);
+    moderate(&mut fixture, moderator, reporter, 3);
+
+    fixture
+        .svm
+        .process(resolve_meta_market_ix(invalidated, fixture.market), &[])
+        .unwrap();
+    let market: Market = fixture.svm.fetch(&invalidated);
+    assert!(market.winning_outcome && !market.invalidated);
+
+    // The parent's outcome stays readable until every meta market has it
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::ChildMarketsOpen);
+    fixture
+        .svm
+        .process(resolve_meta_market_ix(yes, fixture.market), &[])
+        .unwrap();
+    let market: Market = fixture.svm.fetch(&yes);
+    assert!(market.resolved && !market.winning_outcome && !market.invalidated);
+    let result = fixture
+        .svm
+        .process(resolve_meta_market_ix(yes, fixture.market), &[]);
+    assert_error(result, ErrorCode::AlreadyResolved);
+
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn meta_markets_hold_their_parent_open() {
+    let mut fixture = Fixture::new();
+    let meta = meta_market(&mut fixture, META_PARENT_YES);
+    let parent = fixture.market();
+    assert_eq!(parent.child_markets, 1);
+    assert_eq!(
+        fixture.svm.fetch::<Market>(&meta).parent_created_at,
+        parent.created_at
+    );
+
+    // A market recreated at the parent's address is not read
+    let mut recreated = parent.clone();
+    recreated.created_at += 1;
+    fixture.svm.store(&fixture.market, &recreated);
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(resolve_meta_market_ix(meta, fixture.market), &[]);
+    assert_error(result, ErrorCode::InvalidParentMarket);
+    let mut restored = fixture.market();
+    restored.created_at = parent.created_at;
+    fixture.svm.store(&fixture.market, &restored);
+
+    // A meta market settled by moderation only releases its parent
+    let moderator = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(set_moderator_ix(fixture.admin, moderator), &[fixture.admin])
+        .unwrap();
+    let reporter = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(report_market_ix(reporter, meta, 1, [9; 32]), &[reporter])
+        .unwrap();
+    fixture
+        .svm
+        .process(
+            moderate_report_ix(moderator, meta, reporter, 3),
+            &[moderator],
+        )
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::ChildMarketsOpen);
+    fixture
+        .svm
+        .process(resolve_meta_market_ix(meta, fixture.market), &[])
+        .unwrap();
+    let market: Market = fixture.svm.fetch(&meta);
+    assert!(market.invalidated && market.parent_created_at == 0);
+    assert_eq!(fixture.market().child_markets, 0);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn create_market_stores_metadata() {
+    let fixture = Fixture::with(|params| {
+        params.metadata = MarketMetadata {
+            category: "politica".to_string(),
+            tags: vec!["brasil".to_string(), "eleicoes".to_string()],
+            uri: "https://example.com/markets/1.json".to_string(),
+            uri_hash: [5; 32],
+            resolution_source: "TSE official results".to_string(),
+        }
+    });
+
+    let market = fixture.market();
+    assert_eq!(market.category_name(), "politica");
+    assert_eq!(market.tags, ["brasil", "eleicoes"]);
+    assert_eq!(market.metadata_uri, "https://example.com/markets/1.json");
+    assert_eq!(market.metadata_hash, [5; 32]);
+    assert_eq!(market.resolution_source, "TSE official results");
+
+    // Clients filter by category with a memcmp at a fixed offset
+    let data = &fixture.svm.account(&fixture.market).unwrap().data;
+    assert_eq!(
+        &data[MARKET_CATEGORY_OFFSET..MARKET_CATEGORY_OFFSET + 9],
+        b"politica\0"
+    );
+}
+
+#[test]
+fn create_market_rejects_oversized_metadata() {
+    let mut fixture = Fixture::new();
+    let cases = [
+        (
+            MarketMetadata {
+                category: "c".repeat(33),
+                ..Default::default()
+            },
+            ErrorCode::CategoryTooLong,
+        ),
+        (
+            MarketMetadata {
+                tags: vec!["tag".to_string(); 6],
+                ..Default::default()
+            },
+            ErrorCode::TooManyTags,
+        ),
+        (
+            MarketMetadata {
+                tags: vec!["t".repeat(21)],
+                ..Default::default()
+            },
+            ErrorCode::TagTooLong,
+        ),
+        (
+            MarketMetadata {
+                tags: vec!["🗳".repeat(11)],
+                ..Default::default()
+            },
+            ErrorCode::TagTooManyBytes,
+        ),
+        (
+            MarketMetadata {
+                uri: "u".repeat(201),
+                ..Default::default()
+            },
+            ErrorCode::MetadataUriTooLong,
+        ),
+        (
+            MarketMetadata {
+                resolution_source: "s".repeat(101),
+                ..Default::default()
+            },
+            ErrorCode::ResolutionSourceTooLong,
+        ),
+        (
+            MarketMetadata {
+                resolution_source: "🗳".repeat(51),
+                ..Default::default()
+            },
+            ErrorCode::ResolutionSourceTooManyBytes,
+        ),
+    ];
+
+    for (metadata, error) in cases {
+        let market = fixture.svm.signer();
+        let mut params = MarketParams::new("Will the budget pass?", fixture.end_time);
+        params.metadata = metadata;
+        let result = fixture.svm.process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        );
+        assert_error(result, error);
+    }
+}
+
+/// Accounts serialized by the program before versioning (as of b6bd4aa):
+/// the protocol's global state, a market and a 1 SOL YES bet on it. The
+/// protocol authority, market authority and bettor are swapped for keypairs
+/// the harness can sign with. Returns the market and the bettor.
+fn legacy_accounts(svm: &mut Svm) -> (Pubkey, Pubkey) {
+    let mut global_state = include_bytes!("fixtures/global_state_v0.bin").to_vec();
+    let mut market_data = include_bytes!("fixtures/market_v0.bin").to_vec();
+    let mut bet = include_bytes!("fixtures/bet_v0.bin").to_vec();
+    for data in [&mut global_state, &mut market_data, &mut bet] {
+        data[8..40].copy_from_slice(svm.signer().as_ref());
+    }
+    let user = Pubkey::try_from(&bet[8..40]).unwrap();
+    let market = Pubkey::try_from(&bet[40..72]).unwrap();
+    svm.set_program_account(bet_pda(&user, &market), bet);
+    svm.set_program_account(market, market_data);
+    svm.set_program_account(global_state_pda(), global_state);
+    (market, user)
+}
+
+#[test]
+fn migration_upgrades_legacy_accounts() {
+    let mut svm = Svm::new();
+    let (market, user) = legacy_accounts(&mut svm);
+    let payer = svm.funded_account(SOL);
+
+    // Legacy accounts keep the size the old program gave them, and the
+    // bet is too short for the current layout
+    let data = &svm.account(&bet_pda(&user, &market)).unwrap().data;
+    assert_eq!(data.len(), LEGACY_BET_SPACE);
+    assert!(Bet::try_deserialize(&mut data.as_slice()).is_err());
+    assert_eq!(
+        svm.account(&market).unwrap().data.len(),
+        LEGACY_MARKET_SPACE
+    );
+    assert_eq!(
+        svm.account(&global_state_pda()).unwrap().data.len(),
+        LEGACY_GLOBAL_STATE_SPACE
+    );
+
+    svm.process(migrate_global_state_ix(payer), &[payer])
+        .unwrap();
+    svm.process(
+        migrate_market_ix(payer, market, &[bet_pda(&user, &market)]),
+        &[payer],
+    )
+    .unwrap();
+
+    let state: GlobalState = svm.fetch(&global_state_pda());
+    assert_eq!(state.version, GLOBAL_STATE_VERSION);
+    assert_eq!((state.total_markets, state.total_volume), (1, SOL));
+    assert_eq!(state.guardian, Pubkey::default());
+    assert_eq!(
+        svm.account(&global_state_pda()).unwrap().data.len(),
+        8 + GlobalState::INIT_SPACE
+    );
+    let migrated: Market = svm.fetch(&market);
+    assert_eq!(migrated.version, MARKET_VERSION);
+    assert_eq!(migrated.question, "¿Será aprobada la reforma?");
+    assert_eq!(migrated.description, "Resolves from public records");
+    assert_eq!(migrated.category_name(), "");
+    assert_eq!(migrated.end_time, svm::GENESIS_TIMESTAMP + ONE_DAY);
+    assert!(!migrated.resolved && !migrated.oracle_enabled);
+    assert_eq!((migrated.yes_amount, migrated.yes_bets), (SOL, 1));
+    assert_eq!(
+        svm.account(&market).unwrap().data.len(),
+        8 + Market::INIT_SPACE
+    );
+    let bet: Bet = svm.fetch(&bet_pda(&user, &market));
+    assert_eq!(bet.version, BET_VERSION);
+    assert_eq!((bet.user, bet.amount, bet.outcome), (user, SOL, true));
+
+    // Migrated accounts work as before once the config exists, and
+    // migrating again is a no-op
+    svm.airdrop(&state.authority, SOL);
+    svm.process(initialize_config_ix(state.authority), &[state.authority])
+        .unwrap();
+    let bettor = svm.funded_account(2 * SOL);
+    svm.process(place_bet_ix(bettor, market, SOL, false), &[bettor])
+        .unwrap();
+    svm.process(
+        migrate_market_ix(payer, market, &[bet_pda(&user, &market)]),
+        &[payer],
+    )
+    .unwrap();
+    let migrated: Market = svm.fetch(&market);
+    assert_eq!(
+        (migrated.no_amount, migrated.yes_bets, migrated.version),
+        (SOL, 1, MARKET_VERSION)
+    );
+}
+
+#[test]
+fn legacy_bettors_claim_without_a_profile() {
+    let mut svm = Svm::new();
+    let (market, user) = legacy_accounts(&mut svm);
+    let payer = svm.funded_account(SOL);
+    svm.process(migrate_global_state_ix(payer), &[payer])
+        .unwrap();
+    svm.process(
+        migrate_market_ix(payer, market, &[bet_pda(&user, &market)]),
+        &[payer],
+    )
+    .unwrap();
+    let state: GlobalState = svm.fetch(&global_state_pda());
+    svm.airdrop(&state.authority, SOL);
+    svm.process(initialize_config_ix(state.authority), &[state.authority])
+        .unwrap();
+
+    // The fixture only holds rent; add the staked pool
+    svm.airdrop(&market, SOL);
+    let migrated: Market = svm.fetch(&market);
+    svm.warp_to(migrated.end_time);
+    svm.process(
+        resolve_market_ix(market, migrated.authority, true),
+        &[migrated.authority],
+    )
+    .unwrap();
+    assert!(svm.account(&profile_pda(&user)).is_none());
+
+    // The bet predates profiles; claiming opens one for it. Being alone
+    // in the pool, it is not scored as a prediction.
+    svm.airdrop(&user, SOL);
+    svm.process(claim_winnings_ix(user, market), &[user])
+        .unwrap();
+    let profile: UserProfile = svm.fetch(&profile_pda(&user));
+    assert_eq!((profile.user, profile.wins), (user, 0));
+    assert!(svm.fetch::<Bet>(&bet_pda(&user, &market)).recorded);
+    svm.process(close_bet_ix(user, market), &[user]).unwrap();
+}
+
+#[test]
+fn migrated_markets_only_finalize_past_the_claim_deadline() {
+    let mut svm = Svm::new();
+    let (market, user) = legacy_accounts(&mut svm);
+    let payer = svm.funded_account(SOL);
+    svm.process(migrate_global_state_ix(payer), &[payer])
+        .unwrap();
+    let state: GlobalState = svm.fetch(&global_state_pda());
+    svm.airdrop(&state.authority, SOL);
+    svm.process(initialize_config_ix(state.authority), &[state.authority])
+        .unwrap();
+
+    // The fixtures leave out the treasury the legacy program created
+    let mut treasury = Vec::new();
+    Treasury { total_collected: 0 }
+        .try_serialize(&mut treasury)
+        .unwrap();
+    svm.set_program_account(treasury_pda(), treasury);
+
+    // Migrating the market without its bets leaves the counts at zero
+    svm.process(migrate_market_ix(payer, market, &[]), &[payer])
+        .unwrap();
+    let migrated: Market = svm.fetch(&market);
+    assert!(migrated.legacy_counts);
+    assert_eq!((migrated.yes_bets, migrated.no_bets), (0, 0));
+    assert_eq!(
+        migrated.claim_deadline,
+        migrated.end_time + LEGACY_CLAIM_PERIOD
+    );
+
+    svm.airdrop(&market, SOL);
+    svm.warp_to(migrated.end_time);
+    svm.process(
+        resolve_market_ix(market, migrated.authority, true),
+        &[migrated.authority],
+    )
+    .unwrap();
+
+    // Zero claims of zero counted bets does not settle the market
+    let result = svm.process(
+        finalize_market_ix(market, migrated.authority),
+        &[migrated.authority],
+    );
+    assert_error(result, ErrorCode::MarketNotSettled);
+
+    // The bet can still be migrated and claimed before the deadline
+    svm.process(
+        migrate_market_ix(payer, market, &[bet_pda(&user, &market)]),
+        &[payer],
+    )
+    .unwrap();
+    svm.airdrop(&user, SOL);
+    let before = svm.lamports(&user);
+    svm.process(claim_winnings_ix(user, market), &[user])
+        .unwrap();
+    assert!(svm.lamports(&user) > before);
+
+    let result = svm.process(
+        finalize_market_ix(market, migrated.authority),
+        &[migrated.authority],
+    );
+    assert_error(result, ErrorCode::MarketNotSettled);
+
+    svm.warp_to(migrated.claim_deadline);
+    svm.process(settle_unclaimed_ix(market), &[]).unwrap();
+    svm.process(
+        finalize_market_ix(market, migrated.authority),
+        &[migrated.authority],
+    )
+    .unwrap();
+}
+
+#[test]
+fn migration_rejects_bets_of_other_markets() {
+    let mut fixture = Fixture::new();
+    let (legacy, user) = legacy_accounts(&mut fixture.svm);
+    let payer = fixture.svm.funded_account(SOL);
+
+    let result = fixture.svm.process(
+        migrate_market_ix(payer, fixture.market, &[bet_pda(&user, &legacy)]),
+        &[payer],
+    );
+    assert_error(result, ErrorCode::InvalidMigrationAccount);
+}
+
+/// Queues `params`, waits out the timelock and applies them. Returns a
+/// fresh market, since the fixture's market ends within the timelock.
+fn configure(fixture: &mut Fixture, params: ConfigParams) -> Pubkey {
+    let timelock = fixture.svm.fetch::<Config>(&config_pda()).params.timelock;
+    fixture
+        .svm
+        .process(update_config_ix(fixture.admin, params), &[fixture.admin])
+        .unwrap();
+    fixture.svm.warp_to(fixture.svm.now() + timelock);
+    fixture.svm.process(apply_config_update_ix(), &[]).unwrap();
+
+    let market = fixture.svm.signer();
+    let params = MarketParams::new("Will the budget pass?", fixture.svm.now() + ONE_DAY);
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    market
+}
+
+#[test]
+fn config_updates_wait_for_the_timelock() {
+    let mut fixture = Fixture::new();
+    let defaults = ConfigParams::default();
+    let params = ConfigParams {
+        min_bet: 2 * MIN_BET,
+        ..defaults.clone()
+    };
+
+    let result = fixture.svm.process(
+        update_config_ix(fixture.authority, params.clone()),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    let result = fixture.svm.process(
+        update_config_ix(
+            fixture.admin,
+            ConfigParams {
+                min_bet: 0,
+                ..defaults.clone()
+            },
+        ),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::InvalidConfig);
+
+    fixture
+        .svm
+        .process(
+            update_config_ix(fixture.admin, params.clone()),
+            &[fixture.admin],
+        )
+        .unwrap();
+    let events = fixture.svm.events::<ConfigUpdateQueued>();
+    assert_eq!((&events[0].old, &events[0].new), (&defaults, &params));
+    assert_eq!(events[0].eta, fixture.svm.now() + defaults.timelock);
+
+    let result = fixture.svm.process(apply_config_update_ix(), &[]);
+    assert_error(result, ErrorCode::TimelockNotExpired);
+    fixture.svm.warp_to(events[0].eta);
+    fixture.svm.process(apply_config_update_ix(), &[]).unwrap();
+    let events = fixture.svm.events::<ConfigUpdated>();
+    assert_eq!((&events[0].old, &events[0].new), (&defaults, &params));
+    let result = fixture.svm.process(apply_config_update_ix(), &[]);
+    assert_error(result, ErrorCode::NoConfigUpdateQueued);
+
+    // Cancelled updates never apply
+    fixture
+        .svm
+        .process(update_config_ix(fixture.admin, defaults), &[fixture.admin])
+        .unwrap();
+    fixture
+        .svm
+        .process(cancel_config_update_ix(fixture.admin), &[fixture.admin])
+        .unwrap();
+    let result = fixture.svm.process(apply_config_update_ix(), &[]);
+    assert_error(result, ErrorCode::NoConfigUpdateQueued);
+    let config: Config = fixture.svm.fetch(&config_pda());
+    assert_eq!(config.params, params);
+}
+
+#[test]
+fn config_limits_apply_to_markets_and_bets() {
+    let mut fixture = Fixture::new();
+    let market = configure(
+        &mut fixture,
+        ConfigParams {
+            min_bet: 2 * MIN_BET,
+            max_question_chars: 30,
+            allowed_comparisons: 0b001,
+            ..ConfigParams::default()
+        },
+    );
+
+    let user = fixture.svm.funded_account(SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, market, MIN_BET, true), &[user]);
+    assert_error(result, ErrorCode::BetTooSmall);
+    fixture
+        .svm
+        .process(place_bet_ix(user, market, 2 * MIN_BET, true), &[user])
+        .unwrap();
+
+    let end_time = fixture.svm.now() + ONE_DAY;
+    let mut params = MarketParams::new(&"?".repeat(31), end_time);
+    let market = fixture.svm.signer();
+    let result = fixture.svm.process(
+        create_market_ix(market, fixture.authority, &params),
+        &[fixture.authority, market],
+    );
+    assert_error(result, ErrorCode::QuestionTooLong);
+
+    params.question = "Will BTC close below 50k?".to_string();
+    params.oracle = Some(OracleParams {
+        feed_id: Some(FEED_ID),
+        threshold: Some(100),
+        comparison: Some(1),
+    });
+    let result = fixture.svm.process(
+        create_market_ix(market, fixture.authority, &params),
+        &[fixture.authority, market],
+    );
+    assert_error(result, ErrorCode::InvalidOracleComparison);
+}
+
+#[test]
+fn config_authority_transfer_takes_two_steps() {
+    let mut fixture = Fixture::new();
+    let successor = fixture.svm.funded_account(SOL);
+
+    fixture
+        .svm
+        .process(
+            transfer_config_authority_ix(fixture.admin, successor),
+            &[fixture.admin],
+        )
+        .unwrap();
+    let result = fixture.svm.process(
+        update_config_ix(successor, ConfigParams::default()),
+        &[successor],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    let result = fixture.svm.process(
+        accept_config_authority_ix(fixture.authority),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+
+    fixture
+        .svm
+        .process(accept_config_authority_ix(successor), &[successor])
+        .unwrap();
+    let events = fixture.svm.events::<ConfigAuthorityTransferred>();
+    assert_eq!((events[0].old, events[0].new), (fixture.admin, successor));
+    let config: Config = fixture.svm.fetch(&config_pda());
+    assert_eq!(
+        (config.authority, config.pending_authority),
+        (successor, Pubkey::default())
+    );
+    let result = fixture.svm.process(
+        update_config_ix(fixture.admin, ConfigParams::default()),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+}
+
+const VOTING_PERIOD: i64 = 3 * ONE_DAY;
+
+/// Governance over a fresh mint needing 20% of deposits to vote and a
+/// simple majority to pass. Returns the mint.
+fn governance(fixture: &mut Fixture) -> Pubkey {
+    let mint = fixture.svm.create_mint(6);
+    fixture
+        .svm
+        .process(
+            initialize_governance_ix(fixture.admin, mint, 2_000, 5_000, VOTING_PERIOD),
+            &[fixture.admin],
+        )
+        .unwrap();
+    mint
+}
+
+/// A voter who deposited `amount` tokens; returns it and its token account
+fn token_holder(fixture: &mut Fixture, mint: Pubkey, amount: u64) -> (Pubkey, Pubkey) {
+    let voter = fixture.svm.funded_account(SOL);
+    let tokens = fixture.svm.create_token_account(mint, voter, amount);
+    fixture
+        .svm
+        .process(
+            deposit_governance_tokens_ix(voter, tokens, amount),
+            &[voter],
+        )
+        .unwrap();
+    (voter, tokens)
+}
+
+fn propose(fixture: &mut Fixture, proposer: Pubkey, action: ProposalAction) -> u64 {
+    let id = fixture
+        .svm
+        .fetch::<Governance>(&governance_pda())
+        .proposal_count;
+    fixture
+        .svm
+        .process(create_proposal_ix(proposer, id, action), &[proposer])
+        .unwrap();
+    id
+}
+
+fn vote(fixture: &mut Fixture, voter: Pubkey, id: u64, approve: bool) {
+    fixture
+        .svm
+        .process(cast_vote_ix(voter, id, approve), &[voter])
+        .unwrap();
+}
+
+#[test]
+fn governance_proposal_pauses_protocol() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, _) = token_holder(&mut fixture, mint, 600);
+    let (bob, _) = token_holder(&mut fixture, mint, 300);
+    let (carol, _) = token_holder(&mut fixture, mint, 100);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+
+    let result = fixture.svm.process(
+        create_proposal_ix(alice, 0, ProposalAction::SetProtocolPause(1 << 4)),
+        &[alice],
+    );
+    assert_error(result, ErrorCode::InvalidPauseFlags);
+    let id = propose(
+        &mut fixture,
+        alice,
+        ProposalAction::SetProtocolPause(PAUSE_BETTING),
+    );
+    vote(&mut fixture, alice, id, true);
+    vote(&mut fixture, bob, id, false);
+    let result = fixture.svm.process(cast_vote_ix(bob, id, true), &[bob]);
+    assert!(result.is_err());
+
+    let result = fixture.svm.process(execute_proposal_ix(id, None), &[]);
+    assert_error(result, ErrorCode::ProposalVotingOpen);
+    fixture.svm.warp_to(fixture.svm.now() + VOTING_PERIOD);
+    let result = fixture
+        .svm
+        .process(cast_vote_ix(carol, id, false), &[carol]);
+    assert_error(result, ErrorCode::ProposalVotingClosed);
+
+    fixture
+        .svm
+        .process(execute_proposal_ix(id, None), &[])
+        .unwrap();
+    let events = fixture.svm.events::<ProposalExecuted>();
+    assert_eq!((events[0].proposal, events[0].id), (proposal_pda(id), id));
+    let state: GlobalState = fixture.svm.fetch(&global_state_pda());
+    assert_eq!(state.paused, PAUSE_BETTING);
+    let result = fixture.svm.process(execute_proposal_ix(id, None), &[]);
+    assert_error(result, ErrorCode::ProposalAlreadyExecuted);
+}
+
+#[test]
+fn proposal_needs_quorum_and_threshold() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, _) = token_holder(&mut fixture, mint, 100);
+    let (bob, _) = token_holder(&mut fixture, mint, 100);
+    token_holder(&mut fixture, mint, 800);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+
+    // 10% of deposits voting misses the 20% quorum
+    let low_turnout = propose(&mut fixture, alice, ProposalAction::SetProtocolPause(0));
+    vote(&mut fixture, alice, low_turnout, true);
+    // A tie is not a majority
+    let tie = propose(&mut fixture, alice, ProposalAction::SetProtocolPause(0));
+    vote(&mut fixture, alice, tie, true);
+    vote(&mut fixture, bob, tie, false);
+
+    fixture.svm.warp_to(fixture.svm.now() + VOTING_PERIOD);
+    for id in [low_turnout, tie] {
+        let result = fixture.svm.process(execute_proposal_ix(id, None), &[]);
+        assert_error(result, ErrorCode::ProposalNotPassed);
+    }
+}
+
+#[test]
+fn votes_count_tokens_deposited_before_the_proposal() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, alice_tokens) = token_holder(&mut fixture, mint, 500);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+    let id = propose(&mut fixture, alice, ProposalAction::SetProtocolPause(0));
+
+    let (latecomer, _) = token_holder(&mut fixture, mint, 1_000);
+    let result = fixture
+        .svm
+        .process(cast_vote_ix(latecomer, id, false), &[latecomer]);
+    assert_error(result, ErrorCode::NoVotingWeight);
+
+    // Votes lock the tokens behind them until voting ends
+    vote(&mut fixture, alice, id, true);
+    let result = fixture.svm.process(
+        withdraw_governance_tokens_ix(alice, alice_tokens, 500),
+        &[alice],
+    );
+    assert_error(result, ErrorCode::GovernanceTokensLocked);
+    fixture.svm.warp_to(fixture.svm.now() + VOTING_PERIOD);
+    fixture
+        .svm
+        .process(
+            withdraw_governance_tokens_ix(alice, alice_tokens, 500),
+            &[alice],
+        )
+        .unwrap();
+    assert_eq!(fixture.svm.token_balance(&alice_tokens), 500);
+    let record: VoterRecord = fixture.svm.fetch(&voter_pda(&alice));
+    assert_eq!(record.amount, 0);
+    let governance: Governance = fixture.svm.fetch(&governance_pda());
+    assert_eq!(governance.total_deposited, 1_000);
+    assert_eq!(fixture.svm.token_balance(&governance_vault_pda()), 1_000);
+}
+
+#[test]
+fn governance_updates_fees_and_oracle_feeds() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, _) = token_holder(&mut fixture, mint, 1_000);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+
+    let params = ConfigParams {
+        lp_fee_bps: 100,
+        ..ConfigParams::default()
+    };
+    let fee_change = propose(
+        &mut fixture,
+        alice,
+        ProposalAction::UpdateConfig(params.clone()),
+    );
+    let allow = propose(
+        &mut fixture,
+        alice,
+        ProposalAction::AllowOracleFeed(FEED_ID),
+    );
+    vote(&mut fixture, alice, fee_change, true);
+    vote(&mut fixture, alice, allow, true);
+    fixture.svm.warp_to(fixture.svm.now() + VOTING_PERIOD);
+    fixture
+        .svm
+        .process(execute_proposal_ix(allow, None), &[])
+        .unwrap();
+
+    // Config changes still go through the config timelock
+    fixture
+        .svm
+        .process(execute_proposal_ix(fee_change, None), &[])
+        .unwrap();
+    let result = fixture.svm.process(apply_config_update_ix(), &[]);
+    assert_error(result, ErrorCode::TimelockNotExpired);
+    fixture
+        .svm
+        .warp_to(fixture.svm.now() + ConfigParams::default().timelock);
+    fixture.svm.process(apply_config_update_ix(), &[]).unwrap();
+    let config: Config = fixture.svm.fetch(&config_pda());
+    assert_eq!(config.params, params);
+    assert_eq!(config.oracle_feeds, [FEED_ID]);
+
+    let end_time = fixture.svm.now() + ONE_DAY;
+    let mut oracle = OracleParams {
+        feed_id: Some([8; 32]),
+        threshold: Some(100),
+        comparison: Some(0),
+    };
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will BTC close above 100k?", end_time);
+    params.oracle = Some(oracle.clone());
+    let result = fixture.svm.process(
+        create_market_ix(market, fixture.authority, &params),
+        &[fixture.authority, market],
+    );
+    assert_error(result, ErrorCode::OracleFeedNotAllowed);
+    oracle.feed_id = Some(FEED_ID);
+    params.oracle = Some(oracle);
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+}
+
+#[test]
+fn governance_rejects_invalid_config() {
+    let mut fixture = Fixture::new();
+    let mint = fixture.svm.create_mint(6);
+    for (quorum_bps, threshold_bps, voting_period) in [
+        (10_001, 5_000, VOTING_PERIOD),
+        (2_000, 4_999, VOTING_PERIOD),
+        (2_000, 10_001, VOTING_PERIOD),
+        (2_000, 5_000, 0),
+    ] {
+        let result = fixture.svm.process(
+            initialize_governance_ix(
+                fixture.admin,
+                mint,
+                quorum_bps,
+                threshold_bps,
+                voting_period,
+            ),
+            &[fixture.admin],
+        );
+        assert_error(result, ErrorCode::InvalidGovernanceConfig);
+    }
+}
+
+#[test]
+fn governance_token_amounts_are_checked() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, alice_tokens) = token_holder(&mut fixture, mint, 500);
+
+    let result = fixture.svm.process(
+        deposit_governance_tokens_ix(alice, alice_tokens, 0),
+        &[alice],
+    );
+    assert_error(result, ErrorCode::InvalidTokenAmount);
+    let result = fixture.svm.process(
+        withdraw_governance_tokens_ix(alice, alice_tokens, 0),
+        &[alice],
+    );
+    assert_error(result, ErrorCode::InvalidTokenAmount);
+    let result = fixture.svm.process(
+        withdraw_governance_tokens_ix(alice, alice_tokens, 501),
+        &[alice],
+    );
+    assert_error(result, ErrorCode::InsufficientGovernanceTokens);
+}
+
+#[test]
+fn governance_caps_the_oracle_allow_list() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, _) = token_holder(&mut fixture, mint, 1_000);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+
+    let proposals: Vec<u64> = (0..=MAX_ORACLE_FEEDS as u8)
+        .map(|feed| {
+            let id = propose(
+                &mut fixture,
+                alice,
+                ProposalAction::AllowOracleFeed([feed; 32]),
+            );
+            vote(&mut fixture, alice, id, true);
+            id
+        })
+        .collect();
+    fixture.svm.warp_to(fixture.svm.now() + VOTING_PERIOD);
+    let (last, allowed) = proposals.split_last().unwrap();
+    for &id in allowed {
+        fixture
+            .svm
+            .process(execute_proposal_ix(id, None), &[])
+            .unwrap();
+    }
+    let result = fixture.svm.process(execute_proposal_ix(*last, None), &[]);
+    assert_error(result, ErrorCode::TooManyOracleFeeds);
+    let config: Config = fixture.svm.fetch(&config_pda());
+    assert_eq!(config.oracle_feeds.len(), MAX_ORACLE_FEEDS);
+}
+
+#[test]
+fn multisig_vault_can_hold_market_and_protocol_authority() {
+    let mut fixture = Fixture::new();
+    // Stands in for the vault PDA of an external multisig program, which
+    // holds no lamports and signs through that program's CPI
+    let vault = fixture.svm.signer();
+
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will the committee approve?", fixture.end_time);
+    params.payer = Some(fixture.authority);
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, vault, &params),
+            &[vault, fixture.authority, market],
+        )
+        .unwrap();
+    fixture.expire();
+    let result = fixture.svm.process(
+        resolve_market_ix(market, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(resolve_market_ix(market, vault, true), &[vault])
+        .unwrap();
+    assert!(fixture.svm.fetch::<Market>(&market).resolved);
+
+    // Protocol admin moves to the vault in two steps
+    let admin = fixture.admin;
+    fixture
+        .svm
+        .process(transfer_authority_ix(admin, vault), &[admin])
+        .unwrap();
+    let result = fixture.svm.process(accept_authority_ix(admin), &[admin]);
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(accept_authority_ix(vault), &[vault])
+        .unwrap();
+    let events = fixture.svm.events::<ProtocolAuthorityTransferred>();
+    assert_eq!((events[0].old, events[0].new), (admin, vault));
+    let state: GlobalState = fixture.svm.fetch(&global_state_pda());
+    assert_eq!(state.pending_authority, Pubkey::default());
+
+    assert!(fixture
+        .svm
+        .process(set_guardian_ix(admin, admin), &[admin])
+        .is_err());
+    fixture
+        .svm
+        .process(set_guardian_ix(vault, admin), &[vault])
+        .unwrap();
+}
+
+#[test]
+fn set_resolvers_validates_the_set() {
+    let mut fixture = Fixture::new();
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let authority = fixture.authority;
+
+    for (resolvers, threshold) in [
+        (vec![], 0),
+        (vec![a, b], 0),
+        (vec![a, b], 3),
+        (vec![a, a], 2),
+        (vec![a; 11], 1),
+    ] {
+        let result = fixture.svm.process(
+            set_resolvers_ix(fixture.market, authority, resolvers, threshold),
+            &[authority],
+        );
+        assert_error(result, ErrorCode::InvalidResolverSet);
+    }
+    let outsider = fixture.svm.funded_account(SOL);
+    let result = fixture.svm.process(
+        set_resolvers_ix(fixture.market, outsider, vec![a, b], 2),
+        &[outsider],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+
+    let oracle = Fixture::with_oracle(Some(OracleParams {
+        feed_id: Some(FEED_ID),
+        threshold: Some(100),
+        comparison: Some(0),
+    }));
+    let mut oracle_svm = oracle.svm;
+    let result = oracle_svm.process(
+        set_resolvers_ix(oracle.market, oracle.authority, vec![a], 1),
+        &[oracle.authority],
+    );
+    assert_error(result, ErrorCode::MustUseOracle);
+}
+
+#[test]
+fn resolver_set_resolves_on_threshold_of_matching_approvals() {
+    let mut fixture = Fixture::new();
+    let resolvers = [
+        fixture.svm.signer(),
+        fixture.svm.signer(),
+        fixture.svm.signer(),
+    ];
+    fixture
+        .svm
+        .process(
+            set_resolvers_ix(fixture.market, fixture.authority, resolvers.to_vec(), 2),
+            &[fixture.authority],
+        )
+        .unwrap();
+    assert_eq!(fixture.market().resolver_threshold, 2);
+    let approve = |fixture: &mut Fixture, resolver: Pubkey, outcome: bool| {
+        fixture.svm.process(
+            approve_resolution_ix(fixture.market, resolver, outcome),
+            &[resolver],
+        )
+    };
+
+    let result = approve(&mut fixture, resolvers[0], true);
+    assert_error(result, ErrorCode::MarketNotExpired);
+    fixture.expire();
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::MustUseResolvers);
+    let outsider = fixture.svm.signer();
+    let result = approve(&mut fixture, outsider, true);
+    assert_error(result, ErrorCode::NotAResolver);
+
+    // Split approvals don't resolve; a resolver changing sides does
+    approve(&mut fixture, resolvers[0], true).unwrap();
+    approve(&mut fixture, resolvers[1], false).unwrap();
+    let set: ResolverSet = fixture.svm.fetch(&resolver_set_pda(&fixture.market));
+    assert_eq!((set.yes_approvals, set.no_approvals), (0b001, 0b010));
+    assert!(!fixture.market().resolved);
+    approve(&mut fixture, resolvers[1], true).unwrap();
+
+    let events = fixture.svm.events::<ResolutionApproved>();
+    assert_eq!(events[0].resolver, resolvers[1]);
+    assert_eq!(events[0].approvals, 2);
+    let market = fixture.market();
+    assert!(market.resolved);
+    assert!(market.winning_outcome);
+    let set: ResolverSet = fixture.svm.fetch(&resolver_set_pda(&fixture.market));
+    assert_eq!((set.yes_approvals, set.no_approvals), (0b011, 0));
+    let result = approve(&mut fixture, resolvers[2], false);
+    assert_error(result, ErrorCode::AlreadyResolved);
+}
+
+#[test]
+fn market_authority_transfers_in_two_steps() {
+    let mut fixture = Fixture::new();
+    let (market, authority) = (fixture.market, fixture.authority);
+    let new_authority = fixture.svm.funded_account(SOL);
+    let delegate = fixture.svm.signer();
+
+    let result = fixture.svm.process(
+        propose_authority_transfer_ix(market, new_authority, new_authority),
+        &[new_authority],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(
+            delegate_resolver_ix(market, authority, delegate),
+            &[authority],
+        )
+        .unwrap();
+    fixture
+        .svm
+        .process(
+            propose_authority_transfer_ix(market, authority, new_authority),
+            &[authority],
+        )
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(accept_authority_transfer_ix(market, delegate), &[delegate]);
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(
+            accept_authority_transfer_ix(market, new_authority),
+            &[new_authority],
+        )
+        .unwrap();
+
+    let events = fixture.svm.events::<MarketAuthorityTransferred>();
+    assert_eq!(
+        (events[0].market, events[0].old, events[0].new),
+        (market, authority, new_authority)
+    );
+    let state = fixture.market();
+    assert_eq!(state.authority, new_authority);
+    assert_eq!(state.pending_authority, Pubkey::default());
+    assert_eq!(state.resolver, Pubkey::default());
+
+    // Only the new authority can resolve; the old delegate went with the
+    // old authority
+    fixture.expire();
+    for signer in [authority, delegate] {
+        let result = fixture
+            .svm
+            .process(resolve_market_ix(market, signer, true), &[signer]);
+        assert_error(result, ErrorCode::Unauthorized);
+    }
+    fixture
+        .svm
+        .process(
+            resolve_market_ix(market, new_authority, true),
+            &[new_authority],
+        )
+        .unwrap();
+}
+
+#[test]
+fn resolver_delegate_resolves_for_the_authority() {
+    let mut fixture = Fixture::new();
+    let (market, authority) = (fixture.market, fixture.authority);
+    let delegate = fixture.svm.signer();
+
+    let result = fixture.svm.process(
+        delegate_resolver_ix(market, delegate, delegate),
+        &[delegate],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(
+            delegate_resolver_ix(market, authority, delegate),
+            &[authority],
+        )
+        .unwrap();
+    assert_eq!(fixture.market().resolver, delegate);
+
+    fixture.expire();
+    fixture
+        .svm
+        .process(resolve_market_ix(market, delegate, false), &[delegate])
+        .unwrap();
+    let state = fixture.market();
+    assert!(state.resolved);
+    assert!(!state.winning_outcome);
+    assert_eq!(state.authority, authority);
+}
+
+#[test]
+fn governance_resolves_markets_abandoned_past_the_timeout() {
+    let mut fixture = Fixture::new();
+    let mint = governance(&mut fixture);
+    let (alice, _) = token_holder(&mut fixture, mint, 1_000);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+    let yes = fixture.bettor(SOL, true);
+    let other = another_market(&mut fixture);
+
+    let action = ProposalAction::ResolveMarket(fixture.market, true);
+    let id = propose(&mut fixture, alice, action);
+    vote(&mut fixture, alice, id, true);
+    fixture.svm.warp_to(fixture.svm.now() + VOTING_PERIOD);
+
+    let result = fixture.svm.process(execute_proposal_ix(id, None), &[]);
+    assert_error(result, ErrorCode::InvalidProposalMarket);
+    let result = fixture
+        .svm
+        .process(execute_proposal_ix(id, Some(other)), &[]);
+    assert_error(result, ErrorCode::InvalidProposalMarket);
+    let result = fixture
+        .svm
+        .process(execute_proposal_ix(id, Some(fixture.market)), &[]);
+    assert_error(result, ErrorCode::ResolutionTimeoutNotReached);
+
+    let (moderator, reporter) = reported_market(&mut fixture);
+    moderate(&mut fixture, moderator, reporter, 2);
+    fixture
+        .svm
+        .warp_to(fixture.end_time + GOVERNANCE_RESOLUTION_TIMEOUT);
+    // A frozen market waits for moderation like any other resolution
+    let result = fixture
+        .svm
+        .process(execute_proposal_ix(id, Some(fixture.market)), &[]);
+    assert_error(result, ErrorCode::MarketFrozen);
+    fixture
+        .svm
+        .process(restore_market_ix(moderator, fixture.market), &[moderator])
+        .unwrap();
+    fixture
+        .svm
+        .process(execute_proposal_ix(id, Some(fixture.market)), &[])
+        .unwrap();
+    let market = fixture.market();
+    assert!(market.resolved);
+    assert!(market.winning_outcome);
+    assert_eq!(market.fallback_reason, FALLBACK_AUTHORITY_TIMEOUT);
+    fixture.claim(yes);
+}
+
+#[test]
+fn oracle_fallback_invalidates_after_the_grace_period() {
+    let mut fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+    let yes = fixture.bettor(SOL, true);
+    let no = fixture.bettor(2 * SOL, false);
+    let grace = ConfigParams::default().oracle_grace_period;
+    let manual = another_market(&mut fixture);
+    fixture.expire();
+
+    let result = fixture
+        .svm
+        .process(resolve_oracle_fallback_ix(fixture.market), &[]);
+    assert_error(result, ErrorCode::OracleGracePeriodActive);
+    fixture.svm.warp_to(fixture.end_time + grace);
+    let result = fixture.svm.process(resolve_oracle_fallback_ix(manual), &[]);
+    assert_error(result, ErrorCode::OracleNotEnabled);
+
+    fixture
+        .svm
+        .process(resolve_oracle_fallback_ix(fixture.market), &[])
+        .unwrap();
+    let market = fixture.market();
+    assert!(market.resolved && market.invalidated);
+    assert_eq!(market.fallback_reason, FALLBACK_ORACLE_TIMEOUT);
+
+    // A late price can no longer settle it, and every bet is refunded
+    let price_update = Pubkey::new_unique();
+    let now = fixture.svm.now();
+    fixture
+        .svm
+        .set_price_update(price_update, FEED_ID, 150, now);
+    let result = fixture.svm.process(
+        resolve_with_oracle_ix(fixture.market, price_update, yes),
+        &[yes],
+    );
+    assert_error(result, ErrorCode::AlreadyResolved);
+    for (user, stake) in [(yes, SOL), (no, 2 * SOL)] {
+        let before = fixture.svm.lamports(&user);
+        fixture
+            .svm
+            .process(claim_refund_ix(user, fixture.market), &[user])
+            .unwrap();
+        assert_eq!(fixture.svm.lamports(&user), before + stake);
+    }
+}
+
+#[test]
+fn oracle_fallback_can_go_to_governance() {
+    let mut fixture = Fixture::new();
+    configure(
+        &mut fixture,
+        ConfigParams {
+            oracle_fallback: ORACLE_FALLBACK_GOVERNANCE,
+            ..ConfigParams::default()
+        },
+    );
+    let result = fixture.svm.process(
+        update_config_ix(
+            fixture.admin,
+            ConfigParams {
+                oracle_fallback: ORACLE_FALLBACK_GOVERNANCE + 1,
+                ..ConfigParams::default()
+            },
+        ),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::InvalidConfig);
+
+    let market = fixture.svm.signer();
+    let end_time = fixture.svm.now() + ONE_DAY;
+    let mut params = MarketParams::new("Will SOL close above 100?", end_time);
+    params.oracle = Some(OracleParams::above(100));
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    assert_eq!(
+        fixture.svm.fetch::<Market>(&market).fallback_reason,
+        FALLBACK_NONE
+    );
+    let mint = governance(&mut fixture);
+    let (alice, _) = token_holder(&mut fixture, mint, 1_000);
+    fixture.svm.warp_to(fixture.svm.now() + 1);
+    let id = propose(
+        &mut fixture,
+        alice,
+        ProposalAction::ResolveMarket(market, false),
+    );
+    vote(&mut fixture, alice, id, true);
+
+    // The grace period replaces the longer authority timeout
+    let grace = ConfigParams::default().oracle_grace_period;
+    fixture.svm.warp_to(end_time + grace);
+    let result = fixture.svm.process(resolve_oracle_fallback_ix(market), &[]);
+    assert_error(result, ErrorCode::MustUseGovernance);
+    fixture
+        .svm
+        .process(execute_proposal_ix(id, Some(market)), &[])
+        .unwrap();
+    let market: Market = fixture.svm.fetch(&market);
+    assert!(market.resolved && !market.invalidated);
+    assert!(!market.winning_outcome);
+    assert_eq!(market.fallback_reason, FALLBACK_ORACLE_TIMEOUT);
+}
+
+#[test]
+fn create_market_validates_the_schedule() {
+    let mut fixture = Fixture::new();
+    let now = fixture.svm.now();
+    let end_time = now + ONE_DAY;
+
+    for (close_time, resolve_after) in [
+        (Some(now), None),
+        (Some(end_time + 1), None),
+        (None, Some(end_time - 1)),
+    ] {
+        let mut params = MarketParams::new("Will the match go to penalties?", end_time);
+        params.schedule = MarketSchedule {
+            close_time,
+            resolve_after,
+        };
+        let market = fixture.svm.signer();
+        let result = fixture.svm.process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        );
+        assert_error(result, ErrorCode::InvalidSchedule);
+    }
+
+    let state = fixture.market();
+    assert_eq!(
+        (state.close_time, state.resolve_after),
+        (fixture.end_time, fixture.end_time)
+    );
+}
+
+#[test]
+fn betting_closes_before_resolution_opens() {
+    let mut fixture = Fixture::with(|params| {
+        params.schedule = MarketSchedule {
+            close_time: Some(params.end_time - ONE_DAY / 2),
+            resolve_after: Some(params.end_time + ONE_DAY),
+        }
+    });
+    let close_time = fixture.market().close_time;
+    let provider = fixture.provide(SOL, SOL);
+    fixture.bettor(SOL, true);
+
+    fixture.svm.warp_to(close_time);
+    let user = fixture.svm.funded_account(2 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, fixture.market, SOL, false), &[user]);
+    assert_error(result, ErrorCode::MarketExpired);
+    let result = fixture.svm.process(
+        remove_liquidity_ix(provider, fixture.market, SOL),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::LiquidityLocked);
+
+    // end_time alone no longer opens resolution
+    fixture.expire();
+    let result = fixture.svm.process(
+        resolve_market_ix(fixture.market, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::MarketNotExpired);
+    fixture.svm.warp_to(fixture.end_time + ONE_DAY);
+    fixture
+        .svm
+        .process(
+            resolve_market_ix(fixture.market, fixture.authority, true),
+            &[fixture.authority],
+        )
+        .unwrap();
+    let events = fixture.svm.events::<MarketResolved>();
+    assert_eq!(
+        (events[0].market, events[0].outcome, events[0].invalidated),
+        (fixture.market, true, false)
+    );
+}
+
+#[test]
+fn authority_or_trigger_can_close_betting_early() {
+    let mut fixture = Fixture::new();
+    let (market, authority) = (fixture.market, fixture.authority);
+    let trigger = fixture.svm.signer();
+
+    let result = fixture
+        .svm
+        .process(close_betting_ix(market, trigger), &[trigger]);
+    assert_error(result, ErrorCode::Unauthorized);
+    let result = fixture
+        .svm
+        .process(set_close_trigger_ix(market, trigger, trigger), &[trigger]);
+    assert_error(result, ErrorCode::Unauthorized);
+    fixture
+        .svm
+        .process(
+            set_close_trigger_ix(market, authority, trigger),
+            &[authority],
+        )
+        .unwrap();
+
+    fixture.svm.warp_to(fixture.svm.now() + 60);
+    fixture
+        .svm
+        .process(close_betting_ix(market, trigger), &[trigger])
+        .unwrap();
+    let now = fixture.svm.now();
+    let events = fixture.svm.events::<BettingClosed>();
+    assert_eq!(
+        (events[0].market, events[0].closed_by, events[0].close_time),
+        (market, trigger, now)
+    );
+    assert_eq!(fixture.market().close_time, now);
+
+    let user = fixture.svm.funded_account(2 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(user, market, SOL, true), &[user]);
+    assert_error(result, ErrorCode::MarketExpired);
+    let result = fixture
+        .svm
+        .process(close_betting_ix(market, authority), &[authority]);
+    assert_error(result, ErrorCode::BettingAlreadyClosed);
+
+    // Closing early doesn't bring resolution forward
+    let result = fixture
+        .svm
+        .process(resolve_market_ix(market, authority, false), &[authority]);
+    assert_error(result, ErrorCode::MarketNotExpired);
+}
+
+#[test]
+fn position_caps_limit_users_and_the_pool() {
+    let mut fixture = Fixture::with(|params| {
+        params.limits = MarketLimits {
+            max_bet_per_user: Some(SOL),
+            max_total_pool: Some(2 * SOL),
+        }
+    });
+    let market = fixture.market;
+
+    let whale = fixture.svm.funded_account(3 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(whale, market, SOL + 1, true), &[whale]);
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+    fixture.bettor(SOL, true);
+    fixture.bettor(SOL - MIN_BET, false);
+    assert!(fixture.svm.events::<MarketCapReached>().is_empty());
+
+    // The last bet that fits fills the pool
+    fixture.bettor(MIN_BET, false);
+    let events = fixture.svm.events::<MarketCapReached>();
+    assert_eq!(
+        (
+            events[0].market,
+            events[0].total_pool,
+            events[0].max_total_pool
+        ),
+        (market, 2 * SOL, 2 * SOL)
+    );
+    let result = fixture
+        .svm
+        .process(place_bet_ix(whale, market, MIN_BET, true), &[whale]);
+    assert_error(result, ErrorCode::PoolCapExceeded);
+}
+
+#[test]
+fn position_caps_cover_liquidity() {
+    let mut fixture = Fixture::with(|params| {
+        params.limits = MarketLimits {
+            max_bet_per_user: Some(SOL),
+            max_total_pool: Some(2 * SOL),
+        }
+    });
+    let market = fixture.market;
+
+    let provider = fixture.svm.funded_account(3 * SOL);
+    let result = fixture.svm.process(
+        add_liquidity_ix(provider, market, SOL / 2, SOL / 2 + 1),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+    fixture
+        .svm
+        .process(
+            add_liquidity_ix(provider, market, SOL / 2, SOL / 2),
+            &[provider],
+        )
+        .unwrap();
+    // Topping up counts the inventory already behind the provider's shares
+    let result = fixture.svm.process(
+        add_liquidity_ix(provider, market, MIN_BET, MIN_BET),
+        &[provider],
+    );
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+
+    fixture.bettor(SOL, true);
+    let other = fixture.svm.funded_account(SOL);
+    let result = fixture
+        .svm
+        .process(add_liquidity_ix(other, market, SOL / 4, SOL / 4), &[other]);
+    assert_error(result, ErrorCode::PoolCapExceeded);
+}
+
+#[test]
+fn position_caps_sum_every_position_in_the_market() {
+    let mut fixture = Fixture::with(|params| {
+        params.limits = MarketLimits {
+            max_bet_per_user: Some(SOL),
+            max_total_pool: None,
+        }
+    });
+    open_order_book(&mut fixture);
+    let market = fixture.market;
+    let user = fixture.svm.funded_account(10 * SOL);
+    let maker = fixture.svm.funded_account(10 * SOL);
+
+    // A quarter in the pool, a quarter in liquidity, and a bid that fills
+    // 100 of its 300 shares at 50%
+    fixture
+        .svm
+        .process(place_bet_ix(user, market, SOL / 4, true), &[user])
+        .unwrap();
+    fixture
+        .svm
+        .process(add_liquidity_ix(user, market, SOL / 8, SOL / 8), &[user])
+        .unwrap();
+    order(&mut fixture, maker, ORDER_SIDE_ASK, 5_000, 100);
+    let bid = order(&mut fixture, user, ORDER_SIDE_BID, 5_000, 300);
+
+    // Split sets fill whatever is left of the cap
+    let result = fixture
+        .svm
+        .process(split_position_ix(user, market, 351), &[user]);
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+    fixture
+        .svm
+        .process(split_position_ix(user, market, 350), &[user])
+        .unwrap();
+
+    // Settling the fill into the position doesn't free anything
+    consume(&mut fixture, &[user, maker]);
+    let result = fixture
+        .svm
+        .process(split_position_ix(user, market, 1), &[user]);
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+    let result = fixture
+        .svm
+        .process(add_liquidity_ix(user, market, MIN_BET, MIN_BET), &[user]);
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+    let result = fixture.svm.process(
+        place_order_ix(user, market, ORDER_SIDE_BID, 5_000, 20, 10),
+        &[user],
+    );
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+
+    // Cancelling the rest of the bid does
+    fixture
+        .svm
+        .process(cancel_order_ix(user, market, bid), &[user])
+        .unwrap();
+    fixture
+        .svm
+        .process(split_position_ix(user, market, 100), &[user])
+        .unwrap();
+
+    // Order book shares count against a bet too
+    let trader = fixture.svm.funded_account(3 * SOL);
+    fixture
+        .svm
+        .process(split_position_ix(trader, market, 1_000), &[trader])
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(place_bet_ix(trader, market, MIN_BET, true), &[trader]);
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+}
+
+#[test]
+fn position_caps_default_to_the_config() {
+    let mut fixture = Fixture::new();
+    let result = fixture.svm.process(
+        update_config_ix(
+            fixture.admin,
+            ConfigParams {
+                max_bet_per_user: SOL,
+                max_total_pool: SOL / 2,
+                ..ConfigParams::default()
+            },
+        ),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::InvalidConfig);
+    let market = configure(
+        &mut fixture,
+        ConfigParams {
+            max_bet_per_user: SOL,
+            ..ConfigParams::default()
+        },
+    );
+    let state: Market = fixture.svm.fetch(&market);
+    assert_eq!((state.max_bet_per_user, state.max_total_pool), (SOL, 0));
+    let whale = fixture.svm.funded_account(10 * SOL);
+    let result = fixture
+        .svm
+        .process(place_bet_ix(whale, market, 2 * SOL, true), &[whale]);
+    assert_error(result, ErrorCode::UserPositionCapExceeded);
+
+    let end_time = fixture.svm.now() + ONE_DAY;
+    for (max_bet_per_user, max_total_pool) in [(Some(MIN_BET - 1), None), (None, Some(SOL - 1))] {
+        let mut params = MarketParams::new("Will the mayor resign?", end_time);
+        params.limits = MarketLimits {
+            max_bet_per_user,
+            max_total_pool,
+        };
+        let market = fixture.svm.signer();
+        let result = fixture.svm.process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        );
+        assert_error(result, ErrorCode::InvalidPositionCap);
+    }
+
+    // A market can opt out of the default
+    let mut params = MarketParams::new("Will the mayor resign?", end_time);
+    params.limits.max_bet_per_user = Some(0);
+    let uncapped = fixture.svm.signer();
+    fixture
+        .svm
+        .process(
+            create_market_ix(uncapped, fixture.authority, &params),
+            &[fixture.authority, uncapped],
+        )
+        .unwrap();
+    fixture
+        .svm
+        .process(place_bet_ix(whale, uncapped, 2 * SOL, true), &[whale])
+        .unwrap();
+}
+
+/// Opens an order book on the fixture's market
+fn open_order_book(fixture: &mut Fixture) {
+    fixture
+        .svm
+        .process(
+            initialize_order_book_ix(fixture.market, fixture.authority),
+            &[fixture.authority],
+        )
+        .unwrap();
+}
+
+/// Places an order that may cross up to ten resting orders and returns
+/// its id
+fn order(fixture: &mut Fixture, owner: Pubkey, side: u8, price: u64, quantity: u64) -> u64 {
+    fixture
+        .svm
+        .process(
+            place_order_ix(owner, fixture.market, side, price, quantity, 10),
+            &[owner],
+        )
+        .unwrap();
+    fixture.svm.events::<OrderPlaced>()[0].order_id
+}
+
+fn consume(fixture: &mut Fixture, owners: &[Pubkey]) {
+    fixture
+        .svm
+        .process(
+            consume_events_ix(fixture.market, owners, owners.len() as u16),
+            &[],
+        )
+        .unwrap();
+}
+
+fn position(fixture: &Fixture, owner: &Pubkey) -> Position {
+    fixture.svm.fetch(&position_pda(&fixture.market, owner))
+}
+
+/// Claims `owner`'s position and returns the lamports paid beyond its rent
+fn claim_position(fixture: &mut Fixture, owner: Pubkey) -> u64 {
+    let rent = fixture.svm.lamports(&position_pda(&fixture.market, &owner));
+    let before = fixture.svm.lamports(&owner);
+    fixture
+        .svm
+        .process(claim_position_ix(owner, fixture.market), &[owner])
+        .unwrap();
+    fixture.svm.lamports(&owner) - before - rent
+}
+
+#[test]
+fn order_book_matches_at_the_maker_price_and_settles_positions() {
+    let mut fixture = Fixture::new();
+    open_order_book(&mut fixture);
+    let book = order_book_pda(&fixture.market);
+    let book_rent = fixture.svm.lamports(&book);
+    let alice = fixture.svm.funded_account(10 * SOL);
+    let bob = fixture.svm.funded_account(10 * SOL);
+    let fees_before = fixture
+        .svm
+        .fetch::<Treasury>(&treasury_pda())
+        .total_collected;
+
+    // Selling YES at 60% escrows the NO price plus the taker fee
+    let ask = order(&mut fixture, alice, ORDER_SIDE_ASK, 6_000, 100);
+    assert_eq!(
+        fixture.svm.lamports(&book),
+        book_rent + 40_000_000 + 120_000
+    );
+
+    // Bob bids above the ask and trades at Alice's price: 72_000 taker fee
+    // for Bob, 16_000 maker fee for Alice
+    order(&mut fixture, bob, ORDER_SIDE_BID, 6_500, 40);
+    let treasury: Treasury = fixture.svm.fetch(&treasury_pda());
+    assert_eq!(treasury.total_collected, fees_before + 88_000);
+
+    let result = fixture
+        .svm
+        .process(consume_events_ix(fixture.market, &[alice, bob], 2), &[]);
+    assert_error(result, ErrorCode::InvalidPositionAccount);
+    consume(&mut fixture, &[bob, alice]);
+    let bob_position = position(&fixture, &bob);
+    assert_eq!(
+        (
+            bob_position.yes_shares,
+            bob_position.cost,
+            bob_position.balance
+        ),
+        (40, 24_000_000, 2_006_000)
+    );
+    let alice_position = position(&fixture, &alice);
+    assert_eq!(
+        (
+            alice_position.no_shares,
+            alice_position.cost,
+            alice_position.balance
+        ),
+        (40, 16_000_000, 32_000)
+    );
+
+    // The rest of the ask comes back with the fee it no longer needs
+    let result = fixture
+        .svm
+        .process(cancel_order_ix(bob, fixture.market, ask), &[bob]);
+    assert_error(result, ErrorCode::Unauthorized);
+    let before = fixture.svm.lamports(&alice);
+    fixture
+        .svm
+        .process(cancel_order_ix(alice, fixture.market, ask), &[alice])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&alice) - before, 24_072_000);
+    let result = fixture
+        .svm
+        .process(cancel_order_ix(alice, fixture.market, ask), &[alice]);
+    assert_error(result, ErrorCode::OrderNotFound);
+
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::OrderBookOpen);
+    assert_eq!(
+        claim_position(&mut fixture, bob),
+        40 * SHARE_LAMPORTS + 2_006_000
+    );
+    let result = fixture.svm.process(
+        close_order_book_ix(fixture.market, fixture.authority),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::OrderBookNotEmpty);
+    assert_eq!(claim_position(&mut fixture, alice), 32_000);
+    assert_eq!(fixture.svm.lamports(&book), book_rent);
+
+    // An empty book closes and the market finalizes as usual
+    let result = fixture
+        .svm
+        .process(close_order_book_ix(fixture.market, alice), &[alice]);
+    assert_error(result, ErrorCode::Unauthorized);
+    let before = fixture.svm.lamports(&fixture.authority);
+    fixture
+        .svm
+        .process(
+            close_order_book_ix(fixture.market, fixture.authority),
+            &[fixture.authority],
+        )
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&fixture.authority) - before, book_rent);
+    assert!(!fixture.market().has_order_book);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn order_book_fills_by_price_then_time() {
+    let mut fixture = Fixture::new();
+    open_order_book(&mut fixture);
+    let sellers: Vec<Pubkey> = (0..3)
+        .map(|_| fixture.svm.funded_account(10 * SOL))
+        .collect();
+    let bob = fixture.svm.funded_account(10 * SOL);
+    let carol = fixture.svm.funded_account(10 * SOL);
+    order(&mut fixture, sellers[0], ORDER_SIDE_ASK, 6_000, 30);
+    order(&mut fixture, sellers[1], ORDER_SIDE_ASK, 5_800, 30);
+    order(&mut fixture, sellers[2], ORDER_SIDE_ASK, 6_000, 30);
+
+    // The cheapest ask fills first, then equal prices in placement order
+    order(&mut fixture, bob, ORDER_SIDE_BID, 6_000, 75);
+    consume(
+        &mut fixture,
+        &[bob, sellers[1], bob, sellers[0], bob, sellers[2]],
+    );
+    let bob_position = position(&fixture, &bob);
+    assert_eq!(
+        (
+            bob_position.yes_shares,
+            bob_position.cost,
+            bob_position.balance
+        ),
+        (75, 30 * 580_000 + 45 * 600_000, 601_800)
+    );
+    assert_eq!(
+        [0, 1, 2].map(|i| position(&fixture, &sellers[i]).no_shares),
+        [30, 30, 15]
+    );
+
+    // A taker that skips matching leaves the book crossed until a crank
+    // matches it at the resting order's price
+    fixture
+        .svm
+        .process(
+            place_order_ix(carol, fixture.market, ORDER_SIDE_BID, 6_100, 20, 0),
+            &[carol],
+        )
+        .unwrap();
+    assert_eq!(position(&fixture, &carol).yes_shares, 0);
+    fixture
+        .svm
+        .process(match_orders_ix(fixture.market, 5), &[])
+        .unwrap();
+    consume(&mut fixture, &[carol, sellers[2]]);
+    let carol_position = position(&fixture, &carol);
+    assert_eq!(
+        (carol_position.yes_shares, carol_position.cost),
+        (15, 15 * 600_000)
+    );
+    assert_eq!(position(&fixture, &sellers[2]).no_shares, 30);
+}
+
+#[test]
+fn order_book_validates_orders_and_refunds_invalidated_markets() {
+    let mut fixture = Fixture::with_oracle(Some(OracleParams::above(100)));
+    let stranger = fixture.svm.funded_account(SOL);
+    let result = fixture.svm.process(
+        initialize_order_book_ix(fixture.market, stranger),
+        &[stranger],
+    );
+    assert_error(result, ErrorCode::Unauthorized);
+    open_order_book(&mut fixture);
+    let result = fixture.svm.process(
+        update_config_ix(
+            fixture.admin,
+            ConfigParams {
+                taker_fee_bps: 10,
+                maker_fee_bps: 30,
+                ..ConfigParams::default()
+            },
+        ),
+        &[fixture.admin],
+    );
+    assert_error(result, ErrorCode::InvalidConfig);
+
+    let alice = fixture.svm.funded_account(10 * SOL);
+    let bob = fixture.svm.funded_account(10 * SOL);
+    for (side, price, quantity, error) in [
+        (2, 5_000, 1, ErrorCode::InvalidOrderSide),
+        (ORDER_SIDE_BID, 0, 1, ErrorCode::InvalidOrderPrice),
+        (ORDER_SIDE_ASK, 10_000, 1, ErrorCode::InvalidOrderPrice),
+        (ORDER_SIDE_BID, 5_000, 0, ErrorCode::InvalidOrderQuantity),
+        (
+            ORDER_SIDE_BID,
+            5_000,
+            MIN_BET / SHARE_LAMPORTS * 2 - 1,
+            ErrorCode::OrderTooSmall,
+        ),
+    ] {
+        let result = fixture.svm.process(
+            place_order_ix(bob, fixture.market, side, price, quantity, 10),
+            &[bob],
+        );
+        assert_error(result, error);
+    }
+    order(&mut fixture, alice, ORDER_SIDE_ASK, 7_000, 40);
+    order(&mut fixture, bob, ORDER_SIDE_BID, 7_000, 40);
+
+    fixture.expire();
+    let result = fixture.svm.process(
+        place_order_ix(bob, fixture.market, ORDER_SIDE_BID, 7_000, 1, 10),
+        &[bob],
+    );
+    assert_error(result, ErrorCode::MarketExpired);
+    let grace = ConfigParams::default().oracle_grace_period;
+    fixture.svm.warp_to(fixture.end_time + grace);
+    fixture
+        .svm
+        .process(resolve_oracle_fallback_ix(fixture.market), &[])
+        .unwrap();
+
+    // Claims wait for every fill to settle, then refund the collateral
+    let result = fixture
+        .svm
+        .process(claim_position_ix(bob, fixture.market), &[bob]);
+    assert_error(result, ErrorCode::FillEventsPending);
+    consume(&mut fixture, &[bob, alice]);
+    assert_eq!(claim_position(&mut fixture, bob), 28_000_000);
+    assert_eq!(claim_position(&mut fixture, alice), 12_000_000 + 24_000);
+}
+
+#[test]
+fn order_book_markets_refund_a_one_sided_pool() {
+    let mut fixture = Fixture::new();
+    open_order_book(&mut fixture);
+    let bettor = fixture.bettor(SOL, true);
+    let alice = fixture.svm.funded_account(10 * SOL);
+    let bob = fixture.svm.funded_account(10 * SOL);
+    order(&mut fixture, alice, ORDER_SIDE_ASK, 5_000, 20);
+    order(&mut fixture, bob, ORDER_SIDE_BID, 5_000, 20);
+    consume(&mut fixture, &[bob, alice]);
+
+    // Nobody in the pool backed NO, so its bets are refunded while the
+    // book's positions still settle on the outcome
+    fixture.resolve(false);
+    let market = fixture.market();
+    assert!(!market.invalidated && market.pool_refunded && !market.winning_outcome);
+    let result = fixture
+        .svm
+        .process(claim_winnings_ix(bettor, fixture.market), &[bettor]);
+    assert_error(result, ErrorCode::MarketInvalidated);
+    let before = fixture.svm.lamports(&bettor);
+    fixture
+        .svm
+        .process(claim_refund_ix(bettor, fixture.market), &[bettor])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&bettor) - before, SOL);
+
+    let (alice_balance, bob_balance) = (
+        position(&fixture, &alice).balance,
+        position(&fixture, &bob).balance,
+    );
+    assert_eq!(
+        claim_position(&mut fixture, alice),
+        20 * SHARE_LAMPORTS + alice_balance
+    );
+    assert_eq!(claim_position(&mut fixture, bob), bob_balance);
+    fixture
+        .svm
+        .process(
+            close_order_book_ix(fixture.market, fixture.authority),
+            &[fixture.authority],
+        )
+        .unwrap();
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+}
+
+#[test]
+fn order_book_and_event_queue_have_fixed_capacity() {
+    let mut fixture = Fixture::new();
+    open_order_book(&mut fixture);
+    let alice = fixture.svm.funded_account(100 * SOL);
+    let bob = fixture.svm.funded_account(100 * SOL);
+
+    // The smallest orders the minimum bet allows at this price
+    let shares = MIN_BET / (SHARE_LAMPORTS / 10);
+    let asks: Vec<u64> = (0..ORDER_BOOK_CAPACITY)
+        .map(|_| order(&mut fixture, alice, ORDER_SIDE_ASK, 9_000, shares))
+        .collect();
+    let result = fixture.svm.process(
+        place_order_ix(bob, fixture.market, ORDER_SIDE_BID, 9_000, shares, 10),
+        &[bob],
+    );
+    assert_error(result, ErrorCode::OrderBookFull);
+    fixture
+        .svm
+        .process(cancel_order_ix(alice, fixture.market, asks[0]), &[alice])
+        .unwrap();
+
+    // Each fill queues an event for both sides; matching stops once the
+    // queue can't take another pair and the crank waits for consumers
+    let fills = EVENT_QUEUE_CAPACITY as u64 / 2;
+    fixture
+        .svm
+        .process(
+            place_order_ix(
+                bob,
+                fixture.market,
+                ORDER_SIDE_BID,
+                9_000,
+                fills * shares,
+                u16::MAX,
+            ),
+            &[bob],
+        )
+        .unwrap();
+    order(&mut fixture, bob, ORDER_SIDE_BID, 9_000, shares);
+    let result = fixture.svm.process(match_orders_ix(fixture.market, 1), &[]);
+    assert_error(result, ErrorCode::EventQueueFull);
+}
+
+fn conditional_market(fixture: &mut Fixture, outcome: bool) -> Pubkey {
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new(
+        "If the bill passes, will turnout top 60%?",
+        fixture.end_time,
+    );
+    params.condition = Some((fixture.market, outcome));
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    market
+}
+
+#[test]
+fn conditional_market_resolves_once_its_condition_is_met() {
+    let mut fixture = Fixture::new();
+    let conditional = conditional_market(&mut fixture, true);
+    let market: Market = fixture.svm.fetch(&conditional);
+    assert_eq!(market.condition_market, fixture.market);
+    assert!(market.condition_outcome && !market.condition_met);
+
+    let mut params = MarketParams::new("Will the bill market resolve YES?", fixture.end_time);
+    params.meta = Some((fixture.market, META_PARENT_YES));
+    params.condition = Some((fixture.market, true));
+    let both = fixture.svm.signer();
+    let result = fixture.svm.process(
+        create_market_ix(both, fixture.authority, &params),
+        &[fixture.authority, both],
+    );
+    assert_error(result, ErrorCode::InvalidCondition);
+    let result = fixture
+        .svm
+        .process(resolve_condition_ix(fixture.market, Pubkey::default()), &[]);
+    assert_error(result, ErrorCode::NotConditional);
+
+    let yes = fixture.svm.funded_account(2 * SOL);
+    let no = fixture.svm.funded_account(2 * SOL);
+    for (user, bet_yes) in [(yes, true), (no, false)] {
+        fixture
+            .svm
+            .process(place_bet_ix(user, conditional, SOL, bet_yes), &[user])
+            .unwrap();
+    }
+
+    fixture.expire();
+    let result = fixture.svm.process(
+        resolve_market_ix(conditional, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::ConditionNotMet);
+    let result = fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[]);
+    assert_error(result, ErrorCode::ParentNotResolved);
+
+    fixture.resolve(true);
+    fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[])
+        .unwrap();
+    assert!(fixture.svm.events::<ConditionResolved>()[0].met);
+    let market: Market = fixture.svm.fetch(&conditional);
+    assert!(market.condition_met && !market.resolved);
+    let result = fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[]);
+    assert_error(result, ErrorCode::ConditionAlreadyMet);
+
+    fixture
+        .svm
+        .process(
+            resolve_market_ix(conditional, fixture.authority, true),
+            &[fixture.authority],
+        )
+        .unwrap();
+    let before = fixture.svm.lamports(&yes);
+    fixture
+        .svm
+        .process(claim_winnings_ix(yes, conditional), &[yes])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&yes) - before, 2 * SOL);
+}
+
+#[test]
+fn conditional_market_refunds_stakes_when_its_condition_fails() {
+    let mut fixture = Fixture::new();
+    let conditional = conditional_market(&mut fixture, true);
+    let yes = fixture.svm.funded_account(2 * SOL);
+    let no = fixture.svm.funded_account(2 * SOL);
+    for (user, bet_yes) in [(yes, true), (no, false)] {
+        fixture
+            .svm
+            .process(place_bet_ix(user, conditional, SOL, bet_yes), &[user])
+            .unwrap();
+    }
+
+    fixture.resolve(false);
+    fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[])
+        .unwrap();
+    assert!(!fixture.svm.events::<ConditionResolved>()[0].met);
+    let market: Market = fixture.svm.fetch(&conditional);
+    assert!(market.resolved && market.invalidated);
+    let result = fixture.svm.process(
+        resolve_market_ix(conditional, fixture.authority, true),
+        &[fixture.authority],
+    );
+    assert_error(result, ErrorCode::AlreadyResolved);
+
+    for user in [yes, no] {
+        let before = fixture.svm.lamports(&user);
+        fixture
+            .svm
+            .process(claim_refund_ix(user, conditional), &[user])
+            .unwrap();
+        assert_eq!(fixture.svm.lamports(&user) - before, SOL);
+    }
+}
+
+#[test]
+fn condition_markets_stay_open_until_checked() {
+    let mut fixture = Fixture::new();
+    let conditional = conditional_market(&mut fixture, true);
+    let condition = fixture.market();
+    assert_eq!(condition.child_markets, 1);
+    assert_eq!(
+        fixture.svm.fetch::<Market>(&conditional).parent_created_at,
+        condition.created_at
+    );
+
+    fixture.resolve(true);
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[]);
+    assert_error(result, ErrorCode::ChildMarketsOpen);
+
+    // A market recreated at the condition market's address is not read
+    let mut recreated = fixture.market();
+    recreated.created_at += 1;
+    fixture.svm.store(&fixture.market, &recreated);
+    let result = fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[]);
+    assert_error(result, ErrorCode::InvalidParentMarket);
+    recreated.created_at = condition.created_at;
+    fixture.svm.store(&fixture.market, &recreated);
+
+    fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[])
+        .unwrap();
+    assert_eq!(fixture.market().child_markets, 0);
+    fixture
+        .svm
+        .process(finalize_market_ix(fixture.market, fixture.authority), &[])
+        .unwrap();
+
+    // The met condition outlives the condition market
+    fixture
+        .svm
+        .process(
+            resolve_market_ix(conditional, fixture.authority, true),
+            &[fixture.authority],
+        )
+        .unwrap();
+}
+
+#[test]
+fn split_and_merge_keep_conditional_positions_covered() {
+    let mut fixture = Fixture::new();
+    let conditional = conditional_market(&mut fixture, true);
+    fixture
+        .svm
+        .process(
+            initialize_order_book_ix(conditional, fixture.authority),
+            &[fixture.authority],
+        )
+        .unwrap();
+    let book = order_book_pda(&conditional);
+    let book_rent = fixture.svm.lamports(&book);
+    let [erin, alice, carol, dave] = [(); 4].map(|_| fixture.svm.funded_account(10 * SOL));
+
+    // Collateral becomes full sets and back
+    fixture
+        .svm
+        .process(split_position_ix(erin, conditional, 3), &[erin])
+        .unwrap();
+    let position: Position = fixture.svm.fetch(&position_pda(&conditional, &erin));
+    assert_eq!(
+        (position.yes_shares, position.no_shares, position.cost),
+        (3, 3, 3 * SHARE_LAMPORTS)
+    );
+    let before = fixture.svm.lamports(&erin);
+    fixture
+        .svm
+        .process(merge_position_ix(erin, conditional, 1), &[erin])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&erin) - before, SHARE_LAMPORTS);
+    for (sets, error) in [
+        (0, ErrorCode::InvalidOrderQuantity),
+        (3, ErrorCode::InsufficientShares),
+    ] {
+        let result = fixture
+            .svm
+            .process(merge_position_ix(erin, conditional, sets), &[erin]);
+        assert_error(result, error);
+    }
+
+    // Alice buys YES and NO at 40% each; the set pays a full share once
+    // resolved but cannot be merged for more than it cost
+    for (owner, side, price) in [
+        (carol, ORDER_SIDE_ASK, 4_000),
+        (alice, ORDER_SIDE_BID, 4_000),
+        (dave, ORDER_SIDE_BID, 6_000),
+        (alice, ORDER_SIDE_ASK, 6_000),
+    ] {
+        fixture
+            .svm
+            .process(
+                place_order_ix(owner, conditional, side, price, 25, 10),
+                &[owner],
+            )
+            .unwrap();
+    }
+    fixture
+        .svm
+        .process(
+            consume_events_ix(conditional, &[alice, carol, dave, alice], 4),
+            &[],
+        )
+        .unwrap();
+    let position: Position = fixture.svm.fetch(&position_pda(&conditional, &alice));
+    assert_eq!(
+        (position.yes_shares, position.no_shares, position.cost),
+        (25, 25, 20_000_000)
+    );
+    let result = fixture
+        .svm
+        .process(merge_position_ix(alice, conditional, 25), &[alice]);
+    assert_error(result, ErrorCode::MergeExceedsCost);
+
+    // The condition fails and every position gets its collateral back,
+    // plus the maker fee it locked but did not pay
+    fixture.resolve(false);
+    fixture
+        .svm
+        .process(resolve_condition_ix(conditional, fixture.market), &[])
+        .unwrap();
+    for (owner, payout) in [
+        (erin, 2 * SHARE_LAMPORTS),
+        (alice, 20_000_000),
+        (carol, 15_030_000),
+        (dave, 15_030_000),
+    ] {
+        let rent = fixture.svm.lamports(&position_pda(&conditional, &owner));
+        let before = fixture.svm.lamports(&owner);
+        fixture
+            .svm
+            .process(claim_position_ix(owner, conditional), &[owner])
+            .unwrap();
+        assert_eq!(fixture.svm.lamports(&owner) - before - rent, payout);
+    }
+    assert_eq!(fixture.svm.lamports(&book), book_rent);
+}
+
+/// Opens the house vault with `liquidity` from a new provider and returns
+/// the provider
+fn house(fixture: &mut Fixture, liquidity: u64) -> Pubkey {
+    fixture
+        .svm
+        .process(initialize_house_vault_ix(fixture.admin), &[fixture.admin])
+        .unwrap();
+    let provider = fixture.svm.funded_account(liquidity + SOL);
+    fixture
+        .svm
+        .process(deposit_house_liquidity_ix(provider, liquidity), &[provider])
+        .unwrap();
+    provider
+}
+
+/// Requests a withdrawal of `shares` and makes it once the delay is over
+fn withdraw_house(
+    fixture: &mut Fixture,
+    provider: Pubkey,
+    shares: u64,
+) -> std::result::Result<(), ProgramError> {
+    fixture
+        .svm
+        .process(request_house_withdrawal_ix(provider, shares), &[provider])
+        .unwrap();
+    fixture
+        .svm
+        .warp_to(fixture.svm.now() + HOUSE_WITHDRAWAL_DELAY);
+    fixture
+        .svm
+        .process(withdraw_house_liquidity_ix(provider), &[provider])
+}
+
+/// An oracle market ending a day from now with `yes` and `no` staked on it
+fn pooled_market(fixture: &mut Fixture, yes: u64, no: u64) -> Pubkey {
+    let market = fixture.svm.signer();
+    let mut params = MarketParams::new("Will BTC close above 100k?", fixture.svm.now() + ONE_DAY);
+    params.oracle = Some(OracleParams::above(100));
+    fixture
+        .svm
+        .process(
+            create_market_ix(market, fixture.authority, &params),
+            &[fixture.authority, market],
+        )
+        .unwrap();
+    for (amount, bet_yes) in [(yes, true), (no, false)] {
+        if amount > 0 {
+            let user = fixture.svm.funded_account(amount + SOL);
+            fixture
+                .svm
+                .process(place_bet_ix(user, market, amount, bet_yes), &[user])
+                .unwrap();
+        }
+    }
+    market
+}
+
+/// Resolves an oracle market from a fresh price on the right side of its
+/// threshold
+fn resolve_leg(fixture: &mut Fixture, market: Pubkey, outcome: bool) {
+    let end_time = fixture.svm.fetch::<Market>(&market).end_time;
+    fixture.svm.warp_to(end_time.max(fixture.svm.now()));
+    let price_update = Pubkey::new_unique();
+    let now = fixture.svm.now();
+    let price = if outcome { 150 } else { 50 };
+    fixture
+        .svm
+        .set_price_update(price_update, FEED_ID, price, now);
+    let caller = fixture.svm.funded_account(SOL);
+    fixture
+        .svm
+        .process(
+            resolve_with_oracle_ix(market, price_update, caller),
+            &[caller],
+        )
+        .unwrap();
+}
+
+fn parlay(fixture: &mut Fixture, owner: Pubkey, legs: &[(Pubkey, bool)], stake: u64) -> u64 {
+    let id = fixture
+        .svm
+        .fetch::<HouseVault>(&house_vault_pda())
+        .parlay_count;
+    fixture
+        .svm
+        .process(place_parlay_ix(owner, id, legs, stake, 0), &[owner])
+        .unwrap();
+    id
+}
+
+/// Settles parlay `id` without its owner signing and returns what the
+/// owner got beyond the parlay's rent
+fn settle_parlay(fixture: &mut Fixture, owner: Pubkey, id: u64, markets: &[Pubkey]) -> u64 {
+    let rent = fixture.svm.lamports(&parlay_pda(id));
+    let before = fixture.svm.lamports(&owner);
+    fixture
+        .svm
+        .process(settle_parlay_ix(owner, id, markets), &[])
+        .unwrap();
+    fixture.svm.lamports(&owner) - before - rent
+}
+
+#[test]
+fn parlay_prices_legs_from_pool_odds_and_pays_when_every_leg_wins() {
+    let mut fixture = Fixture::new();
+    let provider = house(&mut fixture, 10 * SOL);
+    let market = pooled_market(&mut fixture, SOL, 3 * SOL);
+    let other = pooled_market(&mut fixture, SOL, SOL);
+    let owner = fixture.svm.funded_account(2 * SOL);
+
+    // YES at 1:3 pays 4x and an even pool 2x
+    let legs = [(market, true), (other, false)];
+    let result = fixture.svm.process(
+        place_parlay_ix(owner, 0, &legs, SOL / 10, 8 * SOL / 10 + 1),
+        &[owner],
+    );
+    assert_error(result, ErrorCode::ParlayOddsMoved);
+    for legs in [vec![(market, true)], vec![(market, true), (market, false)]] {
+        let result = fixture
+            .svm
+            .process(place_parlay_ix(owner, 0, &legs, SOL / 10, 0), &[owner]);
+        assert_error(result, ErrorCode::InvalidParlayLegs);
+    }
+    // Its authority decides a manual market, so it can't be a leg
+    let result = fixture.svm.process(
+        place_parlay_ix(
+            owner,
+            0,
+            &[(market, true), (fixture.market, true)],
+            SOL / 10,
+            0,
+        ),
+        &[owner],
+    );
+    assert_error(result, ErrorCode::ParlayLegNotOracle);
+    let id = parlay(&mut fixture, owner, &legs, SOL / 10);
+    let event = &fixture.svm.events::<ParlayPlaced>()[0];
+    assert_eq!(
+        (event.stake, event.payout, event.legs),
+        (SOL / 10, 8 * SOL / 10, 2)
+    );
+    let vault: HouseVault = fixture.svm.fetch(&house_vault_pda());
+    assert_eq!(vault.liability, 8 * SOL / 10);
+    for leg in [market, other] {
+        assert_eq!(fixture.svm.fetch::<Market>(&leg).open_parlays, 1);
+    }
+
+    let result = fixture
+        .svm
+        .process(settle_parlay_ix(owner, id, &[market, other]), &[]);
+    assert_error(result, ErrorCode::ParlayLegsPending);
+    resolve_leg(&mut fixture, market, true);
+    resolve_leg(&mut fixture, other, false);
+
+    // Legs stay open for the parlay to read until it settles
+    let authority = fixture.authority;
+    let result = fixture
+        .svm
+        .process(finalize_market_ix(market, authority), &[]);
+    assert_error(result, ErrorCode::ParlaysOpen);
+    let paid = settle_parlay(&mut fixture, owner, id, &[market, other]);
+    assert_eq!(paid, 8 * SOL / 10);
+    assert!(fixture.svm.events::<ParlaySettled>()[0].won);
+    let vault: HouseVault = fixture.svm.fetch(&house_vault_pda());
+    assert_eq!(vault.liability, 0);
+    for leg in [market, other] {
+        assert_eq!(fixture.svm.fetch::<Market>(&leg).open_parlays, 0);
+    }
+
+    // The house paid out 0.7 SOL net of the stake
+    let before = fixture.svm.lamports(&provider);
+    withdraw_house(&mut fixture, provider, vault.total_shares).unwrap();
+    assert_eq!(
+        fixture.svm.lamports(&provider) - before,
+        10 * SOL - 7 * SOL / 10
+    );
+}
+
+#[test]
+fn parlays_lose_on_any_leg_and_void_invalidated_legs() {
+    let mut fixture = Fixture::new();
+    house(&mut fixture, 10 * SOL);
+    let market = pooled_market(&mut fixture, SOL, 3 * SOL);
+    let open = pooled_market(&mut fixture, SOL, SOL);
+    let unpriced = pooled_market(&mut fixture, SOL, SOL);
+    let owner = fixture.svm.funded_account(2 * SOL);
+    let losing = parlay(
+        &mut fixture,
+        owner,
+        &[(market, false), (open, true)],
+        SOL / 10,
+    );
+    let voided = parlay(
+        &mut fixture,
+        owner,
+        &[(market, true), (unpriced, true)],
+        SOL / 10,
+    );
+
+    // One losing leg settles the parlay while the rest are still open
+    resolve_leg(&mut fixture, market, true);
+    assert_eq!(
+        settle_parlay(&mut fixture, owner, losing, &[market, open]),
+        0
+    );
+    let event = &fixture.svm.events::<ParlaySettled>()[0];
+    assert!(!event.won && event.paid == 0);
+    assert_eq!(fixture.svm.fetch::<Market>(&open).open_parlays, 0);
+
+    // A leg whose price never arrives is invalidated and drops out at its
+    // 2x odds
+    let grace = ConfigParams::default().oracle_grace_period;
+    fixture.svm.warp_to(fixture.svm.now() + grace);
+    fixture
+        .svm
+        .process(resolve_oracle_fallback_ix(unpriced), &[])
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(settle_parlay_ix(owner, voided, &[unpriced, market]), &[]);
+    assert_error(result, ErrorCode::InvalidParlayLegs);
+    assert_eq!(
+        settle_parlay(&mut fixture, owner, voided, &[market, unpriced]),
+        4 * SOL / 10
+    );
+    let vault: HouseVault = fixture.svm.fetch(&house_vault_pda());
+    assert_eq!(vault.liability, 0);
+}
+
+#[test]
+fn parlays_stay_within_the_house_risk_limits() {
+    let mut fixture = Fixture::new();
+    let stranger = fixture.svm.funded_account(SOL);
+    let result = fixture
+        .svm
+        .process(initialize_house_vault_ix(stranger), &[stranger]);
+    assert_error(result, ErrorCode::Unauthorized);
+    for params in [
+        ConfigParams {
+            house_liability_bps: 10_001,
+            ..ConfigParams::default()
+        },
+        ConfigParams {
+            max_parlay_leg_odds: 0,
+            ..ConfigParams::default()
+        },
+    ] {
+        let result = fixture
+            .svm
+            .process(update_config_ix(fixture.admin, params), &[fixture.admin]);
+        assert_error(result, ErrorCode::InvalidConfig);
+    }
+    configure(
+        &mut fixture,
+        ConfigParams {
+            max_parlay_payout: SOL,
+            house_liability_bps: 1_000,
+            ..ConfigParams::default()
+        },
+    );
+    let provider = house(&mut fixture, 5 * SOL);
+    let first = pooled_market(&mut fixture, SOL, SOL);
+    let second = pooled_market(&mut fixture, SOL, SOL);
+    let owner = fixture.svm.funded_account(2 * SOL);
+    let legs = [(first, true), (second, true)];
+
+    // 4x odds: 2 SOL is over the payout cap, 0.8 SOL over a tenth of the
+    // vault, 0.4 SOL fits
+    for (stake, error) in [
+        (SOL / 2, ErrorCode::ParlayPayoutTooLarge),
+        (SOL / 5, ErrorCode::HouseCapacityExceeded),
+    ] {
+        let result = fixture
+            .svm
+            .process(place_parlay_ix(owner, 0, &legs, stake, 0), &[owner]);
+        assert_error(result, error);
+    }
+
+    // Legs need a side to price from, a deep enough pool and odds of at
+    // most 10x
+    let one_sided = pooled_market(&mut fixture, SOL, 0);
+    let shallow = pooled_market(&mut fixture, SOL / 4, SOL / 4);
+    let long_shot = pooled_market(&mut fixture, SOL / 10, SOL);
+    for (leg, outcome, error) in [
+        (one_sided, false, ErrorCode::LegHasNoOdds),
+        (shallow, false, ErrorCode::ParlayLegPoolTooSmall),
+        (long_shot, true, ErrorCode::ParlayLegOddsTooHigh),
+    ] {
+        let result = fixture.svm.process(
+            place_parlay_ix(owner, 0, &[(first, true), (leg, outcome)], SOL / 10, 0),
+            &[owner],
+        );
+        assert_error(result, error);
+    }
+    parlay(&mut fixture, owner, &legs, SOL / 10);
+
+    // Liquidity backing the open payout stays in the vault
+    let shares = fixture
+        .svm
+        .fetch::<HouseVault>(&house_vault_pda())
+        .total_shares;
+    let result = withdraw_house(&mut fixture, provider, shares);
+    assert_error(result, ErrorCode::HouseLiquidityLocked);
+    withdraw_house(&mut fixture, provider, shares / 2).unwrap();
+}
+
+#[test]
+fn house_withdrawals_wait_for_winning_parlays_to_settle() {
+    let mut fixture = Fixture::new();
+    let provider = house(&mut fixture, 10 * SOL);
+    let shares = fixture
+        .svm
+        .fetch::<HouseDeposit>(&house_deposit_pda(&provider))
+        .shares;
+    let result = fixture
+        .svm
+        .process(withdraw_house_liquidity_ix(provider), &[provider]);
+    assert_error(result, ErrorCode::HouseWithdrawalNotRequested);
+
+    let market = pooled_market(&mut fixture, SOL, 3 * SOL);
+    let other = pooled_market(&mut fixture, SOL, SOL);
+    let owner = fixture.svm.funded_account(2 * SOL);
+    let id = parlay(
+        &mut fixture,
+        owner,
+        &[(market, true), (other, false)],
+        SOL / 10,
+    );
+    resolve_leg(&mut fixture, market, true);
+    resolve_leg(&mut fixture, other, false);
+
+    // Seeing the parlay win is too late to leave ahead of its payout
+    fixture
+        .svm
+        .process(
+            request_house_withdrawal_ix(provider, shares / 2),
+            &[provider],
+        )
+        .unwrap();
+    let result = fixture
+        .svm
+        .process(withdraw_house_liquidity_ix(provider), &[provider]);
+    assert_error(result, ErrorCode::HouseWithdrawalNotReady);
+    assert_eq!(
+        settle_parlay(&mut fixture, owner, id, &[market, other]),
+        8 * SOL / 10
+    );
+    fixture
+        .svm
+        .warp_to(fixture.svm.now() + HOUSE_WITHDRAWAL_DELAY);
+    let before = fixture.svm.lamports(&provider);
+    fixture
+        .svm
+        .process(withdraw_house_liquidity_ix(provider), &[provider])
+        .unwrap();
+    let withdrawn = fixture.svm.lamports(&provider) - before;
+    assert!(withdrawn > 46 * SOL / 10 && withdrawn <= 93 * SOL / 20);
+
+    // A request lapses if not used in time, and gains made while it waits
+    // stay in the vault
+    let rest = shares - shares / 2;
+    fixture
+        .svm
+        .process(request_house_withdrawal_ix(provider, rest), &[provider])
+        .unwrap();
+    fixture
+        .svm
+        .warp_to(fixture.svm.now() + HOUSE_WITHDRAWAL_DELAY + HOUSE_WITHDRAWAL_WINDOW);
+    let result = fixture
+        .svm
+        .process(withdraw_house_liquidity_ix(provider), &[provider]);
+    assert_error(result, ErrorCode::HouseWithdrawalExpired);
+    fixture
+        .svm
+        .process(request_house_withdrawal_ix(provider, rest), &[provider])
+        .unwrap();
+    let requested = fixture
+        .svm
+        .fetch::<HouseDeposit>(&house_deposit_pda(&provider))
+        .withdrawal_value;
+    fixture.svm.airdrop(&house_vault_pda(), SOL);
+    fixture
+        .svm
+        .warp_to(fixture.svm.now() + HOUSE_WITHDRAWAL_DELAY);
+    let before = fixture.svm.lamports(&provider);
+    fixture
+        .svm
+        .process(withdraw_house_liquidity_ix(provider), &[provider])
+        .unwrap();
+    assert_eq!(fixture.svm.lamports(&provider) - before, requested);
+}
+
+#[test]
+fn house_shares_check_amounts_and_resist_donations() {
+    let mut fixture = Fixture::new();
+    // An attacker takes the first share and donates to inflate its price
+    let attacker = house(&mut fixture, 1);
+    fixture.svm.airdrop(&house_vault_pda(), 100 * SOL);
+
+    let victim = fixture.svm.funded_account(2 * SOL);
+    let result = fixture
+        .svm
+        .process(deposit_house_liquidity_ix(victim, 0), &[victim]);
+    assert_error(result, ErrorCode::InvalidHouseAmount);
+    fixture
+        .svm
+        .process(deposit_house_liquidity_ix(victim, SOL), &[victim])
+        .unwrap();
+    let shares = fixture
+        .svm
+        .fetch::<HouseDeposit>(&house_deposit_pda(&victim))
+        .shares;
+    for amount in [0, shares + 1] {
+        let result = fixture
+            .svm
+            .process(request_house_withdrawal_ix(victim, amount), &[victim]);
+        assert_error(result, ErrorCode::InvalidHouseAmount);
+    }
+
+    // The victim keeps nearly all of the deposit, and the attacker gets
+    // back less than half of the donation
+    let before = fixture.svm.lamports(&victim);
+    withdraw_house(&mut fixture, victim, shares).unwrap();
+    assert!(fixture.svm.lamports(&victim) - before > 9 * SOL / 10);
+    let attacker_shares = fixture
+        .svm
+        .fetch::<HouseDeposit>(&house_deposit_pda(&attacker))
+        .shares;
+    let before = fixture.svm.lamports(&attacker);
+    withdraw_house(&mut fixture, attacker, attacker_shares).unwrap();
+    assert!(fixture.svm.lamports(&attacker) - before < 51 * SOL);
+}
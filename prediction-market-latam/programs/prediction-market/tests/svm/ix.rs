@@ -0,0 +1,1367 @@
+//! Instruction builders shared by the tests and fuzz targets.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, system_program};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use prediction_market::ProposalAction;
+
+pub const FEED_ID: [u8; 32] = [7; 32];
+
+pub fn bet_pda(user: &Pubkey, market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"bet", user.as_ref(), market.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+pub fn liquidity_pda(provider: &Pubkey, market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"liquidity", provider.as_ref(), market.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+pub fn profile_pda(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"profile", user.as_ref()], &prediction_market::ID).0
+}
+
+pub fn eligibility_vote_pda(market: &Pubkey, voter: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"eligibility_vote", market.as_ref(), voter.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+pub fn config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &prediction_market::ID).0
+}
+
+pub fn governance_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"governance"], &prediction_market::ID).0
+}
+
+pub fn governance_vault_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"governance_vault"], &prediction_market::ID).0
+}
+
+pub fn voter_pda(voter: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"voter", voter.as_ref()], &prediction_market::ID).0
+}
+
+pub fn proposal_pda(id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"proposal", &id.to_le_bytes()], &prediction_market::ID).0
+}
+
+pub fn proposal_vote_pda(proposal: &Pubkey, voter: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"proposal_vote", proposal.as_ref(), voter.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+pub fn resolver_set_pda(market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"resolver_set", market.as_ref()], &prediction_market::ID).0
+}
+
+pub fn global_state_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"global_state"], &prediction_market::ID).0
+}
+
+pub fn human_pda(user: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"human", user.as_ref()], &prediction_market::ID).0
+}
+
+pub fn report_pda(market: &Pubkey, reporter: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"report", market.as_ref(), reporter.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+pub fn treasury_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury"], &prediction_market::ID).0
+}
+
+pub fn house_vault_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"house_vault"], &prediction_market::ID).0
+}
+
+pub fn house_deposit_pda(provider: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"house_deposit", provider.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+pub fn parlay_pda(id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"parlay", &id.to_le_bytes()], &prediction_market::ID).0
+}
+
+pub fn order_book_pda(market: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"order_book", market.as_ref()], &prediction_market::ID).0
+}
+
+pub fn position_pda(market: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"position", market.as_ref(), owner.as_ref()],
+        &prediction_market::ID,
+    )
+    .0
+}
+
+#[derive(Clone)]
+pub struct OracleParams {
+    pub feed_id: Option<[u8; 32]>,
+    pub threshold: Option<i64>,
+    pub comparison: Option<u8>,
+}
+
+impl OracleParams {
+    pub fn above(threshold: i64) -> Self {
+        Self {
+            feed_id: Some(FEED_ID),
+            threshold: Some(threshold),
+            comparison: Some(0),
+        }
+    }
+}
+
+pub fn initialize_ix(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::Initialize {
+            global_state: global_state_pda(),
+            treasury: treasury_pda(),
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::Initialize {}.data(),
+    }
+}
+
+pub fn initialize_config_ix(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::InitializeConfig {
+            config: config_pda(),
+            global_state: global_state_pda(),
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::InitializeConfig {}.data(),
+    }
+}
+
+fn update_config_accounts(authority: Pubkey) -> Vec<AccountMeta> {
+    prediction_market::accounts::UpdateConfig {
+        config: config_pda(),
+        authority,
+    }
+    .to_account_metas(None)
+}
+
+pub fn update_config_ix(authority: Pubkey, params: prediction_market::ConfigParams) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: update_config_accounts(authority),
+        data: prediction_market::instruction::UpdateConfig { params }.data(),
+    }
+}
+
+pub fn apply_config_update_ix() -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ApplyConfigUpdate {
+            config: config_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ApplyConfigUpdate {}.data(),
+    }
+}
+
+pub fn cancel_config_update_ix(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: update_config_accounts(authority),
+        data: prediction_market::instruction::CancelConfigUpdate {}.data(),
+    }
+}
+
+pub fn transfer_config_authority_ix(authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: update_config_accounts(authority),
+        data: prediction_market::instruction::TransferConfigAuthority { new_authority }.data(),
+    }
+}
+
+pub fn accept_config_authority_ix(pending_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::AcceptConfigAuthority {
+            config: config_pda(),
+            pending_authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::AcceptConfigAuthority {}.data(),
+    }
+}
+
+pub fn initialize_governance_ix(
+    authority: Pubkey,
+    mint: Pubkey,
+    quorum_bps: u16,
+    threshold_bps: u16,
+    voting_period: i64,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::InitializeGovernance {
+            governance: governance_pda(),
+            vault: governance_vault_pda(),
+            mint,
+            config: config_pda(),
+            authority,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::InitializeGovernance {
+            quorum_bps,
+            threshold_bps,
+            voting_period,
+        }
+        .data(),
+    }
+}
+
+pub fn deposit_governance_tokens_ix(
+    voter: Pubkey,
+    voter_token_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::DepositGovernanceTokens {
+            governance: governance_pda(),
+            vault: governance_vault_pda(),
+            voter_record: voter_pda(&voter),
+            voter_token_account,
+            voter,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::DepositGovernanceTokens { amount }.data(),
+    }
+}
+
+pub fn withdraw_governance_tokens_ix(
+    voter: Pubkey,
+    voter_token_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::WithdrawGovernanceTokens {
+            governance: governance_pda(),
+            vault: governance_vault_pda(),
+            voter_record: voter_pda(&voter),
+            voter_token_account,
+            voter,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::WithdrawGovernanceTokens { amount }.data(),
+    }
+}
+
+/// Creates proposal `id`, which must be the governance's next proposal.
+pub fn create_proposal_ix(proposer: Pubkey, id: u64, action: ProposalAction) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CreateProposal {
+            governance: governance_pda(),
+            proposal: proposal_pda(id),
+            voter_record: voter_pda(&proposer),
+            proposer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CreateProposal {
+            action,
+            description_hash: [0; 32],
+        }
+        .data(),
+    }
+}
+
+pub fn cast_vote_ix(voter: Pubkey, id: u64, approve: bool) -> Instruction {
+    let proposal = proposal_pda(id);
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CastVote {
+            proposal,
+            voter_record: voter_pda(&voter),
+            vote: proposal_vote_pda(&proposal, &voter),
+            voter,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CastVote { approve }.data(),
+    }
+}
+
+/// `market` is the target of a `ResolveMarket` proposal.
+pub fn execute_proposal_ix(id: u64, market: Option<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ExecuteProposal {
+            governance: governance_pda(),
+            proposal: proposal_pda(id),
+            config: config_pda(),
+            global_state: global_state_pda(),
+            market,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ExecuteProposal {}.data(),
+    }
+}
+
+/// Arguments of `create_market`, defaulting to a manual market.
+#[derive(Clone)]
+pub struct MarketParams {
+    pub question: String,
+    pub description: String,
+    pub end_time: i64,
+    pub oracle: Option<OracleParams>,
+    pub claim_deadline: Option<i64>,
+    pub unclaimed_policy: u8,
+    /// Also passes the creator's attestation.
+    pub require_human: bool,
+    /// Puts the market to an eligibility vote of this many seconds.
+    pub eligibility_period: Option<i64>,
+    /// Parent market and `META_PARENT_*` condition of a meta market.
+    pub meta: Option<(Pubkey, u8)>,
+    pub metadata: prediction_market::MarketMetadata,
+    /// Funds the market instead of the authority.
+    pub payer: Option<Pubkey>,
+    pub schedule: prediction_market::MarketSchedule,
+    pub limits: prediction_market::MarketLimits,
+    /// Condition market and the outcome it must reach for a conditional
+    /// market.
+    pub condition: Option<(Pubkey, bool)>,
+}
+
+impl MarketParams {
+    pub fn new(question: &str, end_time: i64) -> Self {
+        Self {
+            question: question.to_string(),
+            description: "Resolves from public records".to_string(),
+            end_time,
+            oracle: None,
+            claim_deadline: None,
+            unclaimed_policy: 0,
+            require_human: false,
+            eligibility_period: None,
+            meta: None,
+            metadata: Default::default(),
+            payer: None,
+            schedule: Default::default(),
+            limits: Default::default(),
+            condition: None,
+        }
+    }
+}
+
+pub fn create_market_ix(market: Pubkey, authority: Pubkey, params: &MarketParams) -> Instruction {
+    let oracle = params.oracle.clone().unwrap_or(OracleParams {
+        feed_id: None,
+        threshold: None,
+        comparison: None,
+    });
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CreateMarket {
+            market,
+            global_state: global_state_pda(),
+            creator_attestation: params.require_human.then(|| human_pda(&authority)),
+            parent_market: params.meta.map(|(parent, _)| parent).or(params
+                .condition
+                .map(|(condition_market, _)| condition_market)),
+            config: config_pda(),
+            authority,
+            payer: params.payer.unwrap_or(authority),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CreateMarket {
+            question: params.question.clone(),
+            description: params.description.clone(),
+            end_time: params.end_time,
+            oracle_enabled: params.oracle.is_some(),
+            oracle_feed_id: oracle.feed_id,
+            oracle_threshold: oracle.threshold,
+            oracle_comparison: oracle.comparison,
+            claim_deadline: params.claim_deadline,
+            unclaimed_policy: params.unclaimed_policy,
+            require_human: params.require_human,
+            eligibility_period: params.eligibility_period,
+            meta_condition: params.meta.map(|(_, condition)| condition),
+            metadata: params.metadata.clone(),
+            schedule: params.schedule.clone(),
+            limits: params.limits.clone(),
+            condition: params.condition.map(|(_, outcome)| outcome),
+        }
+        .data(),
+    }
+}
+
+pub fn create_user_profile_ix(user: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CreateUserProfile {
+            user_profile: profile_pda(&user),
+            user,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CreateUserProfile {}.data(),
+    }
+}
+
+pub fn place_bet_ix(user: Pubkey, market: Pubkey, amount: u64, bet_yes: bool) -> Instruction {
+    bet_ix(user, market, amount, bet_yes, None)
+}
+
+/// `place_bet` passing the bettor's human attestation.
+pub fn place_human_bet_ix(user: Pubkey, market: Pubkey, amount: u64, bet_yes: bool) -> Instruction {
+    bet_ix(user, market, amount, bet_yes, Some(human_pda(&user)))
+}
+
+fn bet_ix(
+    user: Pubkey,
+    market: Pubkey,
+    amount: u64,
+    bet_yes: bool,
+    human_attestation: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::PlaceBet {
+            bet: bet_pda(&user, &market),
+            market,
+            user_profile: profile_pda(&user),
+            global_state: global_state_pda(),
+            human_attestation,
+            config: config_pda(),
+            liquidity_position: liquidity_pda(&user, &market),
+            position: position_pda(&market, &user),
+            order_book: order_book_pda(&market),
+            user,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::PlaceBet { amount, bet_yes }.data(),
+    }
+}
+
+pub fn set_human_verifier_ix(authority: Pubkey, verifier: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SetHumanVerifier {
+            global_state: global_state_pda(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SetHumanVerifier { verifier }.data(),
+    }
+}
+
+pub fn set_moderator_ix(authority: Pubkey, moderator: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SetModerator {
+            global_state: global_state_pda(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SetModerator { moderator }.data(),
+    }
+}
+
+pub fn transfer_authority_ix(authority: Pubkey, new_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::TransferAuthority {
+            global_state: global_state_pda(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::TransferAuthority { new_authority }.data(),
+    }
+}
+
+pub fn accept_authority_ix(pending_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::AcceptAuthority {
+            global_state: global_state_pda(),
+            pending_authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::AcceptAuthority {}.data(),
+    }
+}
+
+pub fn set_guardian_ix(authority: Pubkey, guardian: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SetGuardian {
+            global_state: global_state_pda(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SetGuardian { guardian }.data(),
+    }
+}
+
+pub fn set_protocol_pause_ix(guardian: Pubkey, flags: u8) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SetProtocolPause {
+            global_state: global_state_pda(),
+            guardian,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SetProtocolPause { flags }.data(),
+    }
+}
+
+pub fn set_market_pause_ix(guardian: Pubkey, market: Pubkey, flags: u8) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SetMarketPause {
+            market,
+            global_state: global_state_pda(),
+            guardian,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SetMarketPause { flags }.data(),
+    }
+}
+
+pub fn attest_human_ix(verifier: Pubkey, user: Pubkey, expires_at: i64) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::AttestHuman {
+            attestation: human_pda(&user),
+            global_state: global_state_pda(),
+            user,
+            verifier,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::AttestHuman { expires_at }.data(),
+    }
+}
+
+pub fn revoke_human_ix(verifier: Pubkey, user: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::RevokeHuman {
+            attestation: human_pda(&user),
+            verifier,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::RevokeHuman {}.data(),
+    }
+}
+
+pub fn add_liquidity_ix(
+    provider: Pubkey,
+    market: Pubkey,
+    amount_yes: u64,
+    amount_no: u64,
+) -> Instruction {
+    liquidity_ix(provider, market, amount_yes, amount_no, None)
+}
+
+/// `add_liquidity` passing the provider's human attestation.
+pub fn add_human_liquidity_ix(
+    provider: Pubkey,
+    market: Pubkey,
+    amount_yes: u64,
+    amount_no: u64,
+) -> Instruction {
+    liquidity_ix(
+        provider,
+        market,
+        amount_yes,
+        amount_no,
+        Some(human_pda(&provider)),
+    )
+}
+
+fn liquidity_ix(
+    provider: Pubkey,
+    market: Pubkey,
+    amount_yes: u64,
+    amount_no: u64,
+    human_attestation: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::AddLiquidity {
+            market,
+            global_state: global_state_pda(),
+            liquidity_position: liquidity_pda(&provider, &market),
+            human_attestation,
+            bet: bet_pda(&provider, &market),
+            order_position: position_pda(&market, &provider),
+            order_book: order_book_pda(&market),
+            provider,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::AddLiquidity {
+            amount_yes,
+            amount_no,
+        }
+        .data(),
+    }
+}
+
+pub fn remove_liquidity_ix(provider: Pubkey, market: Pubkey, lp_tokens: u64) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::RemoveLiquidity {
+            market,
+            global_state: global_state_pda(),
+            liquidity_position: liquidity_pda(&provider, &market),
+            provider,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::RemoveLiquidity { lp_tokens }.data(),
+    }
+}
+
+pub fn resolve_market_ix(market: Pubkey, authority: Pubkey, outcome: bool) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ResolveMarket {
+            market,
+            global_state: global_state_pda(),
+            authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ResolveMarket { outcome }.data(),
+    }
+}
+
+pub fn propose_authority_transfer_ix(
+    market: Pubkey,
+    authority: Pubkey,
+    new_authority: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::UpdateMarket { market, authority }
+            .to_account_metas(None),
+        data: prediction_market::instruction::ProposeAuthorityTransfer { new_authority }.data(),
+    }
+}
+
+pub fn accept_authority_transfer_ix(market: Pubkey, pending_authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::AcceptAuthorityTransfer {
+            market,
+            pending_authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::AcceptAuthorityTransfer {}.data(),
+    }
+}
+
+pub fn delegate_resolver_ix(market: Pubkey, authority: Pubkey, resolver: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::UpdateMarket { market, authority }
+            .to_account_metas(None),
+        data: prediction_market::instruction::DelegateResolver { resolver }.data(),
+    }
+}
+
+pub fn set_close_trigger_ix(market: Pubkey, authority: Pubkey, trigger: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::UpdateMarket { market, authority }
+            .to_account_metas(None),
+        data: prediction_market::instruction::SetCloseTrigger { trigger }.data(),
+    }
+}
+
+pub fn close_betting_ix(market: Pubkey, closer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CloseBetting { market, closer }
+            .to_account_metas(None),
+        data: prediction_market::instruction::CloseBetting {}.data(),
+    }
+}
+
+pub fn set_resolvers_ix(
+    market: Pubkey,
+    authority: Pubkey,
+    resolvers: Vec<Pubkey>,
+    threshold: u8,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SetResolvers {
+            market,
+            resolver_set: resolver_set_pda(&market),
+            authority,
+            payer: authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SetResolvers {
+            resolvers,
+            threshold,
+        }
+        .data(),
+    }
+}
+
+pub fn approve_resolution_ix(market: Pubkey, resolver: Pubkey, outcome: bool) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ApproveResolution {
+            market,
+            resolver_set: resolver_set_pda(&market),
+            global_state: global_state_pda(),
+            resolver,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ApproveResolution { outcome }.data(),
+    }
+}
+
+pub fn resolve_meta_market_ix(market: Pubkey, parent_market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ResolveMetaMarket {
+            market,
+            parent_market,
+            global_state: global_state_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ResolveMetaMarket {}.data(),
+    }
+}
+
+pub fn resolve_condition_ix(market: Pubkey, condition_market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ResolveCondition {
+            market,
+            condition_market,
+            global_state: global_state_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ResolveCondition {}.data(),
+    }
+}
+
+pub fn resolve_with_oracle_ix(market: Pubkey, price_update: Pubkey, caller: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ResolveWithOracle {
+            market,
+            global_state: global_state_pda(),
+            config: config_pda(),
+            price_update,
+            caller,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ResolveWithOracle {}.data(),
+    }
+}
+
+pub fn claim_winnings_ix(user: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ClaimWinnings {
+            bet: bet_pda(&user, &market),
+            market,
+            global_state: global_state_pda(),
+            user_profile: profile_pda(&user),
+            user,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ClaimWinnings {}.data(),
+    }
+}
+
+/// Claims `user`'s bets on every market in `markets`; the bets are passed
+/// as `remaining_accounts` after their market.
+pub fn claim_many_ix(user: Pubkey, markets: &[Pubkey]) -> Instruction {
+    let mut accounts = prediction_market::accounts::ClaimMany {
+        user_profile: profile_pda(&user),
+        global_state: global_state_pda(),
+        user,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    for market in markets {
+        accounts.push(AccountMeta::new(*market, false));
+        accounts.push(AccountMeta::new(bet_pda(&user, market), false));
+    }
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts,
+        data: prediction_market::instruction::ClaimMany {}.data(),
+    }
+}
+
+pub fn resolve_oracle_fallback_ix(market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ResolveOracleFallback {
+            market,
+            global_state: global_state_pda(),
+            config: config_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ResolveOracleFallback {}.data(),
+    }
+}
+
+pub fn initialize_order_book_ix(market: Pubkey, authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::InitializeOrderBook {
+            market,
+            order_book: order_book_pda(&market),
+            authority,
+            payer: authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::InitializeOrderBook {}.data(),
+    }
+}
+
+pub fn place_order_ix(
+    owner: Pubkey,
+    market: Pubkey,
+    side: u8,
+    price: u64,
+    quantity: u64,
+    max_matches: u16,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::PlaceOrder {
+            market,
+            order_book: order_book_pda(&market),
+            position: position_pda(&market, &owner),
+            global_state: global_state_pda(),
+            human_attestation: None,
+            bet: bet_pda(&owner, &market),
+            liquidity_position: liquidity_pda(&owner, &market),
+            config: config_pda(),
+            treasury: treasury_pda(),
+            owner,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::PlaceOrder {
+            side,
+            price,
+            quantity,
+            max_matches,
+        }
+        .data(),
+    }
+}
+
+pub fn match_orders_ix(market: Pubkey, limit: u16) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::MatchOrders {
+            market,
+            order_book: order_book_pda(&market),
+            global_state: global_state_pda(),
+            treasury: treasury_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::MatchOrders { limit }.data(),
+    }
+}
+
+pub fn cancel_order_ix(owner: Pubkey, market: Pubkey, order_id: u64) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CancelOrder {
+            market,
+            order_book: order_book_pda(&market),
+            owner,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CancelOrder { order_id }.data(),
+    }
+}
+
+/// Settles queued fills; `owners` are the fills' owners in queue order,
+/// passed as their `position` accounts.
+pub fn consume_events_ix(market: Pubkey, owners: &[Pubkey], limit: u16) -> Instruction {
+    let mut accounts = prediction_market::accounts::ConsumeEvents {
+        order_book: order_book_pda(&market),
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        owners
+            .iter()
+            .map(|owner| AccountMeta::new(position_pda(&market, owner), false)),
+    );
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts,
+        data: prediction_market::instruction::ConsumeEvents { limit }.data(),
+    }
+}
+
+pub fn claim_position_ix(owner: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ClaimPosition {
+            market,
+            order_book: order_book_pda(&market),
+            position: position_pda(&market, &owner),
+            global_state: global_state_pda(),
+            owner,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ClaimPosition {}.data(),
+    }
+}
+
+pub fn close_order_book_ix(market: Pubkey, authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CloseOrderBook {
+            market,
+            order_book: order_book_pda(&market),
+            authority,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CloseOrderBook {}.data(),
+    }
+}
+
+pub fn split_position_ix(owner: Pubkey, market: Pubkey, sets: u64) -> Instruction {
+    split_ix(owner, market, sets, None)
+}
+
+/// `split_position` passing the owner's human attestation.
+pub fn split_human_position_ix(owner: Pubkey, market: Pubkey, sets: u64) -> Instruction {
+    split_ix(owner, market, sets, Some(human_pda(&owner)))
+}
+
+fn split_ix(
+    owner: Pubkey,
+    market: Pubkey,
+    sets: u64,
+    human_attestation: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SplitPosition {
+            market,
+            order_book: order_book_pda(&market),
+            position: position_pda(&market, &owner),
+            global_state: global_state_pda(),
+            human_attestation,
+            bet: bet_pda(&owner, &market),
+            liquidity_position: liquidity_pda(&owner, &market),
+            owner,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SplitPosition { sets }.data(),
+    }
+}
+
+pub fn merge_position_ix(owner: Pubkey, market: Pubkey, sets: u64) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::MergePosition {
+            market,
+            order_book: order_book_pda(&market),
+            position: position_pda(&market, &owner),
+            global_state: global_state_pda(),
+            owner,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::MergePosition { sets }.data(),
+    }
+}
+
+pub fn initialize_house_vault_ix(authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::InitializeHouseVault {
+            house_vault: house_vault_pda(),
+            config: config_pda(),
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::InitializeHouseVault {}.data(),
+    }
+}
+
+pub fn deposit_house_liquidity_ix(provider: Pubkey, amount: u64) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::DepositHouseLiquidity {
+            house_vault: house_vault_pda(),
+            house_deposit: house_deposit_pda(&provider),
+            provider,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::DepositHouseLiquidity { amount }.data(),
+    }
+}
+
+pub fn request_house_withdrawal_ix(provider: Pubkey, shares: u64) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::RequestHouseWithdrawal {
+            house_vault: house_vault_pda(),
+            house_deposit: house_deposit_pda(&provider),
+            provider,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::RequestHouseWithdrawal { shares }.data(),
+    }
+}
+
+pub fn withdraw_house_liquidity_ix(provider: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::WithdrawHouseLiquidity {
+            house_vault: house_vault_pda(),
+            house_deposit: house_deposit_pda(&provider),
+            global_state: global_state_pda(),
+            provider,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::WithdrawHouseLiquidity {}.data(),
+    }
+}
+
+/// Places parlay number `id` on `legs`, each a market and the outcome
+/// picked on it; the markets are passed as `remaining_accounts`.
+pub fn place_parlay_ix(
+    owner: Pubkey,
+    id: u64,
+    legs: &[(Pubkey, bool)],
+    stake: u64,
+    min_payout: u64,
+) -> Instruction {
+    let mut accounts = prediction_market::accounts::PlaceParlay {
+        house_vault: house_vault_pda(),
+        parlay: parlay_pda(id),
+        global_state: global_state_pda(),
+        config: config_pda(),
+        owner,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        legs.iter()
+            .map(|(market, _)| AccountMeta::new(*market, false)),
+    );
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts,
+        data: prediction_market::instruction::PlaceParlay {
+            outcomes: legs.iter().map(|(_, outcome)| *outcome).collect(),
+            stake,
+            min_payout,
+        }
+        .data(),
+    }
+}
+
+pub fn settle_parlay_ix(owner: Pubkey, id: u64, markets: &[Pubkey]) -> Instruction {
+    let mut accounts = prediction_market::accounts::SettleParlay {
+        parlay: parlay_pda(id),
+        house_vault: house_vault_pda(),
+        global_state: global_state_pda(),
+        owner,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        markets
+            .iter()
+            .map(|market| AccountMeta::new(*market, false)),
+    );
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts,
+        data: prediction_market::instruction::SettleParlay {}.data(),
+    }
+}
+
+pub fn claim_refund_ix(user: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ClaimRefund {
+            bet: bet_pda(&user, &market),
+            market,
+            global_state: global_state_pda(),
+            user,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ClaimRefund {}.data(),
+    }
+}
+
+pub fn close_bet_ix(user: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CloseBet {
+            bet: bet_pda(&user, &market),
+            market,
+            user_profile: profile_pda(&user),
+            user,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CloseBet {}.data(),
+    }
+}
+
+pub fn record_prediction_ix(user: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::RecordPrediction {
+            bet: bet_pda(&user, &market),
+            market,
+            user_profile: profile_pda(&user),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::RecordPrediction {}.data(),
+    }
+}
+
+pub fn finalize_market_ix(market: Pubkey, authority: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::FinalizeMarket {
+            market,
+            authority,
+            treasury: treasury_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::FinalizeMarket {}.data(),
+    }
+}
+
+pub fn report_unclaimed_ix(market: Pubkey, bets: &[Pubkey]) -> Instruction {
+    let mut accounts =
+        prediction_market::accounts::ReportUnclaimed { market }.to_account_metas(None);
+    accounts.extend(
+        bets.iter()
+            .map(|bet| AccountMeta::new_readonly(*bet, false)),
+    );
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts,
+        data: prediction_market::instruction::ReportUnclaimed {}.data(),
+    }
+}
+
+pub fn settle_unclaimed_ix(market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::SettleUnclaimed {
+            market,
+            global_state: global_state_pda(),
+            treasury: treasury_pda(),
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::SettleUnclaimed {}.data(),
+    }
+}
+
+pub fn claim_redistribution_ix(user: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ClaimRedistribution {
+            bet: bet_pda(&user, &market),
+            market,
+            global_state: global_state_pda(),
+            user_profile: profile_pda(&user),
+            user,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ClaimRedistribution {}.data(),
+    }
+}
+
+pub fn vote_eligibility_ix(voter: Pubkey, market: Pubkey, approve: bool) -> Instruction {
+    eligibility_ix(voter, market, approve, None)
+}
+
+/// `vote_eligibility` passing the voter's human attestation.
+pub fn vote_human_eligibility_ix(voter: Pubkey, market: Pubkey, approve: bool) -> Instruction {
+    eligibility_ix(voter, market, approve, Some(human_pda(&voter)))
+}
+
+fn eligibility_ix(
+    voter: Pubkey,
+    market: Pubkey,
+    approve: bool,
+    human_attestation: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::VoteEligibility {
+            vote: eligibility_vote_pda(&market, &voter),
+            market,
+            voter_profile: profile_pda(&voter),
+            global_state: global_state_pda(),
+            human_attestation,
+            voter,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::VoteEligibility {
+            approve,
+            reason_hash: [0; 32],
+        }
+        .data(),
+    }
+}
+
+pub fn tally_eligibility_ix(market: Pubkey, depositor: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::TallyEligibility { market, depositor }
+            .to_account_metas(None),
+        data: prediction_market::instruction::TallyEligibility {}.data(),
+    }
+}
+
+pub fn close_eligibility_vote_ix(voter: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::CloseEligibilityVote {
+            vote: eligibility_vote_pda(&market, &voter),
+            market,
+            voter,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::CloseEligibilityVote {}.data(),
+    }
+}
+
+pub fn report_market_ix(
+    reporter: Pubkey,
+    market: Pubkey,
+    report_type: u8,
+    reason_hash: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ReportMarket {
+            report: report_pda(&market, &reporter),
+            market,
+            reporter,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ReportMarket {
+            report_type,
+            reason_hash,
+        }
+        .data(),
+    }
+}
+
+pub fn moderate_report_ix(
+    moderator: Pubkey,
+    market: Pubkey,
+    reporter: Pubkey,
+    action: u8,
+) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::ModerateReport {
+            report: report_pda(&market, &reporter),
+            market,
+            global_state: global_state_pda(),
+            moderator,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::ModerateReport { action }.data(),
+    }
+}
+
+pub fn restore_market_ix(moderator: Pubkey, market: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::RestoreMarket {
+            market,
+            global_state: global_state_pda(),
+            moderator,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::RestoreMarket {}.data(),
+    }
+}
+
+pub fn migrate_market_ix(payer: Pubkey, market: Pubkey, bets: &[Pubkey]) -> Instruction {
+    let mut accounts = prediction_market::accounts::MigrateMarket {
+        market,
+        payer,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(bets.iter().map(|bet| AccountMeta::new(*bet, false)));
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts,
+        data: prediction_market::instruction::MigrateMarket {}.data(),
+    }
+}
+
+pub fn migrate_global_state_ix(payer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: prediction_market::ID,
+        accounts: prediction_market::accounts::MigrateGlobalState {
+            global_state: global_state_pda(),
+            payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: prediction_market::instruction::MigrateGlobalState {}.data(),
+    }
+}
@@ -0,0 +1,553 @@
+//! Synchronous test harness over `solana-program-test`.
+//!
+//! The program is registered as a native builtin (`processor!`), so the bank
+//! runs it on the host without `cargo build-sbf`, while the system program,
+//! SPL Token, sysvars, signature checks and the runtime's lamport, ownership
+//! and rent rules are the real ones. The SPL Token program is the BPF build
+//! `solana-program-test` ships with.
+//!
+//! Tests stay synchronous: `Svm` blocks on the banks client from a Tokio
+//! runtime it owns. Fees are paid by the context payer, so the lamport
+//! balances tests assert on only move by what the program does. Every
+//! transaction carries a distinct compute unit limit so that repeating an
+//! instruction never trips the duplicate signature check.
+//!
+//! `solana-program-test` 1.18 never frees a bank it starts (the program
+//! cache holds on to the bank forks that own it), and each leaked bank keeps
+//! its accounts index threads polling. Starting one per test makes the suite
+//! slower with every test, so a dropped `Svm` returns its bank to a pool and
+//! the next one wipes every account the previous test wrote before reusing
+//! it.
+//!
+//! Anything that depends on BPF-specific behaviour (compute units, stack
+//! and heap limits) is out of scope and belongs in a deployment check
+//! against a local validator.
+
+#![allow(dead_code)]
+
+pub mod ix;
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Mutex, Once};
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::Instruction,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    system_program, sysvar,
+};
+use anchor_lang::{AccountDeserialize, AccountSerialize, Discriminator};
+use anchor_spl::token::spl_token::{
+    self,
+    state::{Account as TokenAccountState, AccountState, Mint as MintState},
+};
+use base64::Engine;
+use pyth_solana_receiver_sdk::price_update::{PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use tokio::runtime::Runtime;
+
+/// Unix timestamp the clock starts at for every new `Svm`.
+pub const GENESIS_TIMESTAMP: i64 = 1_700_000_000;
+
+/// Pyth receiver program that owns `PriceUpdateV2` accounts.
+pub const PYTH_RECEIVER_ID: Pubkey = pyth_solana_receiver_sdk::ID;
+
+/// Compute unit limit of the first transaction; each later one asks for one
+/// unit less.
+const COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Prefix of the log lines `LogData` writes events to.
+const EVENT_LOG_PREFIX: &str = "Program log: Program data: ";
+
+/// `solana-program-test`'s syscall stubs, except that `sol_log_data` writes
+/// to the transaction log. In native mode 1.18 prints it to stdout instead,
+/// which would lose every event the program emits.
+struct LogData(Box<dyn SyscallStubs>);
+
+impl LogData {
+    /// Wraps the stubs the first started `ProgramTest` installed.
+    fn install() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            struct Placeholder;
+            impl SyscallStubs for Placeholder {}
+            let stubs = set_syscall_stubs(Box::new(Placeholder));
+            set_syscall_stubs(Box::new(LogData(stubs)));
+        });
+    }
+}
+
+impl SyscallStubs for LogData {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| base64::engine::general_purpose::STANDARD.encode(field))
+            .collect();
+        self.0
+            .sol_log(&format!("Program data: {}", fields.join(" ")))
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> anchor_lang::solana_program::entrypoint::ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+/// Adapts Anchor's `entry`, whose accounts share the slice's lifetime, to the
+/// builtin signature. The copy of the infos is leaked to satisfy that
+/// lifetime; the bank serializes the accounts back after the call either
+/// way.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> anchor_lang::solana_program::entrypoint::ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    prediction_market::entry(program_id, accounts, data)
+}
+
+/// A started bank and the runtime its banks client runs on.
+struct Bank {
+    runtime: Runtime,
+    context: ProgramTestContext,
+    /// Every account a test wrote to, wiped before the bank is reused
+    written: HashSet<Pubkey>,
+    transactions: u32,
+}
+
+impl Bank {
+    fn start() -> Self {
+        // `ProgramTest` turns on debug logging of every instruction unless
+        // `RUST_LOG` says otherwise.
+        static QUIET: Once = Once::new();
+        QUIET.call_once(|| {
+            if std::env::var_os("RUST_LOG").is_none() {
+                std::env::set_var("RUST_LOG", "error");
+            }
+        });
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let mut program_test = ProgramTest::new(
+            "prediction_market",
+            prediction_market::ID,
+            processor!(process_instruction),
+        );
+        program_test.prefer_bpf(false);
+        let context = runtime.block_on(program_test.start_with_context());
+        LogData::install();
+        Self {
+            runtime,
+            context,
+            written: HashSet::new(),
+            transactions: 0,
+        }
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    fn banks_client(&self) -> BanksClient {
+        self.context.banks_client.clone()
+    }
+
+    fn set_account(&mut self, key: Pubkey, account: &AccountSharedData) {
+        self.written.insert(key);
+        self.context.set_account(&key, account);
+    }
+
+    /// Deletes every account written since the bank started or was last
+    /// wiped, leaving programs and sysvars in place.
+    fn wipe(&mut self) {
+        for key in std::mem::take(&mut self.written) {
+            let account = self.block_on(self.banks_client().get_account(key)).unwrap();
+            if account.is_some_and(|account| !account.executable && account.owner != sysvar::ID) {
+                self.context
+                    .set_account(&key, &AccountSharedData::default());
+            }
+        }
+    }
+}
+
+/// Banks of dropped `Svm`s, waiting to be reused.
+static BANKS: Mutex<Vec<Bank>> = Mutex::new(Vec::new());
+
+pub struct Svm {
+    bank: Option<Bank>,
+    keypairs: HashMap<Pubkey, Keypair>,
+    logs: Vec<String>,
+}
+
+impl Default for Svm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Svm {
+    fn drop(&mut self) {
+        if let Some(bank) = self.bank.take() {
+            BANKS
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .push(bank);
+        }
+    }
+}
+
+impl Svm {
+    pub fn new() -> Self {
+        let pooled = BANKS
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .pop();
+        let bank = match pooled {
+            Some(mut bank) => {
+                bank.wipe();
+                bank
+            }
+            None => Bank::start(),
+        };
+        let svm = Self {
+            bank: Some(bank),
+            keypairs: HashMap::new(),
+            logs: Vec::new(),
+        };
+        svm.set_clock(Clock {
+            slot: 1,
+            unix_timestamp: GENESIS_TIMESTAMP,
+            ..Clock::default()
+        });
+        svm
+    }
+
+    fn bank(&self) -> &Bank {
+        self.bank.as_ref().unwrap()
+    }
+
+    fn bank_mut(&mut self) -> &mut Bank {
+        self.bank.as_mut().unwrap()
+    }
+
+    fn clock(&self) -> Clock {
+        let bank = self.bank();
+        bank.block_on(bank.banks_client().get_sysvar::<Clock>())
+            .unwrap()
+    }
+
+    fn set_clock(&self, clock: Clock) {
+        self.bank().context.set_sysvar(&clock);
+    }
+
+    fn rent(&self) -> Rent {
+        self.bank().context.genesis_config().rent
+    }
+
+    pub fn now(&self) -> i64 {
+        self.clock().unix_timestamp
+    }
+
+    /// Moves the clock to `unix_timestamp`, advancing the slot alongside it.
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock = self.clock();
+        let elapsed = unix_timestamp.saturating_sub(clock.unix_timestamp).max(0) as u64;
+        clock.slot += elapsed.max(1) * 2;
+        clock.unix_timestamp = unix_timestamp;
+        self.set_clock(clock);
+    }
+
+    /// Returns the address of a new keypair the harness can sign with,
+    /// without funding it.
+    pub fn signer(&mut self) -> Pubkey {
+        let keypair = Keypair::new();
+        let key = keypair.pubkey();
+        self.keypairs.insert(key, keypair);
+        key
+    }
+
+    pub fn airdrop(&mut self, key: &Pubkey, lamports: u64) {
+        let mut account = self.account(key).unwrap_or(Account {
+            owner: system_program::ID,
+            ..Account::default()
+        });
+        account.lamports += lamports;
+        self.set_account(*key, account);
+    }
+
+    /// Returns a fresh system account funded with `lamports`.
+    pub fn funded_account(&mut self, lamports: u64) -> Pubkey {
+        let key = self.signer();
+        self.airdrop(&key, lamports);
+        key
+    }
+
+    fn set_account(&mut self, key: Pubkey, account: Account) {
+        self.bank_mut()
+            .set_account(key, &AccountSharedData::from(account));
+    }
+
+    fn set_owned_account(&mut self, key: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        let account = Account {
+            lamports: self.rent().minimum_balance(data.len()),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: u64::MAX,
+        };
+        self.set_account(key, account);
+    }
+
+    /// Installs raw, rent-exempt program account data, e.g. a fixture
+    /// written by an older program version.
+    pub fn set_program_account(&mut self, key: Pubkey, data: Vec<u8>) {
+        self.set_owned_account(key, prediction_market::ID, data);
+    }
+
+    fn set_token_state<T: Pack>(&mut self, key: Pubkey, state: T) {
+        let mut data = vec![0; T::LEN];
+        T::pack(state, &mut data).unwrap();
+        self.set_owned_account(key, spl_token::ID, data);
+    }
+
+    /// Creates an SPL mint with no mint authority.
+    pub fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        let mint = Pubkey::new_unique();
+        self.set_token_state(
+            mint,
+            MintState {
+                decimals,
+                is_initialized: true,
+                ..MintState::default()
+            },
+        );
+        mint
+    }
+
+    /// Creates a token account of `mint` owned by `owner` holding `amount`.
+    pub fn create_token_account(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+        let account = Pubkey::new_unique();
+        self.set_token_state(
+            account,
+            TokenAccountState {
+                mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..TokenAccountState::default()
+            },
+        );
+        account
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        let account = self
+            .account(key)
+            .unwrap_or_else(|| panic!("account {key} does not exist"));
+        TokenAccountState::unpack(&account.data).unwrap().amount
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<Account> {
+        let bank = self.bank();
+        bank.block_on(bank.banks_client().get_account(*key))
+            .unwrap()
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let account = self
+            .account(key)
+            .unwrap_or_else(|| panic!("account {key} does not exist"));
+        T::try_deserialize(&mut account.data.as_slice()).expect("account deserializes")
+    }
+
+    /// Overwrites a program account's state, keeping its size.
+    pub fn store<T: AccountSerialize>(&mut self, key: &Pubkey, state: &T) {
+        let mut account = self
+            .account(key)
+            .unwrap_or_else(|| panic!("account {key} does not exist"));
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.set_account(*key, account);
+    }
+
+    /// Writes a `PriceUpdateV2` account owned by the Pyth receiver, as if it had
+    /// been posted and fully verified at the current time.
+    pub fn set_price_update(
+        &mut self,
+        key: Pubkey,
+        feed_id: [u8; 32],
+        price: i64,
+        publish_time: i64,
+    ) {
+        let update = PriceUpdateV2 {
+            write_authority: Pubkey::new_unique(),
+            verification_level: VerificationLevel::Full,
+            price_message: PriceFeedMessage {
+                feed_id,
+                price,
+                conf: 0,
+                exponent: -8,
+                publish_time,
+                prev_publish_time: publish_time - 1,
+                ema_price: price,
+                ema_conf: 0,
+            },
+            posted_slot: self.clock().slot,
+        };
+        let mut data = Vec::new();
+        update.try_serialize(&mut data).unwrap();
+        self.set_owned_account(key, PYTH_RECEIVER_ID, data);
+    }
+
+    /// Events of type `T` emitted by the last processed instruction.
+    pub fn events<T: AnchorDeserialize + Discriminator>(&self) -> Vec<T> {
+        self.logs
+            .iter()
+            .filter_map(|log| log.strip_prefix(EVENT_LOG_PREFIX))
+            .flat_map(|fields| fields.split(' '))
+            .map(|field| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(field)
+                    .expect("event is base64")
+            })
+            .filter(|data| data.starts_with(&T::DISCRIMINATOR))
+            .map(|data| T::deserialize(&mut &data[8..]).expect("event deserializes"))
+            .collect()
+    }
+
+    /// Executes a single instruction in its own transaction.
+    ///
+    /// `signers` lists the accounts that signed the transaction; every account
+    /// the instruction marks as a signer must appear in it and must come from
+    /// `signer` or `funded_account`. Errors the program returns are handed
+    /// back, while a transaction the runtime rejects outright, e.g. for
+    /// breaking its lamport or rent rules, panics.
+    pub fn process(
+        &mut self,
+        ix: Instruction,
+        signers: &[Pubkey],
+    ) -> std::result::Result<(), ProgramError> {
+        assert_eq!(ix.program_id, prediction_market::ID);
+        let bank = self.bank.as_mut().unwrap();
+        let mut keypairs: Vec<&Keypair> = vec![&bank.context.payer];
+        for meta in ix.accounts.iter().filter(|meta| meta.is_signer) {
+            assert!(
+                signers.contains(&meta.pubkey),
+                "{} must sign the transaction",
+                meta.pubkey
+            );
+            let keypair = self
+                .keypairs
+                .get(&meta.pubkey)
+                .unwrap_or_else(|| panic!("no keypair for signer {}", meta.pubkey));
+            if !keypairs.iter().any(|signer| signer.pubkey() == meta.pubkey) {
+                keypairs.push(keypair);
+            }
+        }
+        bank.written.extend(
+            ix.accounts
+                .iter()
+                .filter(|meta| meta.is_writable)
+                .map(|meta| meta.pubkey),
+        );
+
+        let limit = ComputeBudgetInstruction::set_compute_unit_limit(
+            COMPUTE_UNIT_LIMIT - bank.transactions,
+        );
+        bank.transactions += 1;
+        let blockhash = bank
+            .block_on(bank.banks_client().get_latest_blockhash())
+            .unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[limit, ix],
+            Some(&bank.context.payer.pubkey()),
+            &keypairs,
+            blockhash,
+        );
+        let processed = bank
+            .block_on(
+                bank.banks_client()
+                    .process_transaction_with_metadata(transaction),
+            )
+            .unwrap();
+        self.logs = processed
+            .metadata
+            .map(|metadata| metadata.log_messages)
+            .unwrap_or_default();
+
+        match processed.result {
+            Ok(()) => Ok(()),
+            Err(TransactionError::InstructionError(1, error)) => Err(ProgramError::try_from(error)
+                .unwrap_or_else(|error| {
+                    panic!(
+                        "runtime rejected the instruction: {error:?}\n{:#?}",
+                        self.logs
+                    )
+                })),
+            Err(error) => panic!(
+                "runtime rejected the transaction: {error}\n{:#?}",
+                self.logs
+            ),
+        }
+    }
+}